@@ -0,0 +1,166 @@
+use bevy::prelude::*;
+use bevy_rapier2d::physics::{RapierConfiguration, RigidBodyHandleComponent};
+use bevy_rapier2d::rapier::dynamics::RigidBodySet;
+use std::collections::HashMap;
+use std::time::Duration;
+use game_data::*;
+use game_data::Direction as Direction;
+use crate::*;
+
+
+/// size of a full charge bar in pixels
+const CHARGE_BAR: (f32, f32) = (PLAYER_SIZE, 4.0);
+
+pub fn load_sprite_sheets(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+) {
+    let path = asset_path(SPRITE_MANIFEST);
+    let manifest = match std::fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| ron::from_str::<SpriteManifest>(&text).map_err(|e| e.to_string()))
+    {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            warn!("could not read {}: {}, using flat sprites", path.display(), e);
+            commands.insert_resource(SpriteSheets::default());
+            return;
+        }
+    };
+
+    let mut sheets = HashMap::new();
+    for (name, definition) in manifest.sheets.into_iter() {
+        if !asset_path(&definition.texture).exists() {
+            warn!("texture {} for sheet {} is missing, using a flat sprite", definition.texture, name);
+            continue;
+        }
+        let tile_size = Vec2::new(definition.tile_size.0, definition.tile_size.1);
+        let texture = asset_server.load(definition.texture.as_str());
+        let atlas = TextureAtlas::from_grid(texture, tile_size, definition.columns, definition.rows);
+        debug!("loaded sprite sheet {}", name);
+        sheets.insert(name, SpriteSheet {
+            atlas: texture_atlases.add(atlas),
+            tile_size,
+            mirror_west: definition.mirror_west,
+            animations: definition.animations,
+        });
+    }
+    commands.insert_resource(SpriteSheets(sheets));
+}
+
+pub fn create_materials(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    commands.insert_resource(GameMaterials::new(&mut materials));
+}
+
+/// picks each entity's animation from its state and advances the frames
+pub fn animate_sprites(
+    sheets: Res<SpriteSheets>,
+    bodies: Res<RigidBodySet>,
+    rapier_config: Res<RapierConfiguration>,
+    time: Res<Time>,
+    mut shots: EventReader<ShotFired>,
+    mut query: Query<(
+        Entity,
+        &mut SpriteAnimation,
+        &mut TextureAtlasSprite,
+        Option<&Direction>,
+        Option<&RigidBodyHandleComponent>,
+        Option<&Dying>,
+        Option<&Hurt>,
+        Option<&Downed>,
+    )>,
+) {
+    let units = Units::new(&rapier_config);
+    let delta = Duration::from_secs_f32(time.delta_seconds());
+    for shot in shots.iter() {
+        if let Ok((_, mut animation, ..)) = query.get_mut(shot.shooter) {
+            animation.shooting = Some(Timer::from_seconds(0.2, false));
+        }
+    }
+
+    for (_, mut animation, mut sprite, direction, rigid_body_handle, dying, hurt, downed) in query.iter_mut() {
+        let sheet = match sheets.0.get(&animation.sheet) {
+            Some(sheet) => sheet,
+            None => continue,
+        };
+        let velocity = rigid_body_handle
+            .and_then(|handle| bodies.get(handle.handle()))
+            .map(|rb| units.velocity_to_world(PhysicsVelocity::from_vector(rb.linvel())).0);
+
+        let mut shooting = false;
+        if let Some(timer) = animation.shooting.as_mut() {
+            timer.tick(delta);
+            shooting = !timer.finished();
+        }
+        let state = pick_state(dying.is_some() || downed.is_some(), hurt.is_some(), shooting, velocity);
+
+        let definition = match sheet.animations.get(&state).or_else(|| sheet.animations.get(&AnimationState::Idle)) {
+            Some(definition) => definition,
+            None => continue,
+        };
+        if state != animation.state {
+            animation.state = state;
+            animation.frame = 0;
+            animation.timer = Timer::from_seconds(1.0 / definition.fps.max(0.1), true);
+        }
+
+        animation.timer.tick(delta);
+        for _ in 0..animation.timer.times_finished() {
+            if animation.frame + 1 < definition.frames {
+                animation.frame += 1;
+            } else if definition.looping {
+                animation.frame = 0;
+            }
+        }
+
+        let facing = pick_facing(direction.copied(), velocity);
+        let first = facing
+            .and_then(|facing| definition.facing.get(&facing))
+            .copied()
+            .unwrap_or(definition.first);
+        sprite.index = (first + animation.frame) as u32;
+        if sheet.mirror_west {
+            if let Some(facing) = facing {
+                sprite.flip_x = matches!(facing, Direction::West | Direction::NorthWest | Direction::Southwest);
+            }
+        }
+    }
+}
+
+/// Gives every player with a weapon a charge bar and keeps it under them, as wide as the charge.
+pub fn show_charge(
+    mut commands: Commands,
+    materials: Res<GameMaterials>,
+    added: Query<Entity, Added<FireCooldown>>,
+    players: Query<(&Transform, &FireCooldown, Option<&Downed>), Without<ChargeIndicator>>,
+    mut indicators: Query<(Entity, &ChargeIndicator, &mut Transform, &mut Visible)>,
+) {
+    for owner in added.iter() {
+        commands
+            .spawn_bundle(SpriteBundle {
+                material: materials.charge.clone(),
+                sprite: Sprite::new(Vec2::new(CHARGE_BAR.0, CHARGE_BAR.1)),
+                visible: Visible { is_visible: false, is_transparent: false },
+                ..Default::default()
+            })
+            .insert(ChargeIndicator { owner });
+    }
+
+    for (entity, indicator, mut transform, mut visible) in indicators.iter_mut() {
+        let (player, cooldown, downed) = match players.get(indicator.owner) {
+            Ok(player) => player,
+            Err(_) => {
+                commands.entity(entity).despawn();
+                continue;
+            }
+        };
+        let charge = cooldown.charge();
+        visible.is_visible = charge > 0.0 && downed.is_none();
+        transform.translation = player.translation + Vec3::new(0.0, -(PLAYER_SIZE + CHARGE_BAR.1) / 2.0 - 2.0, 1.0);
+        transform.scale = Vec3::new(charge, 1.0, 1.0);
+    }
+}
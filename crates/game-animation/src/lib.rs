@@ -0,0 +1,263 @@
+use bevy::prelude::*;
+use bevy::ecs::system::EntityCommands;
+use bevy_rapier2d::physics::RapierConfiguration;
+use serde::Deserialize;
+use std::collections::HashMap;
+use game_data::*;
+use game_data::Direction as Direction;
+use animation_handler::*;
+mod animation_handler;
+
+
+/// Sprite sheets and frame animations for player, enemies and bullets.
+/// Art is described in `assets/sprites.ron`; anything missing from it is drawn as a flat rectangle.
+pub struct AnimationPlugin;
+
+impl Plugin for AnimationPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_startup_system_to_stage(StartupStage::PreStartup, load_sprite_sheets.system())
+            .add_startup_system_to_stage(StartupStage::PreStartup, create_materials.system())
+            .add_system(animate_sprites.system())
+            .add_system(show_charge.system());
+    }
+}
+
+/// relative to the asset folder, see `asset_path`
+pub const SPRITE_MANIFEST: &str = "sprites.ron";
+/// below this speed (pixels per second) an entity counts as standing still
+const WALK_THRESHOLD: f32 = 10.0;
+
+#[derive(Deserialize)]
+pub struct SpriteManifest {
+    pub sheets: HashMap<String, SheetDefinition>,
+}
+
+#[derive(Deserialize)]
+pub struct SheetDefinition {
+    /// path relative to the assets folder
+    pub texture: String,
+    pub tile_size: (f32, f32),
+    pub columns: usize,
+    pub rows: usize,
+    /// frames are drawn facing east, mirror them when facing west
+    #[serde(default)]
+    pub mirror_west: bool,
+    pub animations: HashMap<AnimationState, AnimationDefinition>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct AnimationDefinition {
+    pub first: usize,
+    pub frames: usize,
+    pub fps: f32,
+    #[serde(default)]
+    pub looping: bool,
+    /// first frame per facing direction, falls back to `first`
+    #[serde(default)]
+    pub facing: HashMap<Direction, usize>,
+}
+
+pub struct SpriteSheet {
+    pub atlas: Handle<TextureAtlas>,
+    pub tile_size: Vec2,
+    pub mirror_west: bool,
+    pub animations: HashMap<AnimationState, AnimationDefinition>,
+}
+
+/// every sheet from the manifest whose texture exists, keyed by name ("player", "enemy", "bullet")
+#[derive(Default)]
+pub struct SpriteSheets(pub HashMap<String, SpriteSheet>);
+
+/// Flat colours for entities without sprite art. Created once so spawning never adds assets.
+pub struct GameMaterials {
+    pub player: Handle<ColorMaterial>,
+    pub enemy: Handle<ColorMaterial>,
+    pub bullet: Handle<ColorMaterial>,
+    pub bomb: Handle<ColorMaterial>,
+    pub charge: Handle<ColorMaterial>,
+    pub pickup: Handle<ColorMaterial>,
+    pub xp: Handle<ColorMaterial>,
+}
+
+impl GameMaterials {
+    pub fn new(materials: &mut Assets<ColorMaterial>) -> Self {
+        GameMaterials {
+            player: materials.add(Color::WHITE.into()),
+            enemy: materials.add(Color::RED.into()),
+            bullet: materials.add(Color::YELLOW.into()),
+            bomb: materials.add(Color::DARK_GRAY.into()),
+            charge: materials.add(Color::ORANGE.into()),
+            pickup: materials.add(Color::GREEN.into()),
+            xp: materials.add(Color::CYAN.into()),
+        }
+    }
+}
+
+/// bar under `owner` that fills up while a shot charges
+pub struct ChargeIndicator {
+    pub owner: Entity,
+}
+
+pub struct SpriteAnimation {
+    pub sheet: String,
+    pub state: AnimationState,
+    pub frame: usize,
+    pub timer: Timer,
+    /// set when the entity fires, keeps the shoot animation up for a moment
+    pub shooting: Option<Timer>,
+}
+
+impl SpriteAnimation {
+    pub fn new(sheet: &str) -> Self {
+        SpriteAnimation {
+            sheet: sheet.to_owned(),
+            state: AnimationState::Idle,
+            frame: 0,
+            timer: Timer::from_seconds(0.1, true),
+            shooting: None,
+        }
+    }
+}
+
+/// The animation an entity with `velocity` in pixels per second should show. Dying (or downed)
+/// beats hurt, hurt beats shooting, shooting beats walking.
+pub fn pick_state(dying: bool, hurt: bool, shooting: bool, velocity: Option<Vec2>) -> AnimationState {
+    if dying {
+        AnimationState::Die
+    } else if hurt {
+        AnimationState::Hurt
+    } else if shooting {
+        AnimationState::Shoot
+    } else if velocity.map_or(false, |v| v.length() > WALK_THRESHOLD) {
+        AnimationState::Walk
+    } else {
+        AnimationState::Idle
+    }
+}
+
+/// The way an entity faces: its aim if it has one, otherwise east or west as it moves
+pub fn pick_facing(direction: Option<Direction>, velocity: Option<Vec2>) -> Option<Direction> {
+    direction.or_else(|| velocity.and_then(|v| {
+        if v.x > WALK_THRESHOLD {
+            Some(Direction::East)
+        } else if v.x < -WALK_THRESHOLD {
+            Some(Direction::West)
+        } else {
+            None
+        }
+    }))
+}
+
+/// Spawns an entity drawn from `sheet` at `size` pixels. Without art for `sheet` a flat
+/// rectangle using `fallback` is spawned instead, so gameplay never depends on the manifest.
+pub fn spawn_sprite<'a, 'b>(
+    commands: &'b mut Commands<'a>,
+    sheets: &SpriteSheets,
+    sheet: &str,
+    translation: Vec3,
+    size: Vec2,
+    fallback: Handle<ColorMaterial>,
+) -> EntityCommands<'a, 'b> {
+    match sheets.0.get(sheet) {
+        Some(sprite_sheet) => {
+            let scale = size / sprite_sheet.tile_size;
+            let mut entity_commands = commands.spawn_bundle(SpriteSheetBundle {
+                texture_atlas: sprite_sheet.atlas.clone(),
+                transform: Transform {
+                    translation,
+                    scale: Vec3::new(scale.x, scale.y, 1.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            });
+            entity_commands.insert(SpriteAnimation::new(sheet));
+            entity_commands
+        },
+        None => {
+            commands.spawn_bundle(SpriteBundle {
+                material: fallback,
+                transform: Transform::from_translation(translation),
+                sprite: Sprite::new(size),
+                ..Default::default()
+            })
+        }
+    }
+}
+
+/// `spawn_sprite` when the art and materials exist, otherwise a bare `Transform`. Lets gameplay
+/// run headless, without `AnimationPlugin`.
+pub fn spawn_visual<'a, 'b>(
+    commands: &'b mut Commands<'a>,
+    visuals: Option<(&SpriteSheets, &GameMaterials)>,
+    sheet: &str,
+    position: WorldPos,
+    size: f32,
+    material: impl Fn(&GameMaterials) -> Handle<ColorMaterial>,
+) -> EntityCommands<'a, 'b> {
+    let translation = position.0.extend(0.0);
+    match visuals {
+        Some((sheets, materials)) => spawn_sprite(commands, sheets, sheet, translation, Vec2::splat(size), material(materials)),
+        None => {
+            let mut entity_commands = commands.spawn();
+            entity_commands
+                .insert(Transform::from_translation(translation))
+                .insert(GlobalTransform::default());
+            entity_commands
+        }
+    }
+}
+
+/// Spawns one player as the loadout's character, equipped with its weapon and bonuses.
+pub fn spawn_player<'a, 'b>(
+    commands: &'b mut Commands<'a>,
+    rapier_config: &RapierConfiguration,
+    visuals: Option<(&SpriteSheets, &GameMaterials)>,
+    config: &GameConfig,
+    loadout: Option<&Loadout>,
+    id: PlayerId,
+    position: WorldPos,
+) -> EntityCommands<'a, 'b> {
+    let mut bundle = PlayerBundle::new(rapier_config, config, id, position);
+    if let Some(loadout) = loadout {
+        loadout.equip(config, &mut bundle.stats, &mut bundle.player, &mut bundle.cooldown, &mut bundle.health);
+    }
+    let character = loadout.and_then(|loadout| loadout.character.as_ref());
+    let sheet = character.map_or("player", |character| character.sprite.as_str());
+    let mut entity_commands = spawn_visual(commands, visuals, sheet, position, PLAYER_SIZE, |m| m.player.clone());
+    entity_commands.insert_bundle(bundle);
+    if let Some(character) = character {
+        entity_commands.insert(Character(character.name.clone()));
+        if let Some(regeneration) = character.regeneration() {
+            entity_commands.insert(regeneration);
+        }
+    }
+    entity_commands
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_most_important_state_wins() {
+        let walking = Some(Vec2::new(50.0, 0.0));
+        assert_eq!(pick_state(true, true, true, walking), AnimationState::Die);
+        assert_eq!(pick_state(false, true, true, walking), AnimationState::Hurt);
+        assert_eq!(pick_state(false, false, true, walking), AnimationState::Shoot);
+        assert_eq!(pick_state(false, false, false, walking), AnimationState::Walk);
+        // drifting slower than the threshold is standing still
+        assert_eq!(pick_state(false, false, false, Some(Vec2::new(5.0, 0.0))), AnimationState::Idle);
+        assert_eq!(pick_state(false, false, false, None), AnimationState::Idle);
+    }
+
+    #[test]
+    fn entities_face_their_aim_or_where_they_move() {
+        let west = Some(Vec2::new(-50.0, 20.0));
+        assert_eq!(pick_facing(Some(Direction::North), west), Some(Direction::North));
+        assert_eq!(pick_facing(None, west), Some(Direction::West));
+        assert_eq!(pick_facing(None, Some(Vec2::new(50.0, 0.0))), Some(Direction::East));
+        assert_eq!(pick_facing(None, Some(Vec2::new(0.0, 50.0))), None);
+        assert_eq!(pick_facing(None, None), None);
+    }
+}
@@ -0,0 +1,210 @@
+use bevy::prelude::*;
+use bevy_kira_audio::{Audio, AudioChannel};
+use rand::Rng;
+use std::collections::HashMap;
+use std::time::Duration;
+use game_data::*;
+use crate::*;
+
+
+/// timers for every sound instance still occupying a slot
+#[derive(Default)]
+pub struct ActiveSounds(pub HashMap<SoundEffect, Vec<Timer>>);
+
+/// music alternates between two channels so the old track can fade out under the new one
+pub struct Crossfade {
+    pub current: usize,
+    pub timer: Option<Timer>,
+    pub channels: [AudioChannel; 2],
+}
+
+impl Default for Crossfade {
+    fn default() -> Self {
+        Crossfade {
+            current: 0,
+            timer: None,
+            channels: [AudioChannel::new("music_a".to_owned()), AudioChannel::new("music_b".to_owned())],
+        }
+    }
+}
+
+pub fn load_sounds(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut music_state: ResMut<MusicState>,
+) {
+    let definitions = [
+        (SoundEffect::Shot, "sounds/shot.ogg", SoundCategory::Weapon, 0.1, 4, 0.2),
+        (SoundEffect::EnemyHit, "sounds/enemy_hit.ogg", SoundCategory::Impact, 0.15, 4, 0.25),
+        (SoundEffect::EnemyKilled, "sounds/enemy_killed.ogg", SoundCategory::Impact, 0.1, 3, 0.5),
+        (SoundEffect::PlayerHurt, "sounds/player_hurt.ogg", SoundCategory::Player, 0.05, 1, 0.4),
+        (SoundEffect::WaveStart, "sounds/wave_start.ogg", SoundCategory::Interface, 0.0, 1, 1.5),
+        (SoundEffect::Pickup, "sounds/pickup.ogg", SoundCategory::Interface, 0.1, 2, 0.3),
+        (SoundEffect::Explosion, "sounds/explosion.ogg", SoundCategory::Impact, 0.1, 2, 0.8),
+    ];
+    let mut sounds = HashMap::new();
+    for (effect, path, category, pitch_variance, max_instances, length) in definitions.iter() {
+        sounds.insert(*effect, SoundDefinition {
+            source: asset_server.load(*path),
+            category: *category,
+            pitch_variance: *pitch_variance,
+            max_instances: *max_instances,
+            length: *length,
+            channel: AudioChannel::new(format!("sfx_{:?}", effect)),
+        });
+    }
+    let mut music = HashMap::new();
+    music.insert(MusicTrack::Menu, asset_server.load("music/menu.ogg"));
+    music.insert(MusicTrack::Gameplay, asset_server.load("music/gameplay.ogg"));
+    commands.insert_resource(SoundLibrary { sounds, music });
+
+    if music_state.track.is_none() {
+        music_state.track = Some(MusicTrack::Gameplay);
+    }
+}
+
+impl ActiveSounds {
+    /// Takes a slot for one more `effect` lasting `length` seconds, unless `max_instances` are
+    /// already playing.
+    pub fn claim(&mut self, effect: SoundEffect, max_instances: usize, length: f32) -> bool {
+        let slots = self.0.entry(effect).or_insert_with(Vec::new);
+        if slots.len() >= max_instances {
+            return false;
+        }
+        slots.push(Timer::from_seconds(length, false));
+        true
+    }
+}
+
+/// maps gameplay events to sound effects
+pub fn queue_event_sounds(
+    mut queue: ResMut<SoundQueue>,
+    mut shots: EventReader<ShotFired>,
+    mut hits: EventReader<EnemyHitEvent>,
+    mut kills: EventReader<EnemyKilledEvent>,
+    mut hurts: EventReader<PlayerHurtEvent>,
+    mut waves: EventReader<WaveStartEvent>,
+    mut pickups: EventReader<PickupEvent>,
+    mut explosions: EventReader<BombExploded>,
+) {
+    queue.0.extend(shots.iter().map(|_| SoundEffect::Shot));
+    queue.0.extend(hits.iter().map(|_| SoundEffect::EnemyHit));
+    queue.0.extend(kills.iter().map(|_| SoundEffect::EnemyKilled));
+    queue.0.extend(hurts.iter().map(|_| SoundEffect::PlayerHurt));
+    queue.0.extend(waves.iter().map(|_| SoundEffect::WaveStart));
+    queue.0.extend(pickups.iter().map(|_| SoundEffect::Pickup));
+    queue.0.extend(explosions.iter().map(|_| SoundEffect::Explosion));
+}
+
+/// plays what `queue_event_sounds` asked for, as far as the instance caps allow
+pub fn play_sounds(
+    audio: Res<Audio>,
+    library: Option<Res<SoundLibrary>>,
+    settings: Res<AudioSettings>,
+    mut active: ResMut<ActiveSounds>,
+    mut queue: ResMut<SoundQueue>,
+) {
+    let library = match library {
+        Some(library) => library,
+        None => {
+            queue.0.clear();
+            return;
+        }
+    };
+    let mut rng = rand::thread_rng();
+    for effect in queue.0.drain(..) {
+        let definition = match library.sounds.get(&effect) {
+            Some(definition) => definition,
+            None => continue,
+        };
+        if !active.claim(effect, definition.max_instances, definition.length) {
+            debug!("skipping {:?}, {} already playing", effect, definition.max_instances);
+            continue;
+        }
+
+        let mut rate = 1.0;
+        if definition.pitch_variance > 0.0 {
+            rate += rng.gen_range(-definition.pitch_variance..definition.pitch_variance);
+        }
+        audio.set_playback_rate_in_channel(rate, &definition.channel);
+        audio.set_volume_in_channel(settings.volume(definition.category), &definition.channel);
+        audio.play_in_channel(definition.source.clone(), &definition.channel);
+    }
+}
+
+pub fn expire_sounds(
+    mut active: ResMut<ActiveSounds>,
+    time: Res<Time>,
+) {
+    for slots in active.0.values_mut() {
+        for timer in slots.iter_mut() {
+            timer.tick(Duration::from_secs_f32(time.delta_seconds()));
+        }
+        slots.retain(|timer| !timer.finished());
+    }
+}
+
+/// menu music while the shop or the character select is open, gameplay music otherwise
+pub fn pick_music(
+    mut music_state: ResMut<MusicState>,
+    menu: Option<Res<MenuOpen>>,
+) {
+    let track = match menu.map_or(false, |menu| menu.0) {
+        true => MusicTrack::Menu,
+        false => MusicTrack::Gameplay,
+    };
+    // only written on a change, every write restarts the crossfade
+    if music_state.track != Some(track) {
+        music_state.track = Some(track);
+    }
+}
+
+/// starts the requested track on the idle channel and kicks off the crossfade
+pub fn change_music(
+    audio: Res<Audio>,
+    library: Option<Res<SoundLibrary>>,
+    music_state: Res<MusicState>,
+    settings: Res<AudioSettings>,
+    mut crossfade: ResMut<Crossfade>,
+) {
+    let library = match library {
+        Some(library) => library,
+        None => return,
+    };
+    if !music_state.is_changed() && !library.is_added() {
+        return;
+    }
+    let track = match music_state.track.and_then(|track| library.music.get(&track)) {
+        Some(track) => track,
+        None => return,
+    };
+    let next = 1 - crossfade.current;
+    audio.set_volume_in_channel(0.0, &crossfade.channels[next]);
+    audio.play_looped_in_channel(track.clone(), &crossfade.channels[next]);
+    crossfade.current = next;
+    crossfade.timer = Some(Timer::from_seconds(settings.crossfade_seconds.max(0.01), false));
+}
+
+pub fn crossfade_music(
+    audio: Res<Audio>,
+    settings: Res<AudioSettings>,
+    mut crossfade: ResMut<Crossfade>,
+    time: Res<Time>,
+) {
+    let volume = settings.master * settings.music;
+    let current = crossfade.current;
+    let mut finished = false;
+    if let Some(timer) = crossfade.timer.as_mut() {
+        timer.tick(Duration::from_secs_f32(time.delta_seconds()));
+        let (incoming, outgoing) = crossfade_volumes(volume, timer.percent());
+        audio.set_volume_in_channel(incoming, &crossfade.channels[current]);
+        audio.set_volume_in_channel(outgoing, &crossfade.channels[1 - current]);
+        finished = timer.finished();
+    } else if settings.is_changed() {
+        audio.set_volume_in_channel(volume, &crossfade.channels[current]);
+    }
+    if finished {
+        audio.stop_channel(&crossfade.channels[1 - current]);
+        crossfade.timer = None;
+    }
+}
@@ -0,0 +1,180 @@
+use bevy::prelude::*;
+use bevy_kira_audio::{AudioChannel, AudioSource};
+use std::collections::HashMap;
+use audio_handler::*;
+mod audio_handler;
+
+
+/// Plays sounds for gameplay events and keeps background music running, crossfading to the menu
+/// track while the shop or character select is open.
+/// When no output device exists (CI, servers) the plugin only logs and adds nothing,
+/// so the rest of the game never has to know whether audio is available.
+pub struct GameAudioPlugin;
+
+impl Plugin for GameAudioPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        if !audio_device_available() {
+            warn!("no audio output device found, running without sound");
+            return;
+        }
+        app.add_plugin(bevy_kira_audio::AudioPlugin)
+            .init_resource::<AudioSettings>()
+            .init_resource::<MusicState>()
+            .insert_resource(ActiveSounds::default())
+            .insert_resource(SoundQueue::default())
+            .insert_resource(Crossfade::default())
+            .add_startup_system(load_sounds.system())
+            .add_system(queue_event_sounds.system())
+            .add_system(play_sounds.system())
+            .add_system(expire_sounds.system())
+            .add_system(pick_music.system())
+            .add_system(change_music.system())
+            .add_system(crossfade_music.system());
+    }
+}
+
+fn audio_device_available() -> bool {
+    use cpal::traits::HostTrait;
+    cpal::default_host().default_output_device().is_some()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SoundEffect {
+    Shot,
+    EnemyHit,
+    EnemyKilled,
+    PlayerHurt,
+    WaveStart,
+    Pickup,
+    Explosion,
+}
+
+/// Volume groups the player can tune independently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SoundCategory {
+    Weapon,
+    Impact,
+    Player,
+    Interface,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MusicTrack {
+    Menu,
+    Gameplay,
+}
+
+/// all volumes are 0.0..=1.0 and multiplied with master
+pub struct AudioSettings {
+    pub master: f32,
+    pub music: f32,
+    pub categories: HashMap<SoundCategory, f32>,
+    pub crossfade_seconds: f32,
+}
+
+impl AudioSettings {
+    pub fn volume(&self, category: SoundCategory) -> f32 {
+        self.master * self.categories.get(&category).copied().unwrap_or(1.0)
+    }
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        let mut categories = HashMap::new();
+        categories.insert(SoundCategory::Weapon, 0.5);
+        categories.insert(SoundCategory::Impact, 0.7);
+        categories.insert(SoundCategory::Player, 1.0);
+        categories.insert(SoundCategory::Interface, 0.8);
+        AudioSettings {
+            master: 1.0,
+            music: 0.6,
+            categories,
+            crossfade_seconds: 1.5,
+        }
+    }
+}
+
+/// sound effects asked for by gameplay events, played and emptied by `play_sounds`
+#[derive(Default)]
+pub struct SoundQueue(pub Vec<SoundEffect>);
+
+/// Volumes of the track fading in and the one fading out, `progress` of the crossfade from 0 to 1.
+pub fn crossfade_volumes(volume: f32, progress: f32) -> (f32, f32) {
+    let progress = progress.clamp(0.0, 1.0);
+    (volume * progress, volume * (1.0 - progress))
+}
+
+/// Picked by `pick_music`; a change is crossfaded.
+#[derive(Default)]
+pub struct MusicState {
+    pub track: Option<MusicTrack>,
+}
+
+pub struct SoundDefinition {
+    pub source: Handle<AudioSource>,
+    pub category: SoundCategory,
+    /// playback rate is picked from 1.0 ± pitch_variance
+    pub pitch_variance: f32,
+    /// how many copies of this sound may overlap
+    pub max_instances: usize,
+    /// roughly how long one instance occupies a slot
+    pub length: f32,
+    pub channel: AudioChannel,
+}
+
+pub struct SoundLibrary {
+    pub sounds: HashMap<SoundEffect, SoundDefinition>,
+    pub music: HashMap<MusicTrack, Handle<AudioSource>>,
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game_data::*;
+
+    #[test]
+    fn gameplay_events_queue_their_sounds() {
+        let mut app_builder = App::build();
+        app_builder
+            .add_plugins(MinimalPlugins)
+            .add_event::<ShotFired>()
+            .add_event::<EnemyHitEvent>()
+            .add_event::<EnemyKilledEvent>()
+            .add_event::<PlayerHurtEvent>()
+            .add_event::<WaveStartEvent>()
+            .add_event::<PickupEvent>()
+            .add_event::<BombExploded>()
+            .insert_resource(SoundQueue::default())
+            .add_system(queue_event_sounds.system());
+        let mut app = app_builder.app;
+        let entity = app.world.spawn().id();
+        app.world.get_resource_mut::<Events<WaveStartEvent>>().unwrap().send(WaveStartEvent(2));
+        app.world.get_resource_mut::<Events<PlayerHurtEvent>>().unwrap().send(PlayerHurtEvent(entity));
+        app.world.get_resource_mut::<Events<EnemyKilledEvent>>().unwrap().send(EnemyKilledEvent(entity, None));
+        app.world.get_resource_mut::<Events<PickupEvent>>().unwrap().send(PickupEvent(entity));
+        app.world.get_resource_mut::<Events<BombExploded>>().unwrap().send(BombExploded { position: Vec2::ZERO, radius: 120.0 });
+        app.update();
+
+        let queued = &app.world.get_resource::<SoundQueue>().unwrap().0;
+        let expected = vec![SoundEffect::EnemyKilled, SoundEffect::PlayerHurt, SoundEffect::WaveStart, SoundEffect::Pickup, SoundEffect::Explosion];
+        assert_eq!(queued, &expected);
+    }
+
+    #[test]
+    fn overlapping_copies_of_a_sound_are_capped() {
+        let mut active = ActiveSounds::default();
+        assert!(active.claim(SoundEffect::Shot, 2, 0.2));
+        assert!(active.claim(SoundEffect::Shot, 2, 0.2));
+        assert!(!active.claim(SoundEffect::Shot, 2, 0.2));
+        assert!(active.claim(SoundEffect::Pickup, 2, 0.2));
+    }
+
+    #[test]
+    fn crossfades_trade_volume_between_the_tracks() {
+        assert_eq!(crossfade_volumes(0.6, 0.0), (0.0, 0.6));
+        assert_eq!(crossfade_volumes(0.6, 0.5), (0.3, 0.3));
+        assert_eq!(crossfade_volumes(0.6, 1.0), (0.6, 0.0));
+        assert_eq!(crossfade_volumes(0.6, 1.5), (0.6, 0.0));
+    }
+}
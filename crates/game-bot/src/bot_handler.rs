@@ -0,0 +1,83 @@
+use bevy::prelude::*;
+use game_data::*;
+use game_spatial::*;
+use crate::*;
+
+
+pub fn drive_bots(
+    mut bots: Query<(&Transform, &mut PlayerInput, Option<&FireCooldown>), (With<Bot>, Without<Downed>)>,
+    enemies: Res<SpatialIndex<Enemy>>,
+    pickups: Res<SpatialIndex<Pickup>>,
+    tuning: Res<BotTuning>,
+    config: Res<GameConfig>,
+) {
+    let arena = Vec2::new(config.enemies.arena_half_size.0, config.enemies.arena_half_size.1);
+    let enemy_radius = tuning.kite_radius.max(tuning.fire_range);
+    for (transform, mut input, cooldown) in bots.iter_mut() {
+        let position = transform.translation.truncate();
+        let nearby_enemies = enemies.within_radius(position, enemy_radius).map(|entry| entry.position).collect::<Vec<_>>();
+        let nearby_pickups = pickups.within_radius(position, tuning.pickup_radius).map(|entry| entry.position).collect::<Vec<_>>();
+        let held = input.fire;
+        *input = decide(position, &nearby_enemies, &nearby_pickups, arena, &tuning).into();
+        // tapped weapons need the trigger let go between shots, charged ones fire on release
+        input.fire &= match cooldown {
+            Some(cooldown) => match cooldown.mode {
+                FireMode::Hold => true,
+                FireMode::Tap => !held,
+                FireMode::Charged { .. } => cooldown.charge() < 1.0,
+            },
+            None => true,
+        };
+    }
+}
+
+pub fn make_local_players_bots(
+    mut commands: Commands,
+    players: Query<(Entity, &PlayerId)>,
+    config: Res<GameConfig>,
+) {
+    for (entity, id) in players.iter() {
+        if id.0 < config.coop.players {
+            commands.entity(entity).insert(Bot);
+        }
+    }
+}
+
+pub fn attract_mode(
+    mut commands: Commands,
+    mut attract: ResMut<AttractMode>,
+    keyboard: Res<Input<KeyCode>>,
+    buttons: Res<Input<GamepadButton>>,
+    players: Query<(Entity, &PlayerId, Option<&Bot>)>,
+    config: Res<GameConfig>,
+    time: Res<Time>,
+) {
+    let touched = keyboard.get_just_pressed().next().is_some() || buttons.get_just_pressed().next().is_some();
+    if touched {
+        attract.idle.reset();
+        if attract.demo {
+            info!("attract mode off");
+            attract.demo = false;
+            for (entity, id, _) in players.iter() {
+                if id.0 < config.coop.players {
+                    commands.entity(entity).remove::<Bot>();
+                }
+            }
+        }
+        return;
+    }
+
+    attract.idle.tick(time.delta());
+    if attract.idle.finished() && !attract.demo {
+        info!("nobody is playing, attract mode on");
+        attract.demo = true;
+    }
+    if attract.demo {
+        // players spawned for a new run are taken over too
+        for (entity, id, bot) in players.iter() {
+            if id.0 < config.coop.players && bot.is_none() {
+                commands.entity(entity).insert(Bot);
+            }
+        }
+    }
+}
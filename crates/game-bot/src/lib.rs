@@ -0,0 +1,213 @@
+use bevy::prelude::*;
+use std::time::Duration;
+use game_data::*;
+use bot_handler::*;
+mod bot_handler;
+
+
+/// Bot AI for players marked `Bot`: kites away from close enemies, aims and fires at the
+/// closest one and collects pickups when it's safe. Writes the same `PlayerInput` a human's
+/// bindings would, so everything downstream treats bots like players. Needs `SpatialPlugin`
+/// for its neighbour lookups.
+pub struct BotPlugin {
+    /// make every local player a bot at startup, e.g. for headless soak runs
+    pub control_local_players: bool,
+}
+
+impl Plugin for BotPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<BotTuning>()
+            .add_system_to_stage(CoreStage::PreUpdate, drive_bots.system());
+        if self.control_local_players {
+            app.add_startup_system_to_stage(StartupStage::PostStartup, make_local_players_bots.system());
+        }
+    }
+}
+
+/// Attract mode: once nobody has touched the keyboard or a gamepad for `idle`, bots take over
+/// the local players as a demo. Any key or button hands control back.
+pub struct AttractModePlugin {
+    pub idle: Duration,
+}
+
+impl Plugin for AttractModePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(AttractMode { idle: Timer::new(self.idle, false), demo: false })
+            .add_system_to_stage(CoreStage::PreUpdate, attract_mode.system());
+    }
+}
+
+pub struct AttractMode {
+    pub idle: Timer,
+    /// bots are playing
+    pub demo: bool,
+}
+
+/// distances in pixels
+#[derive(Debug, Clone, PartialEq)]
+pub struct BotTuning {
+    /// enemies closer than this push the bot away
+    pub kite_radius: f32,
+    /// enemies closer than this are shot at
+    pub fire_range: f32,
+    /// pickups closer than this are collected while no enemy is within `kite_radius`
+    pub pickup_radius: f32,
+    /// fraction of the arena half size past which the bot steers back towards the middle
+    pub edge_margin: f32,
+}
+
+impl Default for BotTuning {
+    fn default() -> Self {
+        BotTuning {
+            kite_radius: 200.0,
+            fire_range: 500.0,
+            pickup_radius: 400.0,
+            edge_margin: 0.8,
+        }
+    }
+}
+
+/// What a bot wants to do, in the same terms as a human: a move vector, an aim vector and the trigger.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct BotAction {
+    /// length 0..1
+    pub movement: Vec2,
+    pub aim: Option<Vec2>,
+    pub fire: bool,
+}
+
+impl From<BotAction> for PlayerInput {
+    fn from(action: BotAction) -> Self {
+        PlayerInput {
+            movement: action.movement,
+            aim: action.aim.and_then(|aim| Direction::from_axes(aim.normalize_or_zero())),
+            fire: action.fire,
+            alt_fire: false,
+            reload: false,
+        }
+    }
+}
+
+/// Decides a bot's action at `position` from the positions of live enemies and pickups.
+/// `arena` is the arena's half size.
+pub fn decide(position: Vec2, enemies: &[Vec2], pickups: &[Vec2], arena: Vec2, tuning: &BotTuning) -> BotAction {
+    // every enemy inside the kite radius pushes, the closer the harder
+    let mut away = Vec2::ZERO;
+    for enemy in enemies.iter() {
+        let offset = position - *enemy;
+        let distance = offset.length();
+        if distance > 0.0 && distance < tuning.kite_radius {
+            away += offset / distance * (1.0 - distance / tuning.kite_radius);
+        }
+    }
+
+    let mut movement = if away != Vec2::ZERO {
+        away.normalize()
+    } else {
+        match closest(pickups, position).filter(|pickup| pickup.distance(position) < tuning.pickup_radius) {
+            Some(pickup) => (pickup - position).normalize_or_zero(),
+            None => Vec2::ZERO,
+        }
+    };
+
+    // kiting into a corner gets a bot cornered, so lean back towards the middle near the edges
+    let edge = (position.abs() / arena).max_element();
+    if edge > tuning.edge_margin {
+        movement += -position.normalize_or_zero() * (edge - tuning.edge_margin) / (1.0 - tuning.edge_margin);
+    }
+    if movement.length() > 1.0 {
+        movement = movement.normalize();
+    }
+
+    let target = closest(enemies, position);
+    BotAction {
+        movement,
+        aim: target.map(|enemy| enemy - position),
+        fire: target.map_or(false, |enemy| enemy.distance(position) <= tuning.fire_range),
+    }
+}
+
+fn closest(points: &[Vec2], from: Vec2) -> Option<Vec2> {
+    points
+        .iter()
+        .copied()
+        .min_by(|a, b| a.distance_squared(from).partial_cmp(&b.distance_squared(from)).unwrap_or(std::cmp::Ordering::Equal))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game_spatial::*;
+
+    fn arena() -> Vec2 {
+        Vec2::new(640.0, 360.0)
+    }
+
+    #[test]
+    fn bots_kite_away_and_shoot_back() {
+        let tuning = BotTuning::default();
+        let action = decide(Vec2::ZERO, &[Vec2::new(100.0, 0.0), Vec2::new(450.0, 0.0)], &[], arena(), &tuning);
+        assert!(action.movement.x < -0.9);
+        assert_eq!(action.aim, Some(Vec2::new(100.0, 0.0)));
+        assert!(action.fire);
+        assert_eq!(PlayerInput::from(action).aim, Some(Direction::East));
+
+        let quiet = decide(Vec2::ZERO, &[Vec2::new(0.0, 600.0)], &[], arena(), &tuning);
+        assert_eq!(quiet.movement, Vec2::ZERO);
+        assert!(!quiet.fire);
+    }
+
+    #[test]
+    fn bots_collect_pickups_only_when_safe() {
+        let tuning = BotTuning::default();
+        let pickups = [Vec2::new(0.0, 100.0)];
+        let safe = decide(Vec2::ZERO, &[], &pickups, arena(), &tuning);
+        assert_eq!(safe.movement, Vec2::new(0.0, 1.0));
+
+        let threatened = decide(Vec2::ZERO, &[Vec2::new(0.0, 50.0)], &pickups, arena(), &tuning);
+        assert!(threatened.movement.y < 0.0);
+    }
+
+    #[test]
+    fn bots_are_steered_back_from_the_edge() {
+        let tuning = BotTuning::default();
+        // the enemy pushes towards the right wall, the edge pulls back
+        let action = decide(Vec2::new(630.0, 0.0), &[Vec2::new(560.0, 0.0)], &[], arena(), &tuning);
+        assert!(action.movement.x < 1.0);
+        let cornered = decide(Vec2::new(640.0, 0.0), &[], &[], arena(), &tuning);
+        assert!(cornered.movement.x < 0.0);
+    }
+
+    #[test]
+    fn bots_write_player_input() {
+        let mut app_builder = App::build();
+        app_builder
+            .add_plugins(MinimalPlugins)
+            .insert_resource(GameConfig::default())
+            .add_plugin(SpatialPlugin)
+            .add_plugin(BotPlugin { control_local_players: false });
+        let mut app = app_builder.app;
+        let bot = app.world.spawn()
+            .insert(Transform::default())
+            .insert(PlayerId(0))
+            .insert(PlayerInput::default())
+            .insert(Bot)
+            .id();
+        let human = app.world.spawn()
+            .insert(Transform::default())
+            .insert(PlayerId(1))
+            .insert(PlayerInput::default())
+            .id();
+        app.world.spawn()
+            .insert(Transform::from_translation(Vec3::new(0.0, 100.0, 0.0)))
+            .insert(Enemy);
+        app.update();
+
+        let input = *app.world.get::<PlayerInput>(bot).unwrap();
+        assert!(input.fire);
+        assert_eq!(input.aim, Some(Direction::North));
+        assert!(input.movement.y < 0.0);
+        assert_eq!(*app.world.get::<PlayerInput>(human).unwrap(), PlayerInput::default());
+    }
+}
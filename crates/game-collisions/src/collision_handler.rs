@@ -1,22 +1,40 @@
 use bevy::prelude::*;
 use bevy_rapier2d::{
-    physics::{RigidBodyHandleComponent, EventQueue}, 
+    physics::{RigidBodyHandleComponent, ColliderHandleComponent, EventQueue, RapierConfiguration},
     rapier::{
         dynamics::RigidBodySet,
-        geometry::{ColliderSet, ContactEvent},
+        geometry::{AABB, ColliderSet, ContactEvent, InteractionGroups},
+        na::Vector2,
     }
 };
 use game_data::*;
+use std::time::Duration;
 
 
 pub fn handle_contacts(
     mut commands: Commands,
     events: Res<EventQueue>,
-    colliders: ResMut<ColliderSet>,
-    bodies: ResMut<RigidBodySet>,
-    enemies: Query<&Enemy>,
+    mut colliders: ResMut<ColliderSet>,
+    mut bodies: ResMut<RigidBodySet>,
+    mut enemies: Query<(&Enemy, &RigidBodyHandleComponent, &ColliderHandleComponent, &mut Health, Option<&mut StatusEffects>), (Without<Player>, Without<Dying>)>,
+    mut players: Query<(&Player, Option<&mut Health>, Option<&Hurt>, Option<&Downed>, Option<&mut StatusEffects>), Without<Enemy>>,
     mut bullets: Query<&Bullet>,
+    mut projectiles: Query<(&mut Heading, &RigidBodyHandleComponent, Option<&mut Pierce>, Option<&mut Ricochet>)>,
+    // read-only lookups and event writers are grouped, systems take at most 16 parameters
+    (damages, modifiers, owners, pooled): (Query<&Damage>, Query<(Option<&Split>, Option<&Explosive>)>, Query<&Owner>, Query<&Pooled>),
+    transforms: Query<&Transform>,
+    kinds: Query<&EnemyKind>,
+    enemy_definitions: Res<EnemyDefinitions>,
+    (mut hit_events, mut killed_events, mut hurt_events, mut projectile_hits): (
+        EventWriter<EnemyHitEvent>,
+        EventWriter<EnemyKilledEvent>,
+        EventWriter<PlayerHurtEvent>,
+        EventWriter<ProjectileHitEvent>,
+    ),
+    config: Res<GameConfig>,
+    rapier_config: Res<RapierConfiguration>,
 ) {
+    let units = Units::new(&rapier_config);
     let mut contacts = vec![];
     while let Ok(contact_event) = events.contact_events.pop() {
         match contact_event {
@@ -36,6 +54,16 @@ pub fn handle_contacts(
                 && enemies.get_component::<Enemy>(e1).is_ok() {
                     info!("e2 is a bullet");
                     contacts.push(Contacts::BulletEnemy(e2,e1));
+                } else if enemies.get_component::<Enemy>(e1).is_ok()
+                && players.get_component::<Player>(e2).is_ok() {
+                    contacts.push(Contacts::EnemyPlayer(e1, e2));
+                } else if enemies.get_component::<Enemy>(e2).is_ok()
+                && players.get_component::<Player>(e1).is_ok() {
+                    contacts.push(Contacts::EnemyPlayer(e2, e1));
+                } else if bullets.get_component::<Bullet>(e1).is_ok() && b2.is_static() && !c2.is_sensor() {
+                    contacts.push(Contacts::BulletWall(e1, wall_normal(b1.position().translation.vector, c2.compute_aabb())));
+                } else if bullets.get_component::<Bullet>(e2).is_ok() && b1.is_static() && !c1.is_sensor() {
+                    contacts.push(Contacts::BulletWall(e2, wall_normal(b2.position().translation.vector, c1.compute_aabb())));
                 }
             },
             _ => (),
@@ -45,12 +73,162 @@ pub fn handle_contacts(
     for contact in contacts.into_iter() {
         match contact {
             Contacts::BulletEnemy(e1, e2) => {
-                info!("despawning bullet");
-                let bullet = bullets.get_component_mut::<Bullet>(e1);
-                commands.entity(e1).despawn();
-                let enemy = enemies.get_component::<Enemy>(e2);
-                commands.entity(e2).despawn();
+                // already killed by an earlier hit this frame, the bullet flies on
+                if enemies.get_component::<Health>(e2).map_or(true, |health| health.0 <= 0) {
+                    continue;
+                }
+                let killer = owners.get(e1).ok().map(|owner| owner.0);
+                let mut heading = Vec2::ZERO;
+                let mut used_up = true;
+                if let Ok((bullet_heading, _, pierce, _)) = projectiles.get_mut(e1) {
+                    heading = bullet_heading.0;
+                    // enemies aren't in a bullet's solver groups, so it carries on unslowed
+                    if let Some(mut pierce) = pierce.filter(|pierce| pierce.0 > 0) {
+                        pierce.0 -= 1;
+                        used_up = false;
+                    }
+                }
+                if used_up {
+                    info!("despawning bullet");
+                    use_up_bullet(&mut commands, pooled.get(e1).is_ok(), e1);
+                }
+                hit_events.send(EnemyHitEvent(e2));
+                let position = transforms.get(e1).map_or(Vec2::ZERO, |transform| transform.translation.truncate());
+                let (split, explosive) = modifiers.get(e1).map_or((None, None), |(split, explosive)| (split.copied(), explosive.copied()));
+                projectile_hits.send(ProjectileHitEvent { bullet: e1, enemy: e2, position, heading, owner: killer, split, explosive });
+                if let Ok((_, rigid_body_handle, collider_handle, mut health, effects)) = enemies.get_mut(e2) {
+                    if let Some(mut effects) = effects {
+                        for effect in config.weapon.on_hit.iter() {
+                            effects.apply(effect, heading);
+                        }
+                    }
+                    health.0 -= damages.get(e1).map_or(1, |damage| damage.0);
+                    if health.0 <= 0 {
+                        kill_enemy(&mut commands, &mut bodies, &mut colliders, e2, rigid_body_handle, collider_handle);
+                        killed_events.send(EnemyKilledEvent(e2, killer));
+                    }
+                }
+            }
+            Contacts::BulletWall(bullet, normal) => {
+                let speed = bullets.get(bullet).map_or(0.0, |bullet| bullet.0);
+                match projectiles.get_mut(bullet) {
+                    Ok((mut heading, rigid_body_handle, _, Some(mut ricochet))) if ricochet.0 > 0 => {
+                        ricochet.0 -= 1;
+                        heading.0 = reflect(heading.0, normal);
+                        if let Some(rb) = bodies.get_mut(rigid_body_handle.handle()) {
+                            rb.set_linvel(units.velocity_to_physics(PixelsPerSecond(heading.0 * speed)).vector(), true);
+                        }
+                    },
+                    _ => use_up_bullet(&mut commands, pooled.get(bullet).is_ok(), bullet),
+                }
+            }
+            Contacts::EnemyPlayer(enemy, player) => {
+                // downed players can't be hurt, and a hit player gets a moment to get away
+                if let Ok((_, health, hurt, downed, effects)) = players.get_mut(player) {
+                    if hurt.is_some() || downed.is_some() {
+                        continue;
+                    }
+                    if let Some(mut health) = health {
+                        health.0 -= 1;
+                    }
+                    let definition = kinds.get(enemy).ok().and_then(|kind| enemy_definitions.get(&kind.0));
+                    if let (Some(mut effects), Some(definition)) = (effects, definition) {
+                        let push = match (transforms.get(player), transforms.get(enemy)) {
+                            (Ok(player), Ok(enemy)) => (player.translation - enemy.translation).truncate(),
+                            _ => Vec2::ZERO,
+                        };
+                        for effect in definition.on_hit.iter() {
+                            effects.apply(effect, push);
+                        }
+                    }
+                    commands.entity(player).insert(Hurt(Timer::from_seconds(config.player.hurt_time, false)));
+                    hurt_events.send(PlayerHurtEvent(player));
+                }
+            }
+        }
+    }
+}
+
+/// Recycles a pooled bullet, despawns any other.
+fn use_up_bullet(commands: &mut Commands, pooled: bool, bullet: Entity) {
+    if pooled {
+        commands.entity(bullet).remove::<BulletLifetime>().insert(Recycle);
+    } else {
+        commands.entity(bullet).despawn();
+    }
+}
+
+/// `position` and `aabb` in physics units, see `bounce_normal`
+fn wall_normal(position: Vector2<f32>, aabb: AABB) -> Vec2 {
+    bounce_normal(
+        Vec2::new(position.x, position.y),
+        Vec2::new(aabb.mins.x, aabb.mins.y),
+        Vec2::new(aabb.maxs.x, aabb.maxs.y),
+    )
+}
+
+/// Keeps a killed enemy around, stopped and without collisions, so its death animation can play.
+pub fn kill_enemy(
+    commands: &mut Commands,
+    bodies: &mut RigidBodySet,
+    colliders: &mut ColliderSet,
+    enemy: Entity,
+    rigid_body_handle: &RigidBodyHandleComponent,
+    collider_handle: &ColliderHandleComponent,
+) {
+    if let Some(rb) = bodies.get_mut(rigid_body_handle.handle()) {
+        rb.set_linvel(Vector2::zeros(), false);
+    }
+    if let Some(collider) = colliders.get_mut(collider_handle.handle()) {
+        collider.set_collision_groups(InteractionGroups::none());
+    }
+    commands.entity(enemy).insert(Dying(Timer::from_seconds(0.5, false)));
+}
+
+/// Takes the hit points burns have eaten from enemies and players. Burning enemies die like shot
+/// ones but nobody is credited with the kill; burning players are hurt without the grace period.
+pub fn apply_burns(
+    mut commands: Commands,
+    mut bodies: ResMut<RigidBodySet>,
+    mut colliders: ResMut<ColliderSet>,
+    mut enemies: Query<(Entity, &mut StatusEffects, &mut Health, &RigidBodyHandleComponent, &ColliderHandleComponent), (With<Enemy>, Without<Dying>, Without<Inactive>)>,
+    mut players: Query<(Entity, &mut StatusEffects, &mut Health), (With<Player>, Without<Downed>, Without<Enemy>)>,
+    mut killed_events: EventWriter<EnemyKilledEvent>,
+    mut hurt_events: EventWriter<PlayerHurtEvent>,
+) {
+    for (enemy, mut effects, mut health, rigid_body_handle, collider_handle) in enemies.iter_mut() {
+        let damage = effects.take_damage();
+        if damage == 0 {
+            continue;
+        }
+        health.0 -= damage;
+        if health.0 <= 0 {
+            kill_enemy(&mut commands, &mut bodies, &mut colliders, enemy, rigid_body_handle, collider_handle);
+            killed_events.send(EnemyKilledEvent(enemy, None));
+        }
+    }
+    for (player, mut effects, mut health) in players.iter_mut() {
+        let damage = effects.take_damage();
+        if damage > 0 {
+            health.0 -= damage;
+            hurt_events.send(PlayerHurtEvent(player));
+        }
+    }
+}
+
+pub fn despawn_dying(
+    mut commands: Commands,
+    mut dying_query: Query<(&mut Dying, Entity, Option<&Pooled>)>,
+    time: Res<Time>,
+) {
+    for (mut dying, entity, pooled) in dying_query.iter_mut() {
+        dying.0.tick(Duration::from_secs_f32(time.delta_seconds()));
+        if dying.0.finished() {
+            if pooled.is_some() {
+                commands.entity(entity).remove::<Dying>().insert(Recycle);
+            } else {
+                commands.entity(entity).despawn();
             }
         }
     }
-}
\ No newline at end of file
+}
@@ -1,5 +1,7 @@
 use bevy::prelude::*;
-use collision_handler::handle_contacts;
+use game_data::unpaused;
+use collision_handler::{handle_contacts, apply_burns, despawn_dying};
+pub use collision_handler::kill_enemy;
 mod collision_handler;
 
 
@@ -7,15 +9,98 @@ pub struct CollisionPlugin;
 
 impl Plugin for CollisionPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_system(handle_contacts.system());
+        app.add_system(handle_contacts.system())
+            .add_system(apply_burns.system())
+            .add_system(despawn_dying.system().with_run_criteria(unpaused.system()));
     }
 }
 
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use bevy_rapier2d::physics::{RapierConfiguration, RapierPhysicsPlugin};
+    use bevy_rapier2d::rapier::na::Vector2;
+    use game_data::*;
+    use game_data::Direction as Direction;
+
+    fn app() -> App {
+        let mut app_builder = App::build();
+        app_builder
+            .add_plugins(MinimalPlugins)
+            .add_plugin(bevy::transform::TransformPlugin)
+            .add_plugin(RapierPhysicsPlugin)
+            .add_event::<EnemyHitEvent>()
+            .add_event::<EnemyKilledEvent>()
+            .add_event::<PlayerHurtEvent>()
+            .add_event::<ProjectileHitEvent>()
+            .insert_resource(GameConfig::default())
+            .insert_resource(EnemyDefinitions::default())
+            .add_system(handle_contacts.system());
+        let mut app = app_builder.app;
+        app.world.get_resource_mut::<RapierConfiguration>().unwrap().gravity = Vector2::zeros();
+        app
+    }
+
+    fn spawn_enemy(app: &mut App, health: i32) -> Entity {
+        app.world.spawn()
+            .insert(Transform::default())
+            .insert_bundle(EnemyBundle::new(&RapierConfiguration::default(), WorldPos(Vec2::ZERO), PixelsPerSecond(Vec2::ZERO), health))
+            .id()
+    }
+
+    /// a bullet parked on top of the enemy, `damage` strong and fired by `owner`
+    fn spawn_bullet(app: &mut App, x: f32, damage: i32, owner: Entity) -> Entity {
+        app.world.spawn()
+            .insert(Transform::from_xyz(x, 0.0, 0.0))
+            .insert_bundle(ProjectileBundle::new(&RapierConfiguration::default(), WorldPos(Vec2::new(x, 0.0)), PixelsPerSecond(Vec2::ZERO), Direction::East, 5.0))
+            .insert(Damage(damage))
+            .insert(Owner(owner))
+            .id()
+    }
+
+    /// steps the app and returns every kill reported meanwhile, as (enemy, killer)
+    fn kills(app: &mut App, frames: usize) -> Vec<(Entity, Option<Entity>)> {
+        let mut reader = app.world.get_resource::<Events<EnemyKilledEvent>>().unwrap().get_reader();
+        let mut kills = vec![];
+        for _ in 0..frames {
+            app.update();
+            let events = app.world.get_resource::<Events<EnemyKilledEvent>>().unwrap();
+            kills.extend(reader.iter(events).map(|event| (event.0, event.1)));
+        }
+        kills
+    }
+
     #[test]
-    fn it_works() {
-        assert_eq!(2 + 2, 4);
+    fn shot_enemies_lose_health_and_die() {
+        let mut app = app();
+        let owner = app.world.spawn().id();
+        let enemy = spawn_enemy(&mut app, 5);
+        let bullet = spawn_bullet(&mut app, 2.0, 3, owner);
+
+        assert!(kills(&mut app, 5).is_empty());
+        assert_eq!(app.world.get::<Health>(enemy).unwrap().0, 2);
+        assert!(app.world.get::<Dying>(enemy).is_none());
+        assert!(app.world.get_entity(bullet).is_none());
+
+        spawn_bullet(&mut app, -2.0, 3, owner);
+        let kills = kills(&mut app, 5);
+        assert_eq!(kills.len(), 1);
+        assert_eq!(kills[0], (enemy, Some(owner)));
+        assert!(app.world.get::<Dying>(enemy).is_some());
+    }
+
+    #[test]
+    fn two_hits_in_one_frame_kill_once() {
+        let mut app = app();
+        let owner = app.world.spawn().id();
+        let enemy = spawn_enemy(&mut app, 1);
+        let bullets = [spawn_bullet(&mut app, 2.0, 1, owner), spawn_bullet(&mut app, -2.0, 1, owner)];
+
+        assert_eq!(kills(&mut app, 5).len(), 1);
+        assert!(app.world.get::<Dying>(enemy).is_some());
+        // the second bullet finds the enemy already dead and flies on
+        let used_up = bullets.iter().filter(|bullet| app.world.get_entity(**bullet).is_none()).count();
+        assert_eq!(used_up, 1);
     }
 }
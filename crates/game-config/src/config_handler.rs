@@ -0,0 +1,107 @@
+use bevy::prelude::*;
+use std::time::Duration;
+use game_data::*;
+use crate::*;
+
+
+pub fn watch_config(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    if let Err(e) = asset_server.watch_for_changes() {
+        warn!("config hot reload disabled: {:?}", e);
+    }
+    commands.insert_resource(ConfigHandles {
+        config: asset_server.load(CONFIG_ASSET),
+        enemies: asset_server.load(ENEMY_DEFINITIONS_ASSET),
+        upgrades: asset_server.load(UPGRADE_DEFINITIONS_ASSET),
+    });
+}
+
+/// Copies a reloaded config into the `GameConfig` resource and onto entities that cached values
+/// from it. Bullet speed and lifetime only apply to bullets fired afterwards; ones in flight finish
+/// as they were fired.
+pub fn apply_game_config(
+    mut events: EventReader<AssetEvent<GameConfig>>,
+    configs: Res<Assets<GameConfig>>,
+    mut config: ResMut<GameConfig>,
+    // network clients don't spawn enemies themselves
+    mut enemy_spawn_timer: Option<ResMut<EnemySpawnTimer>>,
+    wave: Option<Res<Wave>>,
+    mut players: Query<(&mut Player, &mut FireCooldown, &PlayerStats)>,
+) {
+    for event in events.iter() {
+        let handle = match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => handle,
+            AssetEvent::Removed { .. } => continue,
+        };
+        let new_config = match configs.get(handle) {
+            Some(new_config) => new_config,
+            None => continue,
+        };
+        if *new_config == *config {
+            continue;
+        }
+        debug!("game config reloaded");
+
+        for (mut player, mut cooldown, stats) in players.iter_mut() {
+            // upgrades and characters scale whatever the config says now
+            stats.update_player(new_config, &mut player, &mut cooldown);
+            // players on a weapon of their own keep its fire mode
+            if cooldown.mode == config.weapon.fire_mode && cooldown.mode != new_config.weapon.fire_mode {
+                cooldown.cancel();
+                cooldown.mode = new_config.weapon.fire_mode;
+            }
+        }
+        if let Some(enemy_spawn_timer) = enemy_spawn_timer.as_mut() {
+            let wave = wave.as_ref().map_or(1, |wave| wave.0);
+            enemy_spawn_timer.0.set_duration(Duration::from_secs_f32(new_config.enemies.spawn_interval_at(wave)));
+        }
+        if new_config.pools != config.pools {
+            debug!("pool sizes apply on the next start, pools grow on demand until then");
+        }
+
+        *config = new_config.clone();
+    }
+}
+
+/// Enemies look their kind up every frame, so replacing the resource is enough
+/// for existing enemies to pick up new speeds.
+pub fn apply_enemy_definitions(
+    mut events: EventReader<AssetEvent<EnemyDefinitions>>,
+    assets: Res<Assets<EnemyDefinitions>>,
+    mut definitions: ResMut<EnemyDefinitions>,
+) {
+    for event in events.iter() {
+        let handle = match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => handle,
+            AssetEvent::Removed { .. } => continue,
+        };
+        if let Some(new_definitions) = assets.get(handle) {
+            if *new_definitions != *definitions {
+                debug!("enemy definitions reloaded: {} kinds", new_definitions.kinds.len());
+                *definitions = new_definitions.clone();
+            }
+        }
+    }
+}
+
+/// Only upgrades offered from now on change, what players already took stays as it was.
+pub fn apply_upgrade_definitions(
+    mut events: EventReader<AssetEvent<UpgradeDefinitions>>,
+    assets: Res<Assets<UpgradeDefinitions>>,
+    mut definitions: ResMut<UpgradeDefinitions>,
+) {
+    for event in events.iter() {
+        let handle = match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => handle,
+            AssetEvent::Removed { .. } => continue,
+        };
+        if let Some(new_definitions) = assets.get(handle) {
+            if *new_definitions != *definitions {
+                debug!("upgrade definitions reloaded: {} upgrades", new_definitions.upgrades.len());
+                *definitions = new_definitions.clone();
+            }
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use bevy::asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset};
+use game_data::*;
+
+
+/// Loads `*.config.ron`. Validation errors fail the load, so the asset server logs them
+/// and keeps the previous asset.
+#[derive(Default)]
+pub struct GameConfigLoader;
+
+impl AssetLoader for GameConfigLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let config = GameConfig::parse(std::str::from_utf8(bytes)?)?;
+            load_context.set_default_asset(LoadedAsset::new(config));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["config.ron"]
+    }
+}
+
+/// Loads `*.enemies.ron`.
+#[derive(Default)]
+pub struct EnemyDefinitionsLoader;
+
+impl AssetLoader for EnemyDefinitionsLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let definitions = EnemyDefinitions::parse(std::str::from_utf8(bytes)?)?;
+            load_context.set_default_asset(LoadedAsset::new(definitions));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["enemies.ron"]
+    }
+}
+
+/// Loads `*.upgrades.ron`.
+#[derive(Default)]
+pub struct UpgradeDefinitionsLoader;
+
+impl AssetLoader for UpgradeDefinitionsLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let definitions = UpgradeDefinitions::parse(std::str::from_utf8(bytes)?)?;
+            load_context.set_default_asset(LoadedAsset::new(definitions));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["upgrades.ron"]
+    }
+}
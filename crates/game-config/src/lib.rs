@@ -0,0 +1,36 @@
+use bevy::prelude::*;
+use game_data::*;
+use config_handler::*;
+use config_loader::*;
+mod config_handler;
+mod config_loader;
+
+
+/// Watches the gameplay config, enemy and upgrade definitions through the asset server and applies
+/// edits while the game runs. A file that fails to parse or validate is logged and ignored,
+/// the last good values stay in use.
+///
+/// Expects `GameConfig`, `EnemyDefinitions` and `UpgradeDefinitions` resources loaded at startup.
+pub struct ConfigPlugin;
+
+impl Plugin for ConfigPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_asset::<GameConfig>()
+            .add_asset::<EnemyDefinitions>()
+            .add_asset::<UpgradeDefinitions>()
+            .init_asset_loader::<GameConfigLoader>()
+            .init_asset_loader::<EnemyDefinitionsLoader>()
+            .init_asset_loader::<UpgradeDefinitionsLoader>()
+            .add_startup_system(watch_config.system())
+            .add_system(apply_game_config.system())
+            .add_system(apply_enemy_definitions.system())
+            .add_system(apply_upgrade_definitions.system());
+    }
+}
+
+/// keeps the watched assets loaded
+pub struct ConfigHandles {
+    pub config: Handle<GameConfig>,
+    pub enemies: Handle<EnemyDefinitions>,
+    pub upgrades: Handle<UpgradeDefinitions>,
+}
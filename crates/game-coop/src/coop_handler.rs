@@ -0,0 +1,104 @@
+use bevy::prelude::*;
+use std::time::Duration;
+use game_data::*;
+use crate::*;
+
+
+pub fn recover_from_hurt(
+    mut commands: Commands,
+    mut hurt_query: Query<(Entity, &mut Hurt)>,
+    time: Res<Time>,
+) {
+    for (entity, mut hurt) in hurt_query.iter_mut() {
+        hurt.0.tick(Duration::from_secs_f32(time.delta_seconds()));
+        if hurt.0.finished() {
+            commands.entity(entity).remove::<Hurt>();
+        }
+    }
+}
+
+/// Standing players with `Regeneration` heal 1 health every period, up to their max. The timer
+/// only runs while they are missing health.
+pub fn regenerate(
+    mut players: Query<(&mut Regeneration, &mut Health, Option<&PlayerStats>), (With<Player>, Without<Downed>)>,
+    config: Res<GameConfig>,
+    time: Res<Time>,
+) {
+    for (mut regeneration, mut health, stats) in players.iter_mut() {
+        let max_health = stats.map_or(config.player.max_health, |stats| stats.max_health);
+        if health.0 >= max_health {
+            regeneration.0.reset();
+            continue;
+        }
+        regeneration.0.tick(time.delta());
+        if regeneration.0.just_finished() {
+            health.0 += 1;
+        }
+    }
+}
+
+pub fn down_players(
+    mut commands: Commands,
+    players: Query<(Entity, &PlayerId, &Health), (With<Player>, Without<Downed>)>,
+    config: Res<GameConfig>,
+) {
+    for (entity, id, health) in players.iter() {
+        if health.0 <= 0 {
+            info!("player {} is down", id.0);
+            commands.entity(entity).insert(Downed {
+                revive: Timer::from_seconds(config.coop.revive_time, false),
+            });
+        }
+    }
+}
+
+/// A downed player is revived once a living teammate has stayed within `revive_radius` for
+/// `revive_time`; walking away resets the progress.
+pub fn revive_players(
+    mut commands: Commands,
+    mut downed_players: Query<(Entity, &PlayerId, &Transform, &mut Downed, &mut Health), With<Player>>,
+    living_players: Query<&Transform, (With<Player>, Without<Downed>)>,
+    mut all_down_events: EventWriter<AllPlayersDownEvent>,
+    mut all_down: Local<bool>,
+    config: Res<GameConfig>,
+    time: Res<Time>,
+) {
+    let living = living_players.iter().map(|transform| transform.translation.truncate()).collect::<Vec<_>>();
+    let mut anyone_down = false;
+    for (entity, id, transform, mut downed, mut health) in downed_players.iter_mut() {
+        anyone_down = true;
+        if reviver_in_range(transform.translation.truncate(), &living, config.coop.revive_radius) {
+            downed.revive.tick(Duration::from_secs_f32(time.delta_seconds()));
+        } else {
+            downed.revive.reset();
+        }
+        if downed.revive.finished() {
+            info!("player {} was revived", id.0);
+            health.0 = config.coop.revive_health;
+            commands.entity(entity).remove::<Downed>();
+        }
+    }
+
+    let everyone_down = living.is_empty() && anyone_down;
+    if everyone_down && !*all_down {
+        info!("all players are down");
+        all_down_events.send(AllPlayersDownEvent);
+    }
+    *all_down = everyone_down;
+}
+
+pub fn count_score(
+    mut killed_events: EventReader<EnemyKilledEvent>,
+    mut score: ResMut<Score>,
+    mut player_scores: Query<&mut PlayerScore>,
+    config: Res<GameConfig>,
+) {
+    for EnemyKilledEvent(_, killer) in killed_events.iter() {
+        score.0 += 1;
+        if config.coop.scoring == ScoreMode::Split {
+            if let Some(mut player_score) = killer.and_then(|killer| player_scores.get_mut(killer).ok()) {
+                player_score.0 += 1;
+            }
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use bevy::prelude::*;
+use game_data::*;
+use coop_handler::*;
+mod coop_handler;
+
+
+/// What happens to players between getting hit and the run ending: hurt cooldowns, regeneration,
+/// going down at 0 health, reviving downed teammates, and crediting kills to the team or to the shooter.
+pub struct CoopPlugin;
+
+impl Plugin for CoopPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(Score::default())
+            .add_event::<AllPlayersDownEvent>()
+            .add_system(recover_from_hurt.system().with_run_criteria(unpaused.system()))
+            .add_system(regenerate.system().with_run_criteria(unpaused.system()))
+            .add_system(down_players.system())
+            .add_system(revive_players.system().with_run_criteria(unpaused.system()))
+            .add_system(count_score.system());
+    }
+}
+
+/// every player is downed, nobody is left to revive them
+pub struct AllPlayersDownEvent;
+
+/// Whether a downed player at `downed` is being revived: some living teammate is within `radius`.
+pub fn reviver_in_range(downed: Vec2, living: &[Vec2], radius: f32) -> bool {
+    living.iter().any(|position| position.distance(downed) <= radius)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_close_teammates_revive() {
+        let living = [Vec2::new(100.0, 0.0), Vec2::new(0.0, 300.0)];
+        assert!(reviver_in_range(Vec2::new(60.0, 0.0), &living, 60.0));
+        assert!(!reviver_in_range(Vec2::new(0.0, 0.0), &living, 60.0));
+        assert!(!reviver_in_range(Vec2::ZERO, &[], 60.0));
+    }
+}
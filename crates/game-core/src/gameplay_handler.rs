@@ -0,0 +1,613 @@
+use bevy::prelude::*;
+use bevy_rapier2d::physics::{RapierConfiguration, RigidBodyHandleComponent};
+use bevy_rapier2d::rapier::dynamics::RigidBodySet;
+use bevy_rapier2d::rapier::na::Vector2;
+use rand::Rng;
+use std::time::Duration;
+use game_animation::*;
+use game_data::*;
+use game_data::Direction as Direction;
+use game_navigation::*;
+use game_pool::*;
+use game_spatial::*;
+use crate::*;
+
+
+/// degrees between neighbouring bullets of a multishot
+pub const MULTISHOT_GAP: f32 = 12.0;
+
+pub fn setup(
+    mut commands: Commands,
+    mut rapier_config: ResMut<RapierConfiguration>,
+    sheets: Option<Res<SpriteSheets>>,
+    game_materials: Option<Res<GameMaterials>>,
+    loadout: Option<Res<Loadout>>,
+    config: Res<GameConfig>,
+) {
+    let visuals = sheets.as_deref().zip(game_materials.as_deref());
+    rapier_config.gravity = Vector2::zeros();
+
+    // While we want our sprite to look ~40 px square, we want to keep the physics units smaller
+    // to prevent float rounding problems. To do this, we set the scale factor in RapierConfiguration
+    // and the bundles divide world positions and sizes by it.
+    rapier_config.scale = PHYSICS_SCALE;
+
+    spawn_walls(&mut commands, &rapier_config, &config);
+    spawn_players(&mut commands, &rapier_config, visuals, &config, loadout.as_deref());
+}
+
+/// Fences the arena in just outside `arena_half_size`, so bullets stop at its edge or ricochet
+/// off it and nobody walks out. Placed once; later arena resizes leave the walls where they are.
+pub fn spawn_walls(commands: &mut Commands, rapier_config: &RapierConfiguration, config: &GameConfig) {
+    let half_size = Vec2::new(config.enemies.arena_half_size.0, config.enemies.arena_half_size.1);
+    let half_thickness = WALL_THICKNESS / 2.0;
+    let across = Vec2::new(half_size.x + WALL_THICKNESS, half_thickness);
+    let along = Vec2::new(half_thickness, half_size.y + WALL_THICKNESS);
+    let walls = [
+        (Vec2::new(0.0, half_size.y + half_thickness), across),
+        (Vec2::new(0.0, -half_size.y - half_thickness), across),
+        (Vec2::new(half_size.x + half_thickness, 0.0), along),
+        (Vec2::new(-half_size.x - half_thickness, 0.0), along),
+    ];
+    for (position, half_size) in walls.iter() {
+        commands.spawn_bundle(WallBundle::new(rapier_config, WorldPos(*position), *half_size));
+    }
+}
+
+/// Spawns the local players side by side, centred on the origin.
+pub fn spawn_players(
+    commands: &mut Commands,
+    rapier_config: &RapierConfiguration,
+    visuals: Option<(&SpriteSheets, &GameMaterials)>,
+    config: &GameConfig,
+    loadout: Option<&Loadout>,
+) {
+    let players = config.coop.players;
+    for id in 0..players {
+        let position = WorldPos(Vec2::new((id as f32 - (players - 1) as f32 / 2.0) * 2.0 * PLAYER_SIZE, 0.0));
+        spawn_player(commands, rapier_config, visuals, config, loadout, PlayerId(id), position);
+    }
+}
+
+/// Clears the arena for a new run: pooled enemies and bullets go back to their pools,
+/// everything else the run left lying around is despawned, score, wave and level-ups start
+/// over, and every player, local or remote, is spawned afresh from the `Loadout`.
+pub fn restart_run(
+    mut commands: Commands,
+    mut new_runs: EventReader<NewRunEvent>,
+    players: Query<(Entity, &PlayerId)>,
+    pooled: Query<Entity, (With<Pooled>, Without<Inactive>, Without<Dying>, Or<(With<Enemy>, With<Bullet>)>)>,
+    leftovers: Query<Entity, Or<(With<Pickup>, With<Bomb>, (With<Bullet>, Without<Pooled>))>>,
+    rapier_config: Res<RapierConfiguration>,
+    sheets: Option<Res<SpriteSheets>>,
+    game_materials: Option<Res<GameMaterials>>,
+    loadout: Option<Res<Loadout>>,
+    mut score: ResMut<Score>,
+    mut wave: ResMut<Wave>,
+    mut enemy_count: ResMut<EnemyCount>,
+    mut enemy_spawn_timer: ResMut<EnemySpawnTimer>,
+    mut pending: ResMut<PendingLevelUps>,
+    mut paused: ResMut<Paused>,
+    config: Res<GameConfig>,
+) {
+    if new_runs.iter().count() == 0 {
+        return;
+    }
+    info!("starting a new run");
+    for entity in players.iter().map(|(entity, _)| entity).chain(leftovers.iter()) {
+        commands.entity(entity).despawn();
+    }
+    for entity in pooled.iter() {
+        commands.entity(entity).remove::<BulletLifetime>().insert(Recycle);
+    }
+    *score = Score::default();
+    *wave = Wave::default();
+    enemy_count.0 = 0;
+    enemy_spawn_timer.0.reset();
+    pending.0.clear();
+    commands.remove_resource::<UpgradeChoice>();
+    paused.0 = false;
+
+    let visuals = sheets.as_deref().zip(game_materials.as_deref());
+    spawn_players(&mut commands, &rapier_config, visuals, &config, loadout.as_deref());
+    // network players stay in the game, they start over where new players join
+    for (_, id) in players.iter().filter(|(_, id)| id.0 >= config.coop.players) {
+        spawn_player(&mut commands, &rapier_config, visuals, &config, loadout.as_deref(), *id, WorldPos(Vec2::ZERO));
+    }
+}
+
+
+pub fn movement_system(
+    mut player_query: Query<( &mut Player, &PlayerInput, &RigidBodyHandleComponent, Option<&Downed>, Option<&StatusEffects>, Option<&mut RemoteSteps>)>,
+    time: Res<Time>,
+    mut rigid_bodies: ResMut<RigidBodySet>,
+    rapier_config: Res<RapierConfiguration>,
+    config: Res<GameConfig>,
+) {
+    let units = Units::new(&rapier_config);
+    for ( mut player, input, rigid_body_component, downed, effects, remote_steps) in player_query.iter_mut() {
+        let steps = remote_steps.map(|mut remote_steps| std::mem::take(&mut remote_steps.0));
+        if downed.is_some() {
+            player.velocity = Vec2::ZERO;
+            if let Some(rb) = rigid_bodies.get_mut(rigid_body_component.handle()) {
+                rb.set_linvel(Vector2::zeros(), false);
+            }
+            continue;
+        }
+        match steps {
+            Some(steps) => {
+                for (movement, dt) in steps {
+                    player.step(movement, dt, &config.player);
+                }
+            },
+            None => player.step(input.movement, time.delta_seconds(), &config.player),
+        }
+        debug!("velocity: {:?}", player.velocity);
+
+        // effects only change where the body goes, so prediction can keep replaying `step` as is
+        let velocity = match effects {
+            Some(effects) => player.velocity * effects.speed_multiplier() + effects.knockback(),
+            None => player.velocity,
+        };
+        if let Some(rb) = rigid_bodies.get_mut(rigid_body_component.handle()) {
+            rb.set_linvel(units.velocity_to_physics(PixelsPerSecond(velocity)).vector(), true);
+        }
+    }
+}
+/// using player position as origin of shot, fires into the direction the player last aimed at
+/// whenever their `FireCooldown` lets the held fire action through
+/// spawns a projectile that despawns on hit or after time elapses
+pub fn spawn_bullet(
+    mut commands: Commands,
+    mut player_query: Query<(Entity, &Transform, &mut FireCooldown, &mut Direction, &PlayerInput, Option<&StatusEffects>, Option<&mut Ammo>, Option<&PlayerStats>), (With<Player>, Without<Downed>)>,
+    mut shots: EventWriter<ShotFired>,
+    rapier_config: ResMut<RapierConfiguration>,
+    sheets: Option<Res<SpriteSheets>>,
+    game_materials: Option<Res<GameMaterials>>,
+    mut bullet_pool: ResMut<EntityPool<Bullet>>,
+    config: Res<GameConfig>,
+    time: Res<Time>,
+) {
+    let visuals = sheets.as_deref().zip(game_materials.as_deref());
+    let delta = Duration::from_secs_f32(time.delta_seconds());
+    let ammo_config = &config.weapon.ammo;
+    for (entity, transform, mut cooldown, mut last_direction, input, effects, mut ammo, stats) in player_query.iter_mut() {
+        if let Some(ammo) = ammo.as_mut() {
+            let interrupts = ammo_config.reload_cancel == ReloadCancel::Fire && ammo.loaded > 0;
+            if input.fire && ammo.is_reloading() && interrupts {
+                debug!("player {:?} fired out of a reload", entity);
+                ammo.cancel_reload();
+            }
+        }
+        // stunned players and empty weapons can't fire or keep charging
+        let armed = effects.map_or(true, StatusEffects::can_act) && ammo.as_ref().map_or(true, |ammo| ammo.can_fire(ammo_config));
+        if !armed {
+            cooldown.cancel();
+        }
+        let charge = match cooldown.update(delta, input.fire && armed) {
+            Some(charge) => charge,
+            None => continue,
+        };
+        if let Some(ammo) = ammo.as_mut() {
+            ammo.use_round(ammo_config);
+        }
+        if let Some(direction) = input.aim {
+            *last_direction = direction;
+        }
+        let origin = transform.translation.truncate();
+        let (extra, bonus_damage) = stats.map_or((0, 0), |stats| (stats.extra_projectiles, stats.damage));
+        if charge >= config.weapon.charged.min_charge && charge > 0.0 {
+            create_charged_bullet(&mut commands, &rapier_config, visuals, &config.weapon, entity, origin, *last_direction, charge, bonus_damage);
+        } else {
+            create_bullet(&mut commands, &rapier_config, visuals, &mut bullet_pool, &config.weapon, entity, origin, *last_direction, extra, 1 + bonus_damage);
+        }
+        shots.send(ShotFired { shooter: entity, charge });
+        debug!("player {:?} fired with charge {}", entity, charge);
+    }
+}
+
+/// pre-allocates parked bullets and enemies so the first waves don't spawn anything
+pub fn fill_pools(
+    mut commands: Commands,
+    rapier_config: ResMut<RapierConfiguration>,
+    sheets: Option<Res<SpriteSheets>>,
+    game_materials: Option<Res<GameMaterials>>,
+    mut bullet_pool: ResMut<EntityPool<Bullet>>,
+    mut enemy_pool: ResMut<EntityPool<Enemy>>,
+    config: Res<GameConfig>,
+) {
+    let visuals = sheets.as_deref().zip(game_materials.as_deref());
+    let parked = Units::new(&rapier_config).to_world(PhysicsPos(Vec2::new(PARK_POSITION.0, PARK_POSITION.1)));
+    for _ in 0..config.pools.bullets {
+        let entity = spawn_pooled_bullet(&mut commands, &rapier_config, visuals, &config.weapon, parked, PixelsPerSecond::default(), Direction::East);
+        commands.entity(entity).insert(Recycle);
+        bullet_pool.add();
+    }
+    for _ in 0..config.pools.enemies {
+        let entity = spawn_pooled_enemy(&mut commands, &rapier_config, visuals, parked, 1);
+        commands.entity(entity).insert(Recycle);
+        enemy_pool.add();
+    }
+}
+
+pub fn create_enemy(
+    commands: &mut Commands,
+    rapier_config: &ResMut<RapierConfiguration>,
+    visuals: Option<(&SpriteSheets, &GameMaterials)>,
+    enemy_pool: &mut EntityPool<Enemy>,
+    position: WorldPos,
+    kind: &EnemyDefinition,
+) {
+    let entity = match enemy_pool.acquire() {
+        Some(entity) => {
+            commands.entity(entity)
+                .remove::<Inactive>()
+                .insert(Activate {
+                    position,
+                    velocity: PixelsPerSecond::default(),
+                });
+            entity
+        },
+        None => {
+            enemy_pool.add();
+            spawn_pooled_enemy(commands, rapier_config, visuals, position, kind.health)
+        }
+    };
+    commands.entity(entity)
+        .insert(EnemyKind(kind.name.clone()))
+        .insert(Health(kind.health))
+        .insert(StatusEffects::default());
+}
+
+pub fn spawn_pooled_enemy(
+    commands: &mut Commands,
+    rapier_config: &ResMut<RapierConfiguration>,
+    visuals: Option<(&SpriteSheets, &GameMaterials)>,
+    position: WorldPos,
+    health: i32,
+) -> Entity {
+    spawn_visual(commands, visuals, "enemy", position, ENEMY_SIZE, |m| m.enemy.clone())
+        .insert_bundle(EnemyBundle::new(rapier_config, position, PixelsPerSecond::default(), health))
+        .insert(Pooled)
+        .id()
+}
+
+/// `owner` fires from just outside its sprite at `origin` towards `direction`, with the
+/// weapon's projectile modifiers. `extra` more bullets fan out `MULTISHOT_GAP` degrees apart.
+pub fn create_bullet (
+    commands: & mut Commands, 
+    rapier_config: &ResMut<RapierConfiguration>, 
+    visuals: Option<(&SpriteSheets, &GameMaterials)>,
+    bullet_pool: &mut EntityPool<Bullet>,
+    weapon: &WeaponConfig,
+    owner: Entity,
+    origin: Vec2,
+    direction: Direction,
+    extra: u32,
+    damage: i32,
+) {
+    debug!("current x translation for player entity: {} y: {}", origin.x, origin.y);
+    let position = WorldPos(origin + direction.axes() * weapon.muzzle_offset);
+    for heading in fan(direction.unit(), 1 + extra, (MULTISHOT_GAP * extra as f32).to_radians()) {
+        let entity = fire_projectile(commands, rapier_config, visuals, bullet_pool, weapon, owner, position, heading);
+        commands.entity(entity).insert(Damage(damage));
+        weapon.projectile.arm(&mut commands.entity(entity));
+    }
+}
+
+/// A bullet grown with `charge` towards the size and damage of a fully charged shot. Charged
+/// bullets don't fit the pooled colliders, so they are spawned and despawned.
+pub fn create_charged_bullet(
+    commands: &mut Commands,
+    rapier_config: &ResMut<RapierConfiguration>,
+    visuals: Option<(&SpriteSheets, &GameMaterials)>,
+    weapon: &WeaponConfig,
+    owner: Entity,
+    origin: Vec2,
+    direction: Direction,
+    charge: f32,
+    bonus_damage: i32,
+) {
+    let size = BULLET_SIZE * (1.0 + (weapon.charged.size - 1.0) * charge);
+    let damage = 1 + ((weapon.charged.damage - 1) as f32 * charge).round() as i32 + bonus_damage;
+    let position = WorldPos(origin + direction.axes() * (weapon.muzzle_offset + size / 2.0));
+    let velocity = PixelsPerSecond(direction.unit() * weapon.bullet_speed);
+    debug!("charged shot at {:.2}: {} px, {} damage", charge, size, damage);
+    let entity = spawn_visual(commands, visuals, "bullet", position, size, |m| m.bullet.clone())
+        .insert_bundle(ProjectileBundle::new(rapier_config, position, velocity, direction, weapon.bullet_lifetime).sized(rapier_config, size))
+        .insert(Damage(damage))
+        .insert(Owner(owner))
+        .id();
+    weapon.projectile.arm(&mut commands.entity(entity));
+}
+
+/// a bullet from the pool flying from `position` along the unit vector `heading`, without modifiers
+/// and dealing 1 damage
+pub fn fire_projectile(
+    commands: &mut Commands,
+    rapier_config: &ResMut<RapierConfiguration>,
+    visuals: Option<(&SpriteSheets, &GameMaterials)>,
+    bullet_pool: &mut EntityPool<Bullet>,
+    weapon: &WeaponConfig,
+    owner: Entity,
+    position: WorldPos,
+    heading: Vec2,
+) -> Entity {
+    let velocity = PixelsPerSecond(heading * weapon.bullet_speed);
+    let direction = Direction::from_axes(heading).unwrap_or(Direction::East);
+
+    let entity = match bullet_pool.acquire() {
+        Some(entity) => {
+            commands.entity(entity)
+                .remove::<Inactive>()
+                .insert(Activate { position, velocity })
+                .insert(Heading(heading))
+                .insert(direction)
+                .insert(Bullet(weapon.bullet_speed))
+                .insert(BulletLifetime(Timer::from_seconds(weapon.bullet_lifetime, true)));
+            entity
+        },
+        None => {
+            bullet_pool.add();
+            spawn_pooled_bullet(commands, rapier_config, visuals, weapon, position, velocity, direction)
+        }
+    };
+    commands.entity(entity).insert(Owner(owner)).insert(Damage(1));
+    entity
+}
+
+pub fn spawn_pooled_bullet(
+    commands: &mut Commands,
+    rapier_config: &ResMut<RapierConfiguration>,
+    visuals: Option<(&SpriteSheets, &GameMaterials)>,
+    weapon: &WeaponConfig,
+    position: WorldPos,
+    velocity: PixelsPerSecond,
+    direction: Direction,
+) -> Entity {
+    spawn_visual(commands, visuals, "bullet", position, BULLET_SIZE, |m| m.bullet.clone())
+        .insert_bundle(ProjectileBundle::new(rapier_config, position, velocity, direction, weapon.bullet_lifetime))
+        .insert(Pooled)
+        .id()
+}
+
+pub fn despawn_bullets(
+    mut commands: Commands,
+    mut bullet_query: Query<(&mut BulletLifetime, Entity, Option<&Pooled>), Without<Inactive>>,
+    time: Res<Time>,
+) {
+    for (mut bullet_timer, entity, pooled) in bullet_query.iter_mut() {
+        bullet_timer.0.tick(Duration::from_secs_f32(time.delta_seconds()));
+        if bullet_timer.0.finished() {
+            if pooled.is_some() {
+                debug!("Recycling a bullet");
+                commands.entity(entity).remove::<BulletLifetime>().insert(Recycle);
+            } else {
+                commands.entity(entity).despawn();
+            }
+        }
+    }
+}
+
+/// Runs reloads, starting one when the magazine runs dry or the player asks for it.
+pub fn reload_weapons(
+    mut players: Query<(Entity, &mut Ammo, &PlayerInput), (With<Player>, Without<Downed>)>,
+    config: Res<GameConfig>,
+    time: Res<Time>,
+) {
+    let ammo_config = &config.weapon.ammo;
+    for (entity, mut ammo, input) in players.iter_mut() {
+        if ammo.tick(time.delta(), ammo_config) {
+            debug!("player {:?} reloaded: {} loaded, {:?} in reserve", entity, ammo.loaded, ammo.reserve);
+        }
+        if (input.reload || ammo.needs_reload(ammo_config)) && ammo.start_reload(ammo_config) {
+            debug!("player {:?} is reloading", entity);
+        }
+    }
+}
+
+/// drops a bomb at the feet of every player who pressed alt fire and has one left
+pub fn drop_bombs(
+    mut commands: Commands,
+    mut players: Query<(Entity, &Transform, &mut Bombs, &PlayerInput, Option<&StatusEffects>), (With<Player>, Without<Downed>)>,
+    sheets: Option<Res<SpriteSheets>>,
+    game_materials: Option<Res<GameMaterials>>,
+    config: Res<GameConfig>,
+) {
+    let visuals = sheets.as_deref().zip(game_materials.as_deref());
+    for (entity, transform, mut bombs, input, effects) in players.iter_mut() {
+        let pressed = input.alt_fire && effects.map_or(true, StatusEffects::can_act);
+        if !bombs.trigger(pressed) {
+            continue;
+        }
+        debug!("player {:?} dropped a bomb, {} left", entity, bombs.left);
+        let bomb = &config.weapon.bomb;
+        spawn_visual(&mut commands, visuals, "bomb", WorldPos(transform.translation.truncate()), BOMB_SIZE, |m| m.bomb.clone())
+            .insert(Bomb { fuse: Timer::from_seconds(bomb.fuse, false), radius: bomb.radius, damage: bomb.damage })
+            .insert(Owner(entity));
+    }
+}
+
+///generates a random number that is outside of the range of the player position plus some buffer distance
+pub fn spawn_enemies(
+    mut commands: Commands,
+    player_position_query: Query<&Transform, (With<Player>, Without<Downed>)>,
+    mut enemy_count: ResMut<EnemyCount>,
+    mut enemy_spawn_timer: ResMut<EnemySpawnTimer>,
+    rapier_config: ResMut<RapierConfiguration>,
+    sheets: Option<Res<SpriteSheets>>,
+    game_materials: Option<Res<GameMaterials>>,
+    mut enemy_pool: ResMut<EntityPool<Enemy>>,
+    config: Res<GameConfig>,
+    enemy_definitions: Res<EnemyDefinitions>,
+    mut rng: ResMut<GameRng>,
+    time: Res<Time>,
+) {
+    let visuals = sheets.as_deref().zip(game_materials.as_deref());
+    //get player position, generate random number around that position, spawn the enemy there, 
+    // use timer and enemy count to decide when to spawn
+    enemy_spawn_timer.0.tick(Duration::from_secs_f32(time.delta_seconds()));
+    if enemy_spawn_timer.0.finished() && enemy_count.0 < config.enemies.max_enemies {
+        debug!("timer finished");
+        // one enemy per tick, placed around a random living player
+        let players = player_position_query.iter().collect::<Vec<_>>();
+        if players.is_empty() {
+            return;
+        }
+        let transform = players[rng.0.gen_range(0..players.len())];
+        let (x_position, y_position) = generate_xy_values(&transform, &config.enemies, &mut rng.0);
+        debug!("Spawn at pos x: {}, pos y: {}", x_position, y_position);
+        let kind = enemy_definitions.pick(rng.0.gen());
+        create_enemy(&mut commands, &rapier_config, visuals, &mut enemy_pool, WorldPos(Vec2::new(x_position as f32, y_position as f32)), kind);
+        enemy_count.0 +=1;
+    }
+}
+/// Starts the next wave every `enemies.wave_kills` team kills and spawns enemies faster for it.
+/// A wave set from outside, by a new run or a restored session, is started the same way.
+pub fn advance_waves(
+    score: Res<Score>,
+    mut wave: ResMut<Wave>,
+    mut enemy_spawn_timer: ResMut<EnemySpawnTimer>,
+    mut wave_starts: EventWriter<WaveStartEvent>,
+    config: Res<GameConfig>,
+) {
+    let reached = config.enemies.wave_at(score.0);
+    if reached > wave.0 {
+        wave.0 = reached;
+    }
+    if !wave.is_changed() {
+        return;
+    }
+    info!("wave {} started", wave.0);
+    enemy_spawn_timer.0.set_duration(Duration::from_secs_f32(config.enemies.spawn_interval_at(wave.0)));
+    wave_starts.send(WaveStartEvent(wave.0));
+}
+/// Enemies follow the flow field around walls towards the nearest living player while keeping
+/// apart from, moving with and staying near the enemies around them, weighted per kind. They
+/// stand still once everyone is down.
+pub fn move_enemies(
+    players: Res<SpatialIndex<Player>>,
+    enemies: Res<SpatialIndex<Enemy>>,
+    navigation: Res<Navigation>,
+    enemies_query: Query<(Entity, &RigidBodyHandleComponent, &Transform, Option<&EnemyKind>, Option<&StatusEffects>), (With<Enemy>, Without<Dying>, Without<Inactive>)>,
+    obstacles: Query<(&Transform, &Obstacle)>,
+    mut rigid_bodies: ResMut<RigidBodySet>,
+    rapier_config: Res<RapierConfiguration>,
+    enemy_definitions: Res<EnemyDefinitions>,
+    config: Res<GameConfig>,
+) {
+    let units = Units::new(&rapier_config);
+    let obstacles = obstacles
+        .iter()
+        .map(|(transform, obstacle)| (transform.translation.truncate(), obstacle.radius))
+        .collect::<Vec<_>>();
+    let velocity_of = |entity: Entity| {
+        enemies_query
+            .get(entity)
+            .ok()
+            .and_then(|(_, rigid_body_handle, _, _, _)| rigid_bodies.get(rigid_body_handle.handle()))
+            .map_or(Vec2::ZERO, |rb| units.velocity_to_world(PhysicsVelocity::from_vector(rb.linvel())).0)
+    };
+
+    // every heading is picked from last frame's velocities before any of them change
+    let mut headings = vec![];
+    let mut neighbours = vec![];
+    for (entity, rigid_body_handle, enemy_transform, kind, effects) in enemies_query.iter() {
+        // kinds removed by a reload fall back to the first definition
+        let definition = kind
+            .and_then(|kind| enemy_definitions.get(&kind.0))
+            .unwrap_or(&enemy_definitions.kinds[0]);
+        let position = enemy_transform.translation.truncate();
+        let target = nearest_player(&players, position);
+        if target.is_none() {
+            headings.push((rigid_body_handle.handle(), Vec2::ZERO));
+            continue;
+        }
+        neighbours.clear();
+        neighbours.extend(
+            enemies
+                .within_radius(position, config.enemies.neighbour_radius)
+                .filter(|entry| entry.entity != entity)
+                .map(|entry| Neighbour { position: entry.position, velocity: velocity_of(entry.entity) }),
+        );
+        let surroundings = Surroundings {
+            target,
+            path: navigation.direction(position),
+            neighbours: &neighbours,
+            obstacles: &obstacles,
+        };
+        let heading = steer(position, &surroundings, &definition.steering, &config.enemies);
+        let velocity = match effects {
+            Some(effects) => heading * definition.speed * effects.speed_multiplier() + effects.knockback(),
+            None => heading * definition.speed,
+        };
+        headings.push((rigid_body_handle.handle(), velocity));
+    }
+
+    for (handle, velocity) in headings {
+        if let Some(rb) = rigid_bodies.get_mut(handle) {
+            rb.set_linvel(units.velocity_to_physics(PixelsPerSecond(velocity)).vector(), velocity != Vec2::ZERO);
+        }
+    }
+}
+
+/// position of the living player closest to `from`
+pub fn nearest_player(players: &SpatialIndex<Player>, from: Vec2) -> Option<Vec2> {
+    players.nearest(from, 1).first().map(|entry| entry.position)
+}
+
+pub fn generate_xy_values(transform: &Transform, config: &EnemyConfig, rng: &mut impl Rng) -> (i32, i32) {
+    let window_max_x= config.arena_half_size.0 as i32;
+    let window_max_y= config.arena_half_size.1 as i32;
+    let buffer = config.spawn_buffer as i32;
+    let window_min_x = -window_max_x;
+    let window_min_y = -window_max_y;
+    debug!("translation.x: {}, translation.y: {}", transform.translation.x, transform.translation.y);
+    let mut x = 0;
+    let mut is_x_valid = false;
+    if transform.translation.x as i32 + buffer < window_max_x {
+        x = rng.gen_range(transform.translation.x as i32 + buffer.. window_max_x);
+        is_x_valid = true;
+    }
+    let mut x2 = 0;
+    let mut is_x2_valid = false;
+    if transform.translation.x as i32 - buffer > window_min_x {
+        x2 = rng.gen_range(window_min_x.. transform.translation.x as i32 + buffer);
+        is_x2_valid = true;
+    }
+    let mut y = 0;
+    let mut is_y_valid = false;
+    if transform.translation.y as i32 + buffer < window_max_y {
+        y = rng.gen_range(transform.translation.y as i32 + buffer..window_max_y);
+        is_y_valid = true;
+    }
+    let mut y2 = 0;
+    let mut is_y2_valid = false;
+    if transform.translation.y as i32 - buffer > window_min_y {
+        y2 = rng.gen_range(window_min_y..transform.translation.y as i32) - buffer;
+        is_y2_valid = true;
+    }
+    let x_pair = [x, x2];
+    let y_pair = [y, y2];
+    // pick between one of the two x values, as long as the value is within range of 0..1280 for x and 0..720 for y.
+    let choose_x = rng.gen_range(0usize..2usize);
+    let choose_y = rng.gen_range(0usize..2usize);
+    let mut x_position = 0;
+    let mut y_position = 0;
+    if is_x_valid && is_x2_valid {
+        x_position = x_pair[choose_x];
+    } else if is_x_valid {
+        x_position = x;
+    } else if is_x2_valid {
+        x_position = x2;
+    }
+    if is_y_valid && is_y2_valid {
+        y_position = y_pair[choose_y];
+    } else if is_y_valid {
+        y_position = y;
+    } else if is_y2_valid {
+        y_position = y2;
+    }
+    debug!("x_position: {}, y_position: {}, translation.x: {}, translation.y: {}", x_position, y_position, transform.translation.x, transform.translation.y);
+    (x_position, y_position)
+}
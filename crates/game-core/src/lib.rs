@@ -0,0 +1,367 @@
+use bevy::prelude::*;
+use game_collisions::*;
+use game_coop::*;
+use game_data::*;
+use game_navigation::*;
+use game_pool::*;
+use game_spatial::*;
+use game_status::*;
+pub use gameplay_handler::*;
+pub use pickup_handler::*;
+pub use progression_handler::*;
+pub use projectile_handler::*;
+pub use steering::*;
+mod gameplay_handler;
+mod pickup_handler;
+mod progression_handler;
+mod projectile_handler;
+mod steering;
+
+
+/// Everything needed to simulate a run: players, shooting and ammo, enemy spawning and chasing around
+/// walls, collisions, status effects, pools, co-op rules and levelling up. Needs `RapierPhysicsPlugin` but no window, renderer or
+/// sprites, so it runs the same under `DefaultPlugins` and `MinimalPlugins`. Inserts the default
+/// `GameConfig`, `EnemyDefinitions`, `UpgradeDefinitions` and `GameRng` unless they are already there.
+///
+/// While an `UpgradeChoice` is open the game is `Paused`: only input and the choice itself run.
+pub struct GameplayPlugin;
+
+impl Plugin for GameplayPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<GameConfig>()
+            .init_resource::<EnemyDefinitions>()
+            .init_resource::<UpgradeDefinitions>()
+            .init_resource::<GameRng>()
+            .init_resource::<Paused>()
+            .init_resource::<PendingLevelUps>();
+        let spawn_interval = app.world().get_resource::<GameConfig>().unwrap().enemies.spawn_interval;
+
+        app.add_plugin(GameEventsPlugin)
+            .add_plugin(CollisionPlugin)
+            .add_plugin(PoolPlugin)
+            .add_plugin(CoopPlugin)
+            .add_plugin(SpatialPlugin)
+            .add_plugin(NavigationPlugin)
+            .add_plugin(StatusPlugin)
+            .add_startup_system(setup.system())
+            .add_startup_system_to_stage(StartupStage::PostStartup, fill_pools.system())
+            .add_system_set(SystemSet::new()
+                .with_run_criteria(unpaused.system())
+                .with_system(movement_system.system())
+                .with_system(spawn_bullet.system())
+                .with_system(steer_homing_bullets.system())
+                .with_system(resolve_projectile_hits.system())
+                .with_system(reload_weapons.system())
+                .with_system(drop_bombs.system())
+                .with_system(detonate_bombs.system())
+                .with_system(move_enemies.system())
+                .with_system(advance_waves.system())
+                .with_system(spawn_enemies.system())
+                .with_system(despawn_bullets.system())
+                .with_system(drop_ammo.system())
+                .with_system(collect_pickups.system())
+                .with_system(drop_xp.system())
+                .with_system(collect_xp.system()))
+            .add_system(offer_upgrades.system())
+            .add_system(choose_upgrade.system())
+            .add_system(pause_physics.system())
+            .add_system(restart_run.system())
+            .insert_resource(EnemySpawnTimer(Timer::from_seconds(spawn_interval, true)))
+            .insert_resource(EnemyCount(0))
+            .insert_resource(Wave::default());
+    }
+}
+
+/// Gameplay events that audio and animation listen to. Part of `GameplayPlugin`; add it on its
+/// own where the game isn't simulated, e.g. on network clients.
+pub struct GameEventsPlugin;
+
+impl Plugin for GameEventsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<ShotFired>()
+            .add_event::<EnemyHitEvent>()
+            .add_event::<EnemyKilledEvent>()
+            .add_event::<PlayerHurtEvent>()
+            .add_event::<WaveStartEvent>()
+            .add_event::<PickupEvent>()
+            .add_event::<NewRunEvent>()
+            .add_event::<ProjectileHitEvent>()
+            .add_event::<BombExploded>();
+    }
+}
+
+/// A missing file falls back to the defaults, a broken one stops the game before it starts.
+/// Once running, broken edits are only logged, see `ConfigPlugin`.
+pub fn load_or_exit<T: Default>(loaded: Result<T, ConfigError>) -> T {
+    match loaded {
+        Ok(loaded) => loaded,
+        Err(ConfigError::Io(path, e)) if e.kind() == std::io::ErrorKind::NotFound => {
+            eprintln!("{} not found, using the defaults", path.display());
+            T::default()
+        },
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::asset::AssetPlugin;
+    use bevy_rapier2d::physics::{RapierConfiguration, RapierPhysicsPlugin, RigidBodyHandleComponent};
+    use bevy_rapier2d::rapier::dynamics::RigidBodySet;
+    use bevy_rapier2d::rapier::geometry::ColliderBuilder;
+    use bevy_rapier2d::rapier::na::Vector2;
+    use std::time::Duration;
+    use game_animation::*;
+    use game_data::Direction as Direction;
+    use game_pool::*;
+    use game_spatial::*;
+
+    #[test]
+    fn firing_does_not_add_materials() {
+        let mut app_builder = App::build();
+        app_builder
+            .add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin)
+            .add_asset::<ColorMaterial>()
+            .add_event::<ShotFired>()
+            .insert_resource(RapierConfiguration::default())
+            .insert_resource(SpriteSheets::default())
+            .insert_resource(EntityPool::<Bullet>::default())
+            .insert_resource(GameConfig::default())
+            .add_system(spawn_bullet.system());
+        let mut app = app_builder.app;
+
+        let game_materials = GameMaterials::new(&mut app.world.get_resource_mut::<Assets<ColorMaterial>>().unwrap());
+        app.world.insert_resource(game_materials);
+        app.world.spawn()
+            .insert(Transform::default())
+            .insert(Player::default())
+            .insert(PlayerInput { fire: true, ..Default::default() })
+            .insert(FireCooldown::new(FireMode::Hold, GameConfig::default().weapon.fire_interval))
+            .insert(Direction::East);
+
+        let material_count = app.world.get_resource::<Assets<ColorMaterial>>().unwrap().len();
+        for _ in 0..5 {
+            // cooled down every frame instead of waiting out the fire interval in real time
+            for mut cooldown in app.world.query::<&mut FireCooldown>().iter_mut(&mut app.world) {
+                cooldown.set_elapsed(Duration::from_secs(1));
+            }
+            app.update();
+        }
+
+        let bullet_count = app.world.query::<&Bullet>().iter(&app.world).count();
+        assert!(bullet_count > 1);
+        assert_eq!(material_count, app.world.get_resource::<Assets<ColorMaterial>>().unwrap().len());
+    }
+
+    #[test]
+    fn enemies_chase_the_nearest_player() {
+        let players = [Vec2::new(-300.0, 0.0), Vec2::new(200.0, 50.0)];
+        let mut index = SpatialIndex::<Player>::default();
+        for (i, position) in players.iter().enumerate() {
+            index.insert(Entity::new(i as u32), *position);
+        }
+        assert_eq!(nearest_player(&index, Vec2::new(150.0, 0.0)), Some(players[1]));
+        assert_eq!(nearest_player(&index, Vec2::new(-100.0, -100.0)), Some(players[0]));
+        assert_eq!(nearest_player(&SpatialIndex::default(), Vec2::ZERO), None);
+    }
+
+    #[test]
+    fn crowded_enemies_spread_out_and_go_around_obstacles() {
+        let config = GameConfig::default().enemies;
+        let weights = SteeringWeights::default();
+        let player = Some(Vec2::new(500.0, 0.0));
+        let alone = steer(Vec2::ZERO, &Surroundings { target: player, path: None, neighbours: &[], obstacles: &[] }, &weights, &config);
+        assert!((alone - Vec2::X).length() < 1e-5);
+
+        // a neighbour right above pushes the enemy down while it keeps chasing
+        let neighbours = [Neighbour { position: Vec2::new(0.0, 10.0), velocity: Vec2::new(100.0, 0.0) }];
+        let crowded = steer(Vec2::ZERO, &Surroundings { target: player, path: None, neighbours: &neighbours, obstacles: &[] }, &weights, &config);
+        assert!(crowded.x > 0.0 && crowded.y < 0.0);
+        assert!(crowded.length() <= 1.0 + 1e-5);
+
+        // an obstacle straight ahead is passed on one side instead of pinning the enemy in front of it
+        let obstacles = [(Vec2::new(60.0, 0.0), 30.0)];
+        let blocked = steer(Vec2::ZERO, &Surroundings { target: player, path: None, neighbours: &[], obstacles: &obstacles }, &weights, &config);
+        assert!(blocked.y.abs() > 0.5);
+
+        let idle = steer(Vec2::ZERO, &Surroundings { target: None, path: None, neighbours: &[], obstacles: &[] }, &weights, &config);
+        assert_eq!(idle, Vec2::ZERO);
+    }
+
+    #[test]
+    fn enemies_steer_around_spawned_obstacles() {
+        let mut app_builder = App::build();
+        app_builder
+            .add_plugins(MinimalPlugins)
+            .add_plugin(bevy::transform::TransformPlugin)
+            .add_plugin(RapierPhysicsPlugin)
+            .add_plugin(SpatialPlugin)
+            .add_plugin(NavigationPlugin)
+            .insert_resource(GameConfig::default())
+            .insert_resource(EnemyDefinitions::default())
+            .add_system(move_enemies.system());
+        let mut app = app_builder.app;
+        app.world.get_resource_mut::<RapierConfiguration>().unwrap().gravity = Vector2::zeros();
+        let rapier_config = RapierConfiguration::default();
+
+        // the player is straight ahead of the enemy with an obstacle in between
+        app.world.spawn()
+            .insert(Transform::from_xyz(500.0, 0.0, 0.0))
+            .insert(Player::default());
+        let enemy = app.world.spawn()
+            .insert(Transform::default())
+            .insert_bundle(EnemyBundle::new(&rapier_config, WorldPos(Vec2::ZERO), PixelsPerSecond(Vec2::ZERO), 1))
+            .id();
+        app.world.spawn()
+            .insert(Transform::from_xyz(60.0, 0.0, 0.0))
+            .insert(Obstacle { radius: 30.0 });
+        for _ in 0..3 {
+            app.update();
+        }
+
+        let handle = app.world.get::<RigidBodyHandleComponent>(enemy).unwrap().handle();
+        let linvel = app.world.get_resource::<RigidBodySet>().unwrap().get(handle).unwrap().linvel().clone();
+        let velocity = Vec2::new(linvel.x, linvel.y);
+        assert!(velocity.length() > 0.0);
+        assert!(velocity.y.abs() > velocity.length() * 0.5);
+    }
+
+    #[test]
+    fn runs_without_a_window_or_sprites() {
+        let mut config = GameConfig::default();
+        config.coop.players = 2;
+        let mut app_builder = App::build();
+        app_builder
+            .add_plugins(MinimalPlugins)
+            .add_plugin(bevy::transform::TransformPlugin)
+            .add_plugin(RapierPhysicsPlugin)
+            .insert_resource(config)
+            .insert_resource(GameRng::from_seed(3))
+            .add_plugin(GameplayPlugin);
+        let mut app = app_builder.app;
+        for _ in 0..3 {
+            app.update();
+        }
+
+        assert_eq!(app.world.query::<&PlayerId>().iter(&app.world).count(), 2);
+        let config = GameConfig::default();
+        assert_eq!(app.world.get_resource::<EntityPool<Bullet>>().unwrap().metrics().capacity, config.pools.bullets);
+        assert!(app.world.get_resource::<SpriteSheets>().is_none());
+    }
+
+    #[test]
+    fn bullets_fly_from_the_frame_they_are_fired() {
+        let mut app_builder = App::build();
+        app_builder
+            .add_plugins(MinimalPlugins)
+            .add_plugin(bevy::transform::TransformPlugin)
+            .add_plugin(RapierPhysicsPlugin)
+            .insert_resource(GameConfig::default())
+            .insert_resource(GameRng::from_seed(3))
+            .add_plugin(GameplayPlugin);
+        let mut app = app_builder.app;
+        app.update();
+        for mut input in app.world.query::<&mut PlayerInput>().iter_mut(&mut app.world) {
+            input.fire = true;
+        }
+        app.update();
+        app.update();
+
+        let bodies = app.world.get_resource::<RigidBodySet>().unwrap();
+        let speeds = app.world
+            .query_filtered::<&RigidBodyHandleComponent, (With<Bullet>, Without<Inactive>)>()
+            .iter(&app.world)
+            .filter_map(|handle| bodies.get(handle.handle()))
+            .map(|rb| (rb.linvel().norm(), rb.is_ccd_enabled()))
+            .collect::<Vec<_>>();
+        assert!(!speeds.is_empty());
+        assert!(speeds.iter().all(|&(speed, ccd)| speed > 0.0 && ccd));
+    }
+
+    fn fire_charged_shots(mut commands: Commands, rapier_config: ResMut<RapierConfiguration>, config: Res<GameConfig>) {
+        for (i, charge) in [0.0, 0.5, 1.0].iter().enumerate() {
+            let owner = commands.spawn().id();
+            let origin = Vec2::new(0.0, i as f32 * 100.0);
+            create_charged_bullet(&mut commands, &rapier_config, None, &config.weapon, owner, origin, Direction::East, *charge, 0);
+        }
+    }
+
+    #[test]
+    fn charged_shots_grow_with_the_charge() {
+        let mut app_builder = App::build();
+        app_builder
+            .add_plugins(MinimalPlugins)
+            .insert_resource(RapierConfiguration::default())
+            .insert_resource(GameConfig::default())
+            .add_startup_system(fire_charged_shots.system());
+        let mut app = app_builder.app;
+        app.update();
+
+        let scale = RapierConfiguration::default().scale;
+        let mut shots = app.world
+            .query::<(&Transform, &Damage, &ColliderBuilder)>()
+            .iter(&app.world)
+            .map(|(transform, damage, collider)| {
+                let size = collider.shape.as_cuboid().unwrap().half_extents.x * 2.0 * scale;
+                (transform.translation.y, size, damage.0)
+            })
+            .collect::<Vec<_>>();
+        shots.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+        // 2.5 times as big and 4 damage at full charge by default
+        let expected = [(BULLET_SIZE, 1), (BULLET_SIZE * 1.75, 3), (BULLET_SIZE * 2.5, 4)];
+        assert_eq!(shots.len(), expected.len());
+        for ((_, size, damage), (expected_size, expected_damage)) in shots.iter().zip(expected.iter()) {
+            assert!((size - expected_size).abs() < 1e-4, "{} px instead of {}", size, expected_size);
+            assert_eq!(damage, expected_damage);
+        }
+    }
+
+    #[test]
+    fn bombs_damage_only_enemies_within_their_radius() {
+        let mut app_builder = App::build();
+        app_builder
+            .add_plugins(MinimalPlugins)
+            .add_plugin(bevy::transform::TransformPlugin)
+            .add_plugin(RapierPhysicsPlugin)
+            .add_plugin(SpatialPlugin)
+            .add_event::<EnemyHitEvent>()
+            .add_event::<EnemyKilledEvent>()
+            .add_event::<BombExploded>()
+            .add_system(detonate_bombs.system());
+        let mut app = app_builder.app;
+        app.world.get_resource_mut::<RapierConfiguration>().unwrap().gravity = Vector2::zeros();
+        let rapier_config = RapierConfiguration::default();
+
+        let mut spawn_enemy = |x: f32, health: i32| {
+            app.world.spawn()
+                .insert(Transform::from_xyz(x, 0.0, 0.0))
+                .insert_bundle(EnemyBundle::new(&rapier_config, WorldPos(Vec2::new(x, 0.0)), PixelsPerSecond(Vec2::ZERO), health))
+                .id()
+        };
+        let near = spawn_enemy(50.0, 10);
+        let weak = spawn_enemy(-80.0, 3);
+        let far = spawn_enemy(200.0, 10);
+        // indexed and given bodies before the bomb goes off
+        app.update();
+        app.update();
+        let bomb = app.world.spawn()
+            .insert(Transform::default())
+            .insert(Bomb { fuse: Timer::from_seconds(0.0, false), radius: 120.0, damage: 5 })
+            .id();
+        app.update();
+
+        assert_eq!(app.world.get::<Health>(near).unwrap().0, 5);
+        assert!(app.world.get::<Dying>(weak).is_some());
+        assert_eq!(app.world.get::<Health>(far).unwrap().0, 10);
+        assert!(app.world.get_entity(bomb).is_none());
+        let explosions = app.world.get_resource::<Events<BombExploded>>().unwrap();
+        assert_eq!(explosions.get_reader().iter(explosions).count(), 1);
+    }
+}
@@ -0,0 +1,69 @@
+use bevy::prelude::*;
+use rand::Rng;
+use game_animation::*;
+use game_data::*;
+use game_spatial::*;
+
+
+/// Killed enemies sometimes leave an ammo pickup where they died, unless enough are lying around
+/// or rounds are endless.
+pub fn drop_ammo(
+    mut commands: Commands,
+    mut killed_events: EventReader<EnemyKilledEvent>,
+    transforms: Query<&Transform>,
+    pickups: Query<(), With<AmmoPickup>>,
+    sheets: Option<Res<SpriteSheets>>,
+    game_materials: Option<Res<GameMaterials>>,
+    mut rng: ResMut<GameRng>,
+    config: Res<GameConfig>,
+) {
+    let visuals = sheets.as_deref().zip(game_materials.as_deref());
+    let ammo = &config.weapon.ammo;
+    if ammo.reserve.is_none() {
+        return;
+    }
+    let mut lying_around = pickups.iter().count() as u32;
+    for killed in killed_events.iter() {
+        if lying_around >= ammo.max_pickups || rng.0.gen::<f32>() >= ammo.drop_chance {
+            continue;
+        }
+        let position = match transforms.get(killed.0) {
+            Ok(transform) => transform.translation.truncate(),
+            Err(_) => continue,
+        };
+        debug!("ammo dropped at {:?}", position);
+        spawn_visual(&mut commands, visuals, "pickup", WorldPos(position), PICKUP_SIZE, |m| m.pickup.clone())
+            .insert(Pickup)
+            .insert(AmmoPickup(ammo.pickup_rounds));
+        lying_around += 1;
+    }
+}
+
+/// The first player close enough with room in their reserve takes an ammo pickup. Upgrades
+/// can give each player a different pickup radius.
+pub fn collect_pickups(
+    mut commands: Commands,
+    pickups: Query<(Entity, &Transform, &AmmoPickup)>,
+    player_index: Res<SpatialIndex<Player>>,
+    mut players: Query<(&mut Ammo, Option<&PlayerStats>), With<Player>>,
+    mut pickup_events: EventWriter<PickupEvent>,
+    config: Res<GameConfig>,
+) {
+    let ammo = &config.weapon.ammo;
+    let pickup_radius = |stats: Option<&PlayerStats>| stats.map_or(ammo.pickup_radius, |stats| stats.pickup_radius);
+    let widest = players.iter_mut().map(|(_, stats)| pickup_radius(stats)).fold(ammo.pickup_radius, f32::max);
+    for (pickup, transform, rounds) in pickups.iter() {
+        let position = transform.translation.truncate();
+        for entry in player_index.within_radius(position, widest) {
+            let taken = players.get_mut(entry.entity).map_or(false, |(mut player_ammo, stats)| {
+                entry.position.distance(position) <= pickup_radius(stats) && player_ammo.refill(rounds.0, ammo.max_reserve)
+            });
+            if taken {
+                debug!("player {:?} picked up {} rounds", entry.entity, rounds.0);
+                commands.entity(pickup).despawn();
+                pickup_events.send(PickupEvent(entry.entity));
+                break;
+            }
+        }
+    }
+}
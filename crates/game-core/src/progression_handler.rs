@@ -0,0 +1,156 @@
+use bevy::prelude::*;
+use bevy_rapier2d::physics::RapierConfiguration;
+use game_animation::*;
+use game_data::*;
+use game_data::Direction as Direction;
+
+
+/// Every killed enemy leaves an experience orb worth its kind's `xp`.
+pub fn drop_xp(
+    mut commands: Commands,
+    mut killed_events: EventReader<EnemyKilledEvent>,
+    enemies: Query<(&Transform, Option<&EnemyKind>)>,
+    definitions: Res<EnemyDefinitions>,
+    sheets: Option<Res<SpriteSheets>>,
+    game_materials: Option<Res<GameMaterials>>,
+) {
+    let visuals = sheets.as_deref().zip(game_materials.as_deref());
+    for killed in killed_events.iter() {
+        let (transform, kind) = match enemies.get(killed.0) {
+            Ok(enemy) => enemy,
+            Err(_) => continue,
+        };
+        let xp = kind.and_then(|kind| definitions.get(&kind.0)).map_or(1, |definition| definition.xp);
+        if xp == 0 {
+            continue;
+        }
+        spawn_visual(&mut commands, visuals, "xp", WorldPos(transform.translation.truncate()), XP_ORB_SIZE, |m| m.xp.clone())
+            .insert(Pickup)
+            .insert(XpPickup(xp));
+    }
+}
+
+/// The closest standing player within their pickup radius takes an orb. Every level it is
+/// worth queues an upgrade choice.
+pub fn collect_xp(
+    mut commands: Commands,
+    orbs: Query<(Entity, &Transform, &XpPickup)>,
+    mut players: Query<(Entity, &Transform, &PlayerStats, &mut Experience), Without<Downed>>,
+    mut pending: ResMut<PendingLevelUps>,
+    mut pickup_events: EventWriter<PickupEvent>,
+    config: Res<GameConfig>,
+) {
+    for (orb, orb_transform, xp) in orbs.iter() {
+        let position = orb_transform.translation.truncate();
+        let closest = players.iter_mut()
+            .map(|(player, transform, stats, _)| (player, transform.translation.truncate().distance(position), stats.pickup_radius))
+            .filter(|(_, distance, radius)| distance <= radius)
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(player, _, _)| player);
+        let player = match closest {
+            Some(player) => player,
+            None => continue,
+        };
+        if let Ok((_, _, _, mut experience)) = players.get_mut(player) {
+            let levels = experience.gain(xp.0, &config.progression);
+            if levels > 0 {
+                info!("player {:?} reached level {}", player, experience.level);
+            }
+            pending.0.extend(std::iter::repeat(player).take(levels as usize));
+        }
+        commands.entity(orb).despawn();
+        pickup_events.send(PickupEvent(player));
+    }
+}
+
+/// Offers the next pending level-up and pauses the game until it is taken. Bots, and local
+/// players when there is no HUD to show the options on, take the first of them straight away;
+/// network players see theirs on their own client.
+pub fn offer_upgrades(
+    mut commands: Commands,
+    choice: Option<Res<UpgradeChoice>>,
+    mut pending: ResMut<PendingLevelUps>,
+    mut paused: ResMut<Paused>,
+    mut players: Query<(&mut PlayerStats, &mut Player, &mut FireCooldown, &mut Health, Option<&Bot>, Option<&RemoteSteps>)>,
+    hud: Option<Res<HudShown>>,
+    definitions: Res<UpgradeDefinitions>,
+    mut rng: ResMut<GameRng>,
+    config: Res<GameConfig>,
+) {
+    if choice.is_some() || pending.0.is_empty() {
+        return;
+    }
+    let player = pending.0.remove(0);
+    let (mut stats, mut player_state, mut cooldown, mut health, bot, remote) = match players.get_mut(player) {
+        Ok(components) => components,
+        Err(_) => return,
+    };
+    let options = definitions.offer(&stats, config.progression.choices, &mut rng.0);
+    if options.is_empty() {
+        debug!("player {:?} has every upgrade there is", player);
+        return;
+    }
+    if bot.is_some() || (remote.is_none() && hud.is_none()) {
+        let upgrade = &definitions.upgrades[options[0]];
+        info!("player {:?} took {} without choosing", player, upgrade.name);
+        stats.upgrade(upgrade, &config, &mut player_state, &mut cooldown, &mut health);
+        return;
+    }
+    debug!("player {:?} is choosing an upgrade", player);
+    commands.insert_resource(UpgradeChoice { player, options, ready: false });
+    paused.0 = true;
+}
+
+/// The choosing player aims left, up or right for the first, second or third upgrade on offer.
+pub fn choose_upgrade(
+    mut commands: Commands,
+    choice: Option<ResMut<UpgradeChoice>>,
+    mut paused: ResMut<Paused>,
+    mut players: Query<(&PlayerInput, &mut PlayerStats, &mut Player, &mut FireCooldown, &mut Health, Option<&Bot>)>,
+    definitions: Res<UpgradeDefinitions>,
+    config: Res<GameConfig>,
+) {
+    let mut choice = match choice {
+        Some(choice) => choice,
+        None => return,
+    };
+    let (input, mut stats, mut player, mut cooldown, mut health, bot) = match players.get_mut(choice.player) {
+        Ok(components) => components,
+        Err(_) => {
+            debug!("player {:?} left before choosing an upgrade", choice.player);
+            commands.remove_resource::<UpgradeChoice>();
+            paused.0 = false;
+            return;
+        }
+    };
+    let slot = match (input.aim, bot) {
+        (_, Some(_)) => 0,
+        (None, None) => {
+            choice.ready = true;
+            return;
+        },
+        (Some(_), None) if !choice.ready => return,
+        (Some(Direction::West), None) => 0,
+        (Some(Direction::North), None) => 1,
+        (Some(Direction::East), None) => 2,
+        (Some(_), None) => return,
+    };
+    let upgrade = match choice.options.get(slot).and_then(|&index| definitions.upgrades.get(index)) {
+        Some(upgrade) => upgrade,
+        None => return,
+    };
+    info!("player {:?} took {}", choice.player, upgrade.name);
+    stats.upgrade(upgrade, &config, &mut player, &mut cooldown, &mut health);
+    commands.remove_resource::<UpgradeChoice>();
+    paused.0 = false;
+}
+
+/// stops the physics step while paused, so bodies keep their velocities for when it resumes
+pub fn pause_physics(
+    paused: Res<Paused>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+) {
+    if paused.is_changed() {
+        rapier_config.physics_pipeline_active = !paused.0;
+    }
+}
@@ -0,0 +1,127 @@
+use bevy::prelude::*;
+use bevy_rapier2d::physics::{ColliderHandleComponent, RapierConfiguration, RigidBodyHandleComponent};
+use bevy_rapier2d::rapier::dynamics::RigidBodySet;
+use bevy_rapier2d::rapier::geometry::ColliderSet;
+use game_animation::*;
+use game_collisions::*;
+use game_data::*;
+use game_data::Direction as Direction;
+use game_pool::*;
+use game_spatial::*;
+use crate::*;
+
+
+/// Homing bullets turn towards the nearest enemy in range every frame.
+pub fn steer_homing_bullets(
+    mut bullets: Query<(&Transform, &Homing, &Bullet, &mut Heading, &mut Direction, &RigidBodyHandleComponent), Without<Inactive>>,
+    enemies: Res<SpatialIndex<Enemy>>,
+    mut rigid_bodies: ResMut<RigidBodySet>,
+    rapier_config: Res<RapierConfiguration>,
+    time: Res<Time>,
+) {
+    let units = Units::new(&rapier_config);
+    for (transform, homing, bullet, mut heading, mut direction, rigid_body_handle) in bullets.iter_mut() {
+        let position = transform.translation.truncate();
+        let target = match enemies.nearest(position, 1).first() {
+            Some(target) if target.position.distance(position) <= homing.range => target.position,
+            _ => continue,
+        };
+        heading.0 = turn_towards(heading.0, target - position, homing.turn_rate * time.delta_seconds());
+        if let Some(facing) = Direction::from_axes(heading.0) {
+            *direction = facing;
+        }
+        if let Some(rb) = rigid_bodies.get_mut(rigid_body_handle.handle()) {
+            rb.set_linvel(units.velocity_to_physics(PixelsPerSecond(heading.0 * bullet.0)).vector(), true);
+        }
+    }
+}
+
+/// Split bullets fan plain fragments out past the enemy they hit, explosive ones damage every
+/// other enemy around it.
+pub fn resolve_projectile_hits(
+    mut commands: Commands,
+    mut hits: EventReader<ProjectileHitEvent>,
+    enemy_index: Res<SpatialIndex<Enemy>>,
+    mut enemies: Query<(&mut Health, &RigidBodyHandleComponent, &ColliderHandleComponent), (With<Enemy>, Without<Dying>, Without<Inactive>)>,
+    mut bodies: ResMut<RigidBodySet>,
+    mut colliders: ResMut<ColliderSet>,
+    rapier_config: ResMut<RapierConfiguration>,
+    sheets: Option<Res<SpriteSheets>>,
+    game_materials: Option<Res<GameMaterials>>,
+    mut bullet_pool: ResMut<EntityPool<Bullet>>,
+    mut hit_events: EventWriter<EnemyHitEvent>,
+    mut killed_events: EventWriter<EnemyKilledEvent>,
+    config: Res<GameConfig>,
+) {
+    let visuals = sheets.as_deref().zip(game_materials.as_deref());
+    for hit in hits.iter() {
+        if let (Some(split), Some(owner)) = (hit.split, hit.owner) {
+            // start beyond the enemy so the fragments don't hit it again straight away
+            let origin = hit.position + hit.heading * (ENEMY_SIZE + BULLET_SIZE);
+            for heading in fan(hit.heading, split.fragments, split.spread) {
+                let fragment = fire_projectile(&mut commands, &rapier_config, visuals, &mut bullet_pool, &config.weapon, owner, WorldPos(origin), heading);
+                ProjectileConfig::default().arm(&mut commands.entity(fragment));
+            }
+        }
+
+        if let Some(explosive) = hit.explosive {
+            debug!("explosion at {:?}", hit.position);
+            let hit_enemies = enemy_index
+                .within_radius(hit.position, explosive.radius)
+                .map(|entry| entry.entity)
+                .filter(|&enemy| enemy != hit.enemy);
+            for enemy in hit_enemies {
+                if let Ok((mut health, rigid_body_handle, collider_handle)) = enemies.get_mut(enemy) {
+                    if damage_enemy(&mut health, explosive.damage, enemy, &mut hit_events) {
+                        kill_enemy(&mut commands, &mut bodies, &mut colliders, enemy, rigid_body_handle, collider_handle);
+                        killed_events.send(EnemyKilledEvent(enemy, hit.owner));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Bombs whose fuse ran out damage every enemy around them and disappear.
+pub fn detonate_bombs(
+    mut commands: Commands,
+    mut bombs: Query<(Entity, &Transform, &mut Bomb, Option<&Owner>)>,
+    enemy_index: Res<SpatialIndex<Enemy>>,
+    mut enemies: Query<(&mut Health, &RigidBodyHandleComponent, &ColliderHandleComponent), (With<Enemy>, Without<Dying>, Without<Inactive>)>,
+    mut bodies: ResMut<RigidBodySet>,
+    mut colliders: ResMut<ColliderSet>,
+    mut hit_events: EventWriter<EnemyHitEvent>,
+    mut killed_events: EventWriter<EnemyKilledEvent>,
+    mut explosions: EventWriter<BombExploded>,
+    time: Res<Time>,
+) {
+    for (bomb_entity, transform, mut bomb, owner) in bombs.iter_mut() {
+        bomb.fuse.tick(time.delta());
+        if !bomb.fuse.finished() {
+            continue;
+        }
+        let position = transform.translation.truncate();
+        debug!("bomb went off at {:?}", position);
+        for entry in enemy_index.within_radius(position, bomb.radius) {
+            if let Ok((mut health, rigid_body_handle, collider_handle)) = enemies.get_mut(entry.entity) {
+                if damage_enemy(&mut health, bomb.damage, entry.entity, &mut hit_events) {
+                    kill_enemy(&mut commands, &mut bodies, &mut colliders, entry.entity, rigid_body_handle, collider_handle);
+                    killed_events.send(EnemyKilledEvent(entry.entity, owner.map(|owner| owner.0)));
+                }
+            }
+        }
+        explosions.send(BombExploded { position, radius: bomb.radius });
+        commands.entity(bomb_entity).despawn();
+    }
+}
+
+/// Takes `damage` off an enemy still alive, returns whether that killed it.
+fn damage_enemy(health: &mut Health, damage: i32, enemy: Entity, hit_events: &mut EventWriter<EnemyHitEvent>) -> bool {
+    // already killed by an earlier hit this frame
+    if health.0 <= 0 {
+        return false;
+    }
+    health.0 -= damage;
+    hit_events.send(EnemyHitEvent(enemy));
+    health.0 <= 0
+}
@@ -0,0 +1,84 @@
+use bevy::prelude::*;
+use game_data::*;
+
+
+/// another enemy near the one being steered
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Neighbour {
+    pub position: Vec2,
+    /// pixels per second
+    pub velocity: Vec2,
+}
+
+/// What an enemy sees when it picks its heading.
+pub struct Surroundings<'a> {
+    /// nearest living player, `None` once everyone is down
+    pub target: Option<Vec2>,
+    /// unit vector the flow field points along, `None` to head straight at `target`
+    pub path: Option<Vec2>,
+    pub neighbours: &'a [Neighbour],
+    /// position and radius of every `Obstacle`
+    pub obstacles: &'a [(Vec2, f32)],
+}
+
+/// Blends seek, separation, alignment, cohesion and avoidance into a heading at most 1 long,
+/// to be scaled by the enemy's speed.
+pub fn steer(position: Vec2, surroundings: &Surroundings, weights: &SteeringWeights, config: &EnemyConfig) -> Vec2 {
+    let seek = match (surroundings.target, surroundings.path) {
+        (Some(_), Some(path)) => path,
+        (Some(target), None) => (target - position).normalize_or_zero(),
+        (None, _) => Vec2::ZERO,
+    };
+
+    let mut separation = Vec2::ZERO;
+    let mut heading = Vec2::ZERO;
+    let mut centre = Vec2::ZERO;
+    let mut flock = 0;
+    for neighbour in surroundings.neighbours.iter() {
+        let offset = position - neighbour.position;
+        let distance = offset.length();
+        if distance > config.neighbour_radius {
+            continue;
+        }
+        // enemies stacked exactly on top of each other can't tell which way is away
+        if distance > 0.0 && distance < config.separation_radius {
+            separation += offset / distance * (1.0 - distance / config.separation_radius);
+        }
+        heading += neighbour.velocity.normalize_or_zero();
+        centre += neighbour.position;
+        flock += 1;
+    }
+    let (alignment, cohesion) = if flock > 0 {
+        (heading.normalize_or_zero(), (centre / flock as f32 - position).normalize_or_zero())
+    } else {
+        (Vec2::ZERO, Vec2::ZERO)
+    };
+
+    let mut avoidance = Vec2::ZERO;
+    for (obstacle, radius) in surroundings.obstacles.iter() {
+        let offset = position - *obstacle;
+        let clearance = offset.length() - radius;
+        if clearance < config.avoid_distance {
+            let away = offset.normalize_or_zero();
+            // pushing straight back would cancel seek and pin enemies against the obstacle,
+            // so ones heading into it also slide around whichever side is closer to the target
+            let mut around = Vec2::new(-away.y, away.x);
+            if around.dot(seek) < 0.0 {
+                around = -around;
+            }
+            let ahead = if seek.dot(away) < 0.0 { around } else { Vec2::ZERO };
+            avoidance += (away + ahead) * (1.0 - clearance.max(0.0) / config.avoid_distance);
+        }
+    }
+
+    let steering = seek * weights.seek
+        + separation * weights.separation
+        + alignment * weights.alignment
+        + cohesion * weights.cohesion
+        + avoidance * weights.avoidance;
+    if steering.length() > 1.0 {
+        steering.normalize()
+    } else {
+        steering
+    }
+}
@@ -0,0 +1,137 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::time::Duration;
+use crate::AmmoConfig;
+
+
+/// what interrupts a reload
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum ReloadCancel {
+    /// a reload always runs to the end
+    Never,
+    /// firing with rounds still in the magazine drops the reload
+    Fire,
+}
+
+/// the ammo pickup's rounds go to the reserve of the player who walks over it
+pub struct AmmoPickup(pub u32);
+
+/// Rounds a player's weapon has left. Without a magazine, shots come straight out of the
+/// reserve; without a reserve, rounds are endless.
+#[derive(Debug, Clone)]
+pub struct Ammo {
+    /// rounds in the magazine, always 0 without one
+    pub loaded: u32,
+    /// rounds carried besides the magazine, `None` for endless
+    pub reserve: Option<u32>,
+    reload: Option<Timer>,
+}
+
+impl Ammo {
+    /// a full magazine and the starting reserve
+    pub fn new(config: &AmmoConfig) -> Self {
+        Ammo {
+            loaded: config.magazine.unwrap_or(0),
+            reserve: config.reserve,
+            reload: None,
+        }
+    }
+
+    pub fn can_fire(&self, config: &AmmoConfig) -> bool {
+        if self.is_reloading() {
+            return false;
+        }
+        match config.magazine {
+            Some(_) => self.loaded > 0,
+            None => self.reserve.map_or(true, |reserve| reserve > 0),
+        }
+    }
+
+    pub fn use_round(&mut self, config: &AmmoConfig) {
+        match (config.magazine, self.reserve.as_mut()) {
+            (Some(_), _) => self.loaded = self.loaded.saturating_sub(1),
+            (None, Some(reserve)) => *reserve = reserve.saturating_sub(1),
+            (None, None) => (),
+        }
+    }
+
+    /// magazine is empty and a reload could fill it
+    pub fn needs_reload(&self, config: &AmmoConfig) -> bool {
+        config.magazine.is_some() && self.loaded == 0 && self.has_reserve()
+    }
+
+    /// Starts a reload unless one is running, the magazine is full or there is nothing to load.
+    pub fn start_reload(&mut self, config: &AmmoConfig) -> bool {
+        let room = match config.magazine {
+            Some(size) => self.loaded < size,
+            None => false,
+        };
+        if self.is_reloading() || !room || !self.has_reserve() {
+            return false;
+        }
+        self.reload = Some(Timer::from_seconds(config.reload_time, false));
+        true
+    }
+
+    /// Advances a running reload, returns whether it finished and filled the magazine.
+    pub fn tick(&mut self, delta: Duration, config: &AmmoConfig) -> bool {
+        let finished = match self.reload.as_mut() {
+            Some(reload) => reload.tick(delta).finished(),
+            None => false,
+        };
+        if !finished {
+            return false;
+        }
+        self.reload = None;
+        let missing = config.magazine.unwrap_or(0).saturating_sub(self.loaded);
+        let rounds = match self.reserve.as_mut() {
+            Some(reserve) => {
+                let rounds = missing.min(*reserve);
+                *reserve -= rounds;
+                rounds
+            },
+            None => missing,
+        };
+        self.loaded += rounds;
+        true
+    }
+
+    pub fn cancel_reload(&mut self) {
+        self.reload = None;
+    }
+
+    pub fn is_reloading(&self) -> bool {
+        self.reload.is_some()
+    }
+
+    /// how far a running reload is, 0.0 to 1.0
+    pub fn reload_progress(&self) -> Option<f32> {
+        self.reload.as_ref().map(Timer::percent)
+    }
+
+    pub fn reload_elapsed(&self) -> Option<Duration> {
+        self.reload.as_ref().map(Timer::elapsed)
+    }
+
+    /// puts back a reload `elapsed` into running, e.g. from a save
+    pub fn resume_reload(&mut self, elapsed: Duration, config: &AmmoConfig) {
+        let mut reload = Timer::from_seconds(config.reload_time, false);
+        reload.set_elapsed(elapsed);
+        self.reload = Some(reload);
+    }
+
+    /// Adds `rounds` to a limited reserve, up to `max_reserve`. Returns whether any were taken.
+    pub fn refill(&mut self, rounds: u32, max_reserve: u32) -> bool {
+        match self.reserve.as_mut() {
+            Some(reserve) if *reserve < max_reserve => {
+                *reserve = (*reserve + rounds).min(max_reserve);
+                true
+            },
+            _ => false,
+        }
+    }
+
+    fn has_reserve(&self) -> bool {
+        self.reserve.map_or(true, |reserve| reserve > 0)
+    }
+}
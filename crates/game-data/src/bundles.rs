@@ -0,0 +1,167 @@
+use bevy::prelude::*;
+use bevy_rapier2d::physics::RapierConfiguration;
+use bevy_rapier2d::rapier::dynamics::RigidBodyBuilder;
+use bevy_rapier2d::rapier::geometry::{ColliderBuilder, InteractionGroups};
+use crate::*;
+use crate::Direction as Direction;
+
+
+/// on-screen sizes in pixels, colliders are built from these
+pub const PLAYER_SIZE: f32 = 40.0;
+pub const ENEMY_SIZE: f32 = 10.0;
+pub const BULLET_SIZE: f32 = 5.0;
+pub const BOMB_SIZE: f32 = 12.0;
+pub const PICKUP_SIZE: f32 = 14.0;
+pub const XP_ORB_SIZE: f32 = 8.0;
+pub const WALL_THICKNESS: f32 = 40.0;
+
+/// Solver group of enemies. Bullets leave it out of their filter, so touching an enemy still
+/// raises a contact event but never slows or deflects the bullet, and piercing ones fly on.
+const ENEMY_SOLVER_GROUP: u32 = 0b1;
+
+fn rigid_body(units: Units, position: WorldPos, velocity: PixelsPerSecond) -> RigidBodyBuilder {
+    let position = units.to_physics(position).0;
+    let velocity = units.velocity_to_physics(velocity).0;
+    RigidBodyBuilder::new_dynamic()
+        .translation(position.x, position.y)
+        .linvel(velocity.x, velocity.y)
+}
+
+fn square_collider(units: Units, size: f32) -> ColliderBuilder {
+    let half_size = units.length_to_physics(size / 2.0);
+    ColliderBuilder::cuboid(half_size, half_size)
+}
+
+/// Gameplay and physics components of an entity; visuals are added by the caller,
+/// so the same bundles work with sprites, headless and in tests.
+#[derive(Bundle)]
+pub struct PlayerBundle {
+    pub player: Player,
+    pub id: PlayerId,
+    pub input: PlayerInput,
+    pub score: PlayerScore,
+    pub health: Health,
+    pub status: StatusEffects,
+    pub cooldown: FireCooldown,
+    pub bombs: Bombs,
+    pub ammo: Ammo,
+    pub stats: PlayerStats,
+    pub experience: Experience,
+    pub direction: Direction,
+    pub rigid_body: RigidBodyBuilder,
+    pub collider: ColliderBuilder,
+}
+
+impl PlayerBundle {
+    pub fn new(rapier_config: &RapierConfiguration, config: &GameConfig, id: PlayerId, position: WorldPos) -> Self {
+        let units = Units::new(rapier_config);
+        PlayerBundle {
+            player: Player::from_config(&config.player),
+            id,
+            input: PlayerInput::default(),
+            score: PlayerScore::default(),
+            health: Health(config.player.max_health),
+            status: StatusEffects::default(),
+            cooldown: FireCooldown::new(config.weapon.fire_mode, config.weapon.fire_interval),
+            bombs: Bombs::new(config.weapon.bomb.ammo),
+            ammo: Ammo::new(&config.weapon.ammo),
+            stats: PlayerStats::new(config),
+            experience: Experience::default(),
+            direction: Direction::East,
+            rigid_body: rigid_body(units, position, PixelsPerSecond::default()),
+            collider: square_collider(units, PLAYER_SIZE),
+        }
+    }
+}
+
+#[derive(Bundle)]
+pub struct EnemyBundle {
+    pub enemy: Enemy,
+    pub health: Health,
+    pub status: StatusEffects,
+    pub rigid_body: RigidBodyBuilder,
+    pub collider: ColliderBuilder,
+}
+
+impl EnemyBundle {
+    pub fn new(rapier_config: &RapierConfiguration, position: WorldPos, velocity: PixelsPerSecond, health: i32) -> Self {
+        let units = Units::new(rapier_config);
+        EnemyBundle {
+            enemy: Enemy,
+            health: Health(health),
+            status: StatusEffects::default(),
+            rigid_body: rigid_body(units, position, velocity),
+            collider: square_collider(units, ENEMY_SIZE)
+                .solver_groups(InteractionGroups::new(ENEMY_SOLVER_GROUP, u32::MAX)),
+        }
+    }
+}
+
+#[derive(Bundle)]
+pub struct ProjectileBundle {
+    pub bullet: Bullet,
+    pub heading: Heading,
+    pub direction: Direction,
+    pub lifetime: BulletLifetime,
+    pub rigid_body: RigidBodyBuilder,
+    pub collider: ColliderBuilder,
+}
+
+impl ProjectileBundle {
+    /// `lifetime` in seconds
+    pub fn new(
+        rapier_config: &RapierConfiguration,
+        position: WorldPos,
+        velocity: PixelsPerSecond,
+        direction: Direction,
+        lifetime: f32,
+    ) -> Self {
+        let units = Units::new(rapier_config);
+        ProjectileBundle {
+            bullet: Bullet(velocity.0.length()),
+            // parked bullets stand still but still need a way to face
+            heading: Heading(if velocity.0 == Vec2::ZERO { direction.unit() } else { velocity.0.normalize() }),
+            direction,
+            lifetime: BulletLifetime(Timer::from_seconds(lifetime, true)),
+            // bullets cross a 10 px enemy in a frame or two, so sweep them instead of testing
+            // where they land
+            rigid_body: rigid_body(units, position, velocity).ccd_enabled(true),
+            collider: bullet_collider(units, BULLET_SIZE),
+        }
+    }
+
+    /// `size` pixels across instead of `BULLET_SIZE`
+    pub fn sized(self, rapier_config: &RapierConfiguration, size: f32) -> Self {
+        ProjectileBundle {
+            collider: bullet_collider(Units::new(rapier_config), size),
+            ..self
+        }
+    }
+}
+
+fn bullet_collider(units: Units, size: f32) -> ColliderBuilder {
+    square_collider(units, size).solver_groups(InteractionGroups::new(u32::MAX, !ENEMY_SOLVER_GROUP))
+}
+
+/// A static box; `WALL_THICKNESS` thick ones fence the arena in.
+#[derive(Bundle)]
+pub struct WallBundle {
+    pub wall: Wall,
+    pub transform: Transform,
+    pub rigid_body: RigidBodyBuilder,
+    pub collider: ColliderBuilder,
+}
+
+impl WallBundle {
+    /// `half_size` in pixels around `position`
+    pub fn new(rapier_config: &RapierConfiguration, position: WorldPos, half_size: Vec2) -> Self {
+        let units = Units::new(rapier_config);
+        let translation = units.to_physics(position).0;
+        WallBundle {
+            wall: Wall,
+            transform: Transform::from_translation(position.0.extend(0.0)),
+            rigid_body: RigidBodyBuilder::new_static().translation(translation.x, translation.y),
+            collider: ColliderBuilder::cuboid(units.length_to_physics(half_size.x), units.length_to_physics(half_size.y)),
+        }
+    }
+}
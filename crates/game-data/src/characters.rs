@@ -0,0 +1,178 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use crate::{ConfigError, PlayerStats, UpgradeEffect};
+
+
+/// relative to the asset folder, see `asset_path`
+pub const CHARACTER_DEFINITIONS_ASSET: &str = "config/characters.characters.ron";
+
+/// what a character can do that the others can't
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum Passive {
+    /// heals 1 health every `seconds` while standing, up to max health
+    Regeneration { seconds: f32 },
+    /// a bonus that lasts the whole run, same as taking an upgrade
+    Stat(UpgradeEffect),
+}
+
+/// Someone to play a run as. Speeds and health are relative to `player` in the game config,
+/// so tuning the config tunes every character.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct CharacterDefinition {
+    pub name: String,
+    pub description: String,
+    /// multiplies `player.max_velocity` and `player.acceleration`
+    #[serde(default = "default_multiplier")]
+    pub speed: f32,
+    /// multiplies acceleration on top of `speed`: above 1 gets up to speed quicker, below 1 slower
+    #[serde(default = "default_multiplier")]
+    pub acceleration: f32,
+    /// added to `player.max_health`
+    #[serde(default)]
+    pub health: i32,
+    /// `WeaponDefinition` the character starts with unless the profile equips another one
+    pub weapon: String,
+    #[serde(default)]
+    pub passive: Option<Passive>,
+    /// sheet in `assets/sprites.ron`
+    pub sprite: String,
+}
+
+fn default_multiplier() -> f32 {
+    1.0
+}
+
+impl CharacterDefinition {
+    /// puts the character's speeds, health and stat passive into `stats`
+    pub fn apply(&self, stats: &mut PlayerStats) {
+        stats.move_speed *= self.speed;
+        stats.acceleration *= self.acceleration;
+        stats.max_health += self.health;
+        if let Some(Passive::Stat(effect)) = self.passive {
+            stats.apply_effect(effect);
+        }
+    }
+
+    pub fn regeneration(&self) -> Option<Regeneration> {
+        match self.passive {
+            Some(Passive::Regeneration { seconds }) => Some(Regeneration(Timer::from_seconds(seconds, true))),
+            _ => None,
+        }
+    }
+}
+
+/// which `CharacterDefinition` a player is playing
+#[derive(Debug, Clone, PartialEq)]
+pub struct Character(pub String);
+
+/// heals the player 1 health every time the timer finishes
+pub struct Regeneration(pub Timer);
+
+/// Every character, inserted as a resource. The first one is unlocked from the start.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct CharacterDefinitions {
+    pub characters: Vec<CharacterDefinition>,
+}
+
+impl Default for CharacterDefinitions {
+    fn default() -> Self {
+        CharacterDefinitions {
+            characters: vec![
+                CharacterDefinition {
+                    name: "soldier".to_owned(),
+                    description: "steady all-rounder with the charger".to_owned(),
+                    speed: 1.0,
+                    acceleration: 1.0,
+                    health: 0,
+                    weapon: "charger".to_owned(),
+                    passive: None,
+                    sprite: "player".to_owned(),
+                },
+                CharacterDefinition {
+                    name: "scout".to_owned(),
+                    description: "fast and fragile, grabs pickups from further away, starts with the repeater".to_owned(),
+                    speed: 1.25,
+                    acceleration: 1.2,
+                    health: -1,
+                    weapon: "repeater".to_owned(),
+                    passive: Some(Passive::Stat(UpgradeEffect::PickupRadius { pixels: 25.0 })),
+                    sprite: "scout".to_owned(),
+                },
+                CharacterDefinition {
+                    name: "tank".to_owned(),
+                    description: "slow and sturdy, regenerates health, starts with the scattergun".to_owned(),
+                    speed: 0.8,
+                    acceleration: 0.85,
+                    health: 2,
+                    weapon: "scattergun".to_owned(),
+                    passive: Some(Passive::Regeneration { seconds: 5.0 }),
+                    sprite: "tank".to_owned(),
+                },
+            ],
+        }
+    }
+}
+
+impl CharacterDefinitions {
+    pub fn load(path: impl AsRef<Path>) -> Result<CharacterDefinitions, ConfigError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_owned(), e))?;
+        let definitions = CharacterDefinitions::parse(&text).map_err(|e| match e {
+            ConfigError::Parse(_, e) => ConfigError::Parse(path.to_owned(), e),
+            e => e,
+        })?;
+        Ok(definitions)
+    }
+
+    pub fn parse(text: &str) -> Result<CharacterDefinitions, ConfigError> {
+        let definitions: CharacterDefinitions = ron::from_str(text).map_err(|e| ConfigError::Parse(PathBuf::new(), e))?;
+        definitions.validate()?;
+        Ok(definitions)
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = vec![];
+        if self.characters.is_empty() {
+            problems.push("characters must contain at least one character".to_owned());
+        }
+        for (i, character) in self.characters.iter().enumerate() {
+            if character.name.is_empty() {
+                problems.push(format!("characters[{}].name must not be empty", i));
+            }
+            if self.characters[..i].iter().any(|other| other.name == character.name) {
+                problems.push(format!("characters[{}].name {:?} is defined twice", i, character.name));
+            }
+            if !(character.speed > 0.0) {
+                problems.push(format!("characters[{}].speed must be greater than 0, got {}", i, character.speed));
+            }
+            if !(character.acceleration > 0.0) {
+                problems.push(format!("characters[{}].acceleration must be greater than 0, got {}", i, character.acceleration));
+            }
+            match character.passive {
+                Some(Passive::Regeneration { seconds }) if !(seconds > 0.0) => {
+                    problems.push(format!("characters[{}].passive regeneration seconds must be greater than 0, got {}", i, seconds));
+                },
+                Some(Passive::Stat(effect)) if !effect.improves() => {
+                    problems.push(format!("characters[{}].passive must improve something, got {:?}", i, effect));
+                },
+                _ => (),
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&CharacterDefinition> {
+        self.characters.iter().find(|character| character.name == name)
+    }
+
+    /// the character every profile starts with
+    pub fn starter(&self) -> &CharacterDefinition {
+        self.characters.first().expect("validated definitions are never empty")
+    }
+}
@@ -0,0 +1,501 @@
+use bevy::reflect::TypeUuid;
+use serde::Deserialize;
+use std::fmt;
+use crate::{FireMode, ReloadCancel, TimedEffect};
+use std::path::{Path, PathBuf};
+
+
+/// relative to the asset folder, read with `asset_path` at startup and by the asset server for reloads
+pub const CONFIG_ASSET: &str = "config/game.config.ron";
+
+/// Every tunable gameplay value. Loaded from `CONFIG_ASSET` at startup and inserted as a resource.
+/// Distances are in pixels and speeds in pixels per second, see `Units`.
+#[derive(Debug, Clone, PartialEq, Deserialize, TypeUuid)]
+#[uuid = "6f1d2a3e-6c55-4f4b-9a8e-2b7c1d9e4a10"]
+#[serde(default)]
+pub struct GameConfig {
+    pub player: PlayerConfig,
+    pub weapon: WeaponConfig,
+    pub enemies: EnemyConfig,
+    pub pools: PoolConfig,
+    pub coop: CoopConfig,
+    pub progression: ProgressionConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct PlayerConfig {
+    pub max_health: i32,
+    pub max_velocity: f32,
+    pub acceleration: f32,
+    /// fraction of the velocity lost every frame
+    pub friction: f32,
+    /// flat slowdown per frame while moving right/up
+    pub friction_step_positive: f32,
+    /// flat slowdown per frame while moving left/down
+    pub friction_step_negative: f32,
+    /// seconds a player can't be hurt again after a hit
+    pub hurt_time: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct WeaponConfig {
+    /// seconds between shots while fire is held
+    pub fire_interval: f32,
+    pub fire_mode: FireMode,
+    pub bullet_speed: f32,
+    /// seconds before a bullet that hit nothing is removed
+    pub bullet_lifetime: f32,
+    /// distance from the player's centre where bullets appear
+    pub muzzle_offset: f32,
+    /// status effects a bullet puts on the enemy it hits
+    pub on_hit: Vec<TimedEffect>,
+    pub projectile: ProjectileConfig,
+    pub charged: ChargedShotConfig,
+    pub bomb: BombConfig,
+    pub ammo: AmmoConfig,
+}
+
+/// Magazine, reserve and reload, and the ammo enemies drop. Rounds are endless by default.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct AmmoConfig {
+    /// rounds per magazine, `None` fires straight from the reserve and never reloads
+    pub magazine: Option<u32>,
+    /// rounds each player starts with besides the magazine, `None` for endless
+    pub reserve: Option<u32>,
+    /// pickups don't fill the reserve past this
+    pub max_reserve: u32,
+    /// seconds to fill the magazine
+    pub reload_time: f32,
+    pub reload_cancel: ReloadCancel,
+    /// chance from 0 to 1 that a killed enemy drops an ammo pickup
+    pub drop_chance: f32,
+    /// rounds in one pickup
+    pub pickup_rounds: u32,
+    /// no more pickups drop while this many are lying around
+    pub max_pickups: u32,
+    /// how close a player has to get to collect a pickup
+    pub pickup_radius: f32,
+}
+
+/// What a charged shot, see `FireMode::Charged`, turns into. Size and damage grow with the charge
+/// from a plain bullet's up to these at full charge.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ChargedShotConfig {
+    /// charge from 0 to 1 below which a plain bullet is fired
+    pub min_charge: f32,
+    /// size at full charge, in plain bullet sizes
+    pub size: f32,
+    pub damage: i32,
+}
+
+/// alternate fire
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct BombConfig {
+    /// bombs each player starts with
+    pub ammo: u32,
+    /// seconds from dropping a bomb to it going off
+    pub fuse: f32,
+    pub radius: f32,
+    pub damage: i32,
+}
+
+/// Modifiers every bullet is fired with. They combine, e.g. a piercing bullet that explodes on
+/// every enemy it passes through.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ProjectileConfig {
+    /// degrees per second bullets turn towards the nearest enemy, 0 flies straight
+    pub homing_turn_rate: f32,
+    /// how far away homing bullets pick a target
+    pub homing_range: f32,
+    /// static colliders a bullet bounces off before it is used up
+    pub ricochets: u32,
+    /// enemies a bullet passes through before it is used up
+    pub pierce: u32,
+    /// plain bullets fired in a fan from every enemy hit, 0 for none
+    pub split_fragments: u32,
+    /// degrees between the outermost fragments
+    pub split_spread: f32,
+    /// every other enemy this close to a hit takes `explosion_damage`, 0 for no explosion
+    pub explosion_radius: f32,
+    pub explosion_damage: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct EnemyConfig {
+    pub spawn_interval: f32,
+    pub max_enemies: i32,
+    /// enemies never spawn closer than this to the player
+    pub spawn_buffer: f32,
+    /// enemies spawn within ±arena_half_size around the origin
+    pub arena_half_size: (f32, f32),
+    /// other enemies closer than this count as neighbours for alignment and cohesion
+    pub neighbour_radius: f32,
+    /// enemies closer than this push each other apart
+    pub separation_radius: f32,
+    /// how close enemies get to an `Obstacle` before steering around it
+    pub avoid_distance: f32,
+    /// team kills that end a wave
+    pub wave_kills: u32,
+    /// every wave multiplies `spawn_interval` by this, below 1 spawns faster
+    pub wave_spawn_factor: f32,
+}
+
+impl EnemyConfig {
+    /// the wave a team with `kills` kills is on, starting at 1
+    pub fn wave_at(&self, kills: u32) -> u32 {
+        1 + kills / self.wave_kills.max(1)
+    }
+
+    /// seconds between spawns during `wave`
+    pub fn spawn_interval_at(&self, wave: u32) -> f32 {
+        self.spawn_interval * self.wave_spawn_factor.powi(wave.saturating_sub(1) as i32)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct PoolConfig {
+    pub bullets: usize,
+    pub enemies: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct CoopConfig {
+    /// local players, 1 to 4. Two players split the keyboard, everyone can also use a gamepad.
+    pub players: u8,
+    pub scoring: ScoreMode,
+    /// seconds a teammate has to stay next to a downed player
+    pub revive_time: f32,
+    /// how close, in pixels, a teammate has to be to revive
+    pub revive_radius: f32,
+    /// health a revived player comes back with
+    pub revive_health: i32,
+}
+
+/// Experience and levelling, see `UpgradeDefinitions` for what a level-up offers.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ProgressionConfig {
+    /// experience from level 1 to 2
+    pub first_level_xp: u32,
+    /// every level needs this many times the experience of the one before
+    pub level_growth: f32,
+    /// upgrades offered per level-up
+    pub choices: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum ScoreMode {
+    /// every kill goes to the team score
+    Shared,
+    /// kills also count towards the `PlayerScore` of whoever fired the bullet
+    Split,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig {
+            player: PlayerConfig::default(),
+            weapon: WeaponConfig::default(),
+            enemies: EnemyConfig::default(),
+            pools: PoolConfig::default(),
+            coop: CoopConfig::default(),
+            progression: ProgressionConfig::default(),
+        }
+    }
+}
+
+impl Default for PlayerConfig {
+    fn default() -> Self {
+        PlayerConfig {
+            max_health: 3,
+            max_velocity: 400.0,
+            acceleration: 1000.0,
+            friction: 0.02,
+            friction_step_positive: 2.0,
+            friction_step_negative: 6.0,
+            hurt_time: 1.0,
+        }
+    }
+}
+
+impl Default for WeaponConfig {
+    fn default() -> Self {
+        WeaponConfig {
+            fire_interval: 0.15,
+            fire_mode: FireMode::Hold,
+            bullet_speed: 600.0,
+            bullet_lifetime: 1.5,
+            muzzle_offset: 21.0,
+            on_hit: vec![],
+            projectile: ProjectileConfig::default(),
+            charged: ChargedShotConfig::default(),
+            bomb: BombConfig::default(),
+            ammo: AmmoConfig::default(),
+        }
+    }
+}
+
+impl Default for ProjectileConfig {
+    fn default() -> Self {
+        ProjectileConfig {
+            homing_turn_rate: 0.0,
+            homing_range: 300.0,
+            ricochets: 0,
+            pierce: 0,
+            split_fragments: 0,
+            split_spread: 60.0,
+            explosion_radius: 0.0,
+            explosion_damage: 1,
+        }
+    }
+}
+
+impl Default for ChargedShotConfig {
+    fn default() -> Self {
+        ChargedShotConfig {
+            min_charge: 0.2,
+            size: 2.5,
+            damage: 4,
+        }
+    }
+}
+
+impl Default for BombConfig {
+    fn default() -> Self {
+        BombConfig {
+            ammo: 3,
+            fuse: 1.0,
+            radius: 120.0,
+            damage: 5,
+        }
+    }
+}
+
+impl Default for AmmoConfig {
+    fn default() -> Self {
+        AmmoConfig {
+            magazine: None,
+            reserve: None,
+            max_reserve: 180,
+            reload_time: 1.2,
+            reload_cancel: ReloadCancel::Fire,
+            drop_chance: 0.15,
+            pickup_rounds: 24,
+            max_pickups: 4,
+            pickup_radius: 30.0,
+        }
+    }
+}
+
+impl Default for EnemyConfig {
+    fn default() -> Self {
+        EnemyConfig {
+            spawn_interval: 3.0,
+            max_enemies: 20,
+            spawn_buffer: 50.0,
+            arena_half_size: (640.0, 360.0),
+            neighbour_radius: 120.0,
+            separation_radius: 50.0,
+            avoid_distance: 60.0,
+            wave_kills: 10,
+            wave_spawn_factor: 0.9,
+        }
+    }
+}
+
+impl Default for CoopConfig {
+    fn default() -> Self {
+        CoopConfig {
+            players: 1,
+            scoring: ScoreMode::Shared,
+            revive_time: 3.0,
+            revive_radius: 60.0,
+            revive_health: 1,
+        }
+    }
+}
+
+impl Default for ProgressionConfig {
+    fn default() -> Self {
+        ProgressionConfig {
+            first_level_xp: 5,
+            level_growth: 1.4,
+            choices: 3,
+        }
+    }
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        PoolConfig {
+            bullets: 64,
+            enemies: 20,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, std::io::Error),
+    Parse(PathBuf, ron::Error),
+    /// one message per bad field
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, e) => write!(f, "could not read {}: {}", path.display(), e),
+            ConfigError::Parse(path, e) => write!(f, "could not parse {}: {}", path.display(), e),
+            ConfigError::Invalid(problems) => {
+                writeln!(f, "invalid game config:")?;
+                for problem in problems.iter() {
+                    writeln!(f, "  - {}", problem)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl GameConfig {
+    /// Reads and validates the config at `path`.
+    pub fn load(path: impl AsRef<Path>) -> Result<GameConfig, ConfigError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_owned(), e))?;
+        let config = GameConfig::parse(&text).map_err(|e| match e {
+            ConfigError::Parse(_, e) => ConfigError::Parse(path.to_owned(), e),
+            e => e,
+        })?;
+        Ok(config)
+    }
+
+    pub fn parse(text: &str) -> Result<GameConfig, ConfigError> {
+        let config: GameConfig = ron::from_str(text).map_err(|e| ConfigError::Parse(PathBuf::new(), e))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = vec![];
+        let mut positive = |name: &str, value: f32| {
+            if !(value > 0.0) {
+                problems.push(format!("{} must be greater than 0, got {}", name, value));
+            }
+        };
+        if self.player.max_health < 1 {
+            problems.push(format!("player.max_health must be at least 1, got {}", self.player.max_health));
+        }
+        positive("player.max_velocity", self.player.max_velocity);
+        positive("player.acceleration", self.player.acceleration);
+        positive("weapon.fire_interval", self.weapon.fire_interval);
+        positive("weapon.bullet_speed", self.weapon.bullet_speed);
+        positive("weapon.bullet_lifetime", self.weapon.bullet_lifetime);
+        positive("enemies.spawn_interval", self.enemies.spawn_interval);
+        positive("enemies.arena_half_size.0", self.enemies.arena_half_size.0);
+        positive("enemies.arena_half_size.1", self.enemies.arena_half_size.1);
+        positive("enemies.neighbour_radius", self.enemies.neighbour_radius);
+        positive("enemies.separation_radius", self.enemies.separation_radius);
+        positive("enemies.avoid_distance", self.enemies.avoid_distance);
+        positive("player.hurt_time", self.player.hurt_time);
+        positive("coop.revive_time", self.coop.revive_time);
+        positive("coop.revive_radius", self.coop.revive_radius);
+        positive("weapon.bomb.fuse", self.weapon.bomb.fuse);
+        positive("weapon.bomb.radius", self.weapon.bomb.radius);
+        positive("weapon.ammo.reload_time", self.weapon.ammo.reload_time);
+        positive("weapon.ammo.pickup_radius", self.weapon.ammo.pickup_radius);
+
+        if !(0.0..1.0).contains(&self.player.friction) {
+            problems.push(format!("player.friction must be in 0.0..1.0, got {}", self.player.friction));
+        }
+        if self.player.friction_step_positive < 0.0 || self.player.friction_step_negative < 0.0 {
+            problems.push("player.friction_step_positive/negative must not be negative".to_owned());
+        }
+        if self.weapon.muzzle_offset < 0.0 {
+            problems.push(format!("weapon.muzzle_offset must not be negative, got {}", self.weapon.muzzle_offset));
+        }
+        if let FireMode::Charged { seconds } = self.weapon.fire_mode {
+            if !(seconds > 0.0) {
+                problems.push(format!("weapon.fire_mode charge seconds must be greater than 0, got {}", seconds));
+            }
+        }
+        let charged = &self.weapon.charged;
+        if !(0.0..=1.0).contains(&charged.min_charge) {
+            problems.push(format!("weapon.charged.min_charge must be in 0.0..=1.0, got {}", charged.min_charge));
+        }
+        if !(charged.size >= 1.0) || charged.damage < 1 {
+            problems.push("weapon.charged size and damage must be at least those of a plain bullet, 1".to_owned());
+        }
+        if self.weapon.bomb.damage < 1 {
+            problems.push(format!("weapon.bomb.damage must be at least 1, got {}", self.weapon.bomb.damage));
+        }
+        let ammo = &self.weapon.ammo;
+        if ammo.magazine == Some(0) {
+            problems.push("weapon.ammo.magazine must be at least 1, or None for no magazine".to_owned());
+        }
+        if ammo.reserve.map_or(false, |reserve| reserve > ammo.max_reserve) {
+            problems.push(format!("weapon.ammo.reserve must not be more than max_reserve, {}", ammo.max_reserve));
+        }
+        if !(0.0..=1.0).contains(&ammo.drop_chance) {
+            problems.push(format!("weapon.ammo.drop_chance must be in 0.0..=1.0, got {}", ammo.drop_chance));
+        }
+        for (i, effect) in self.weapon.on_hit.iter().enumerate() {
+            if let Some(problem) = effect.problem() {
+                problems.push(format!("weapon.on_hit[{}]: {}", i, problem));
+            }
+        }
+        let projectile = &self.weapon.projectile;
+        if projectile.homing_turn_rate < 0.0 || projectile.homing_range < 0.0 || projectile.split_spread < 0.0 || projectile.explosion_radius < 0.0 {
+            problems.push("weapon.projectile turn rate, range, spread and radius must not be negative".to_owned());
+        }
+        if projectile.explosion_damage < 0 {
+            problems.push(format!("weapon.projectile.explosion_damage must not be negative, got {}", projectile.explosion_damage));
+        }
+        if self.enemies.max_enemies < 0 {
+            problems.push(format!("enemies.max_enemies must not be negative, got {}", self.enemies.max_enemies));
+        }
+        if self.enemies.spawn_buffer < 0.0 {
+            problems.push(format!("enemies.spawn_buffer must not be negative, got {}", self.enemies.spawn_buffer));
+        }
+        if self.enemies.wave_kills < 1 {
+            problems.push("enemies.wave_kills must be at least 1".to_owned());
+        }
+        if !(self.enemies.wave_spawn_factor > 0.0 && self.enemies.wave_spawn_factor <= 1.0) {
+            problems.push(format!("enemies.wave_spawn_factor must be greater than 0 and at most 1, got {}", self.enemies.wave_spawn_factor));
+        }
+
+        if !(1..=4).contains(&self.coop.players) {
+            problems.push(format!("coop.players must be between 1 and 4, got {}", self.coop.players));
+        }
+        if self.coop.revive_health < 1 || self.coop.revive_health > self.player.max_health {
+            problems.push(format!("coop.revive_health must be between 1 and player.max_health, got {}", self.coop.revive_health));
+        }
+
+        if self.progression.first_level_xp < 1 {
+            problems.push("progression.first_level_xp must be at least 1".to_owned());
+        }
+        if !(self.progression.level_growth >= 1.0) {
+            problems.push(format!("progression.level_growth must be at least 1.0, got {}", self.progression.level_growth));
+        }
+        if !(1..=3).contains(&self.progression.choices) {
+            problems.push(format!("progression.choices must be between 1 and 3, got {}", self.progression.choices));
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use bevy::reflect::TypeUuid;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use crate::{ConfigError, TimedEffect};
+
+
+/// relative to the asset folder, read with `asset_path` at startup and by the asset server for reloads
+pub const ENEMY_DEFINITIONS_ASSET: &str = "config/enemies.enemies.ron";
+
+/// One kind of enemy. Spawned enemies carry an `EnemyKind` with its name.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct EnemyDefinition {
+    pub name: String,
+    /// pixels per second
+    pub speed: f32,
+    #[serde(default = "default_health")]
+    pub health: i32,
+    /// relative chance of this kind being picked when an enemy spawns
+    #[serde(default = "default_spawn_weight")]
+    pub spawn_weight: f32,
+    #[serde(default)]
+    pub steering: SteeringWeights,
+    /// status effects this kind puts on a player it touches
+    #[serde(default)]
+    pub on_hit: Vec<TimedEffect>,
+    /// experience the orb dropped by a killed enemy of this kind is worth
+    #[serde(default = "default_xp")]
+    pub xp: u32,
+}
+
+/// How strongly each steering behaviour pulls an enemy of one kind, see `EnemyConfig` for the
+/// distances they work over.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct SteeringWeights {
+    /// towards the nearest player
+    pub seek: f32,
+    /// away from neighbours that are too close
+    pub separation: f32,
+    /// along with the neighbours' heading
+    pub alignment: f32,
+    /// towards the middle of the neighbours
+    pub cohesion: f32,
+    /// away from `Obstacle`s in the way
+    pub avoidance: f32,
+}
+
+impl Default for SteeringWeights {
+    fn default() -> Self {
+        SteeringWeights {
+            seek: 1.0,
+            separation: 1.5,
+            alignment: 0.3,
+            cohesion: 0.2,
+            avoidance: 2.0,
+        }
+    }
+}
+
+fn default_health() -> i32 {
+    1
+}
+
+fn default_xp() -> u32 {
+    1
+}
+
+fn default_spawn_weight() -> f32 {
+    1.0
+}
+
+/// Every enemy kind the spawner can pick from, inserted as a resource.
+#[derive(Debug, Clone, PartialEq, Deserialize, TypeUuid)]
+#[uuid = "0b8e7f52-3d2a-4c61-8f0e-5a9d6c4b2e71"]
+pub struct EnemyDefinitions {
+    pub kinds: Vec<EnemyDefinition>,
+}
+
+/// name of the `EnemyDefinition` an enemy was spawned from
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnemyKind(pub String);
+
+impl Default for EnemyDefinitions {
+    fn default() -> Self {
+        EnemyDefinitions {
+            kinds: vec![EnemyDefinition {
+                name: "grunt".to_owned(),
+                speed: 100.0,
+                health: 1,
+                spawn_weight: 1.0,
+                steering: SteeringWeights::default(),
+                on_hit: vec![],
+                xp: 1,
+            }],
+        }
+    }
+}
+
+impl EnemyDefinitions {
+    pub fn load(path: impl AsRef<Path>) -> Result<EnemyDefinitions, ConfigError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_owned(), e))?;
+        let definitions = EnemyDefinitions::parse(&text).map_err(|e| match e {
+            ConfigError::Parse(_, e) => ConfigError::Parse(path.to_owned(), e),
+            e => e,
+        })?;
+        Ok(definitions)
+    }
+
+    pub fn parse(text: &str) -> Result<EnemyDefinitions, ConfigError> {
+        let definitions: EnemyDefinitions = ron::from_str(text).map_err(|e| ConfigError::Parse(PathBuf::new(), e))?;
+        definitions.validate()?;
+        Ok(definitions)
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = vec![];
+        if self.kinds.is_empty() {
+            problems.push("kinds must contain at least one enemy".to_owned());
+        }
+        for (i, kind) in self.kinds.iter().enumerate() {
+            if kind.name.is_empty() {
+                problems.push(format!("kinds[{}].name must not be empty", i));
+            }
+            if self.kinds[..i].iter().any(|other| other.name == kind.name) {
+                problems.push(format!("kinds[{}].name {:?} is defined twice", i, kind.name));
+            }
+            if !(kind.speed > 0.0) {
+                problems.push(format!("kinds[{}].speed must be greater than 0, got {}", i, kind.speed));
+            }
+            if kind.health < 1 {
+                problems.push(format!("kinds[{}].health must be at least 1, got {}", i, kind.health));
+            }
+            if !(kind.spawn_weight >= 0.0) {
+                problems.push(format!("kinds[{}].spawn_weight must not be negative, got {}", i, kind.spawn_weight));
+            }
+            let weights = &kind.steering;
+            if [weights.seek, weights.separation, weights.alignment, weights.cohesion, weights.avoidance].iter().any(|weight| !(*weight >= 0.0)) {
+                problems.push(format!("kinds[{}].steering weights must not be negative", i));
+            }
+            for (j, effect) in kind.on_hit.iter().enumerate() {
+                if let Some(problem) = effect.problem() {
+                    problems.push(format!("kinds[{}].on_hit[{}]: {}", i, j, problem));
+                }
+            }
+        }
+        if !self.kinds.is_empty() && self.kinds.iter().all(|kind| kind.spawn_weight == 0.0) {
+            problems.push("at least one kind needs a spawn_weight above 0".to_owned());
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&EnemyDefinition> {
+        self.kinds.iter().find(|kind| kind.name == name)
+    }
+
+    /// Picks a kind by spawn weight. `roll` is uniform in 0.0..1.0.
+    pub fn pick(&self, roll: f32) -> &EnemyDefinition {
+        let total: f32 = self.kinds.iter().map(|kind| kind.spawn_weight).sum();
+        let mut remaining = roll * total;
+        for kind in self.kinds.iter() {
+            if remaining < kind.spawn_weight {
+                return kind;
+            }
+            remaining -= kind.spawn_weight;
+        }
+        self.kinds.last().expect("validated definitions are never empty")
+    }
+}
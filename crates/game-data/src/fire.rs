@@ -0,0 +1,145 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+
+/// how holding the fire action turns into shots
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum FireMode {
+    /// fires every `fire_interval` while held
+    Hold,
+    /// fires once per press, at most every `fire_interval`
+    Tap,
+    /// charges for up to `seconds` while held and fires on release
+    Charged { seconds: f32 },
+}
+
+impl Default for FireMode {
+    fn default() -> Self {
+        FireMode::Hold
+    }
+}
+
+/// A player's weapon cooldown. It recovers whether or not fire is held, so a player who
+/// stopped shooting fires straight away on the next press.
+#[derive(Debug, Clone)]
+pub struct FireCooldown {
+    pub mode: FireMode,
+    timer: Timer,
+    held: bool,
+    /// seconds charged so far
+    charge: f32,
+}
+
+impl FireCooldown {
+    /// starts ready to fire
+    pub fn new(mode: FireMode, interval: f32) -> Self {
+        let mut timer = Timer::from_seconds(interval, false);
+        timer.tick(timer.duration());
+        FireCooldown { mode, timer, held: false, charge: 0.0 }
+    }
+
+    /// Advances the cooldown by `delta` with the fire action `fire` held or not. Returns the
+    /// charge of the shot to fire this frame, 0.0 for uncharged shots up to 1.0.
+    pub fn update(&mut self, delta: Duration, fire: bool) -> Option<f32> {
+        // how far into the frame the cooldown ran out, a shot fired now is that late
+        let overshoot = match self.timer.finished() {
+            true => Duration::default(),
+            false => (self.timer.elapsed() + delta).checked_sub(self.timer.duration()).unwrap_or_default(),
+        };
+        self.timer.tick(delta);
+        let pressed = fire && !self.held;
+        let released = !fire && self.held;
+        self.held = fire;
+        let ready = self.timer.finished();
+        let shot = match self.mode {
+            FireMode::Hold if fire && ready => Some(0.0),
+            FireMode::Tap if pressed && ready => Some(0.0),
+            FireMode::Charged { seconds } => {
+                if fire && ready {
+                    self.charge = (self.charge + delta.as_secs_f32()).min(seconds);
+                    None
+                } else if released && self.charge > 0.0 {
+                    Some(std::mem::take(&mut self.charge) / seconds)
+                } else {
+                    None
+                }
+            },
+            _ => None,
+        };
+        if shot.is_some() {
+            // carried into the next cooldown, so the fire rate doesn't drop at frame rates that
+            // don't divide the interval
+            self.set_elapsed(overshoot.min(self.timer.duration()));
+        }
+        shot
+    }
+
+    /// Drops a charge in progress and forgets the action was held, e.g. while stunned.
+    pub fn cancel(&mut self) {
+        self.held = false;
+        self.charge = 0.0;
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.timer.finished()
+    }
+
+    /// 0.0 to 1.0, always 0.0 outside `FireMode::Charged`
+    pub fn charge(&self) -> f32 {
+        match self.mode {
+            FireMode::Charged { seconds } => self.charge / seconds,
+            _ => 0.0,
+        }
+    }
+
+    /// time since the last shot, up to the interval
+    pub fn elapsed(&self) -> Duration {
+        self.timer.elapsed()
+    }
+
+    pub fn set_elapsed(&mut self, elapsed: Duration) {
+        self.timer.reset();
+        self.timer.set_elapsed(elapsed);
+        self.timer.tick(Duration::from_secs(0));
+    }
+
+    /// seconds between shots
+    pub fn interval(&self) -> f32 {
+        self.timer.duration().as_secs_f32()
+    }
+
+    pub fn set_interval(&mut self, interval: f32) {
+        self.timer.set_duration(Duration::from_secs_f32(interval));
+    }
+}
+
+/// Alternate fire ammo. One bomb is dropped per press of the alt fire action.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bombs {
+    pub left: u32,
+    held: bool,
+}
+
+impl Bombs {
+    pub fn new(left: u32) -> Self {
+        Bombs { left, held: false }
+    }
+
+    /// whether a bomb should be dropped this frame with alt fire `pressed` or not; uses it up
+    pub fn trigger(&mut self, pressed: bool) -> bool {
+        let drop = pressed && !self.held && self.left > 0;
+        self.held = pressed;
+        if drop {
+            self.left -= 1;
+        }
+        drop
+    }
+}
+
+/// a dropped bomb, dealing `damage` to every enemy within `radius` pixels when `fuse` runs out
+pub struct Bomb {
+    pub fuse: Timer,
+    pub radius: f32,
+    pub damage: i32,
+}
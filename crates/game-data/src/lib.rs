@@ -1,9 +1,36 @@
 use bevy::prelude::*;
-use bevy_rapier2d::rapier::na::Vector2;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::path::{Path, PathBuf};
+pub use ammo::*;
+pub use bundles::*;
+pub use characters::*;
+pub use config::*;
+pub use definitions::*;
+pub use fire::*;
+pub use movement::*;
+pub use progression::*;
+pub use projectile::*;
+pub use shop::*;
+pub use status::*;
+pub use units::*;
+pub use weapons::*;
+mod ammo;
+mod bundles;
+mod characters;
+mod config;
+mod definitions;
+mod fire;
+mod movement;
+mod progression;
+mod projectile;
+mod shop;
+mod status;
+mod units;
+mod weapons;
 
 
-
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
 pub enum Direction{
     North,
     NorthEast,
@@ -14,16 +41,74 @@ pub enum Direction{
     SouthEast,
     Southwest,
 }
+
+impl Direction {
+    /// -1, 0 or 1 on each axis
+    pub fn axes(&self) -> Vec2 {
+        match self {
+            Direction::North => Vec2::new(0.0, 1.0),
+            Direction::NorthEast => Vec2::new(1.0, 1.0),
+            Direction::NorthWest => Vec2::new(-1.0, 1.0),
+            Direction::East => Vec2::new(1.0, 0.0),
+            Direction::West => Vec2::new(-1.0, 0.0),
+            Direction::South => Vec2::new(0.0, -1.0),
+            Direction::SouthEast => Vec2::new(1.0, -1.0),
+            Direction::Southwest => Vec2::new(-1.0, -1.0),
+        }
+    }
+
+    pub fn unit(&self) -> Vec2 {
+        self.axes().normalize()
+    }
+
+    /// Snaps a stick or key vector to the nearest of the eight directions, `None` near the centre.
+    pub fn from_axes(axes: Vec2) -> Option<Direction> {
+        if axes.length() < 0.5 {
+            return None;
+        }
+        let octant = (axes.y.atan2(axes.x) / std::f32::consts::FRAC_PI_4).round() as i32;
+        Some(match octant.rem_euclid(8) {
+            0 => Direction::East,
+            1 => Direction::NorthEast,
+            2 => Direction::North,
+            3 => Direction::NorthWest,
+            4 => Direction::West,
+            5 => Direction::Southwest,
+            6 => Direction::South,
+            _ => Direction::SouthEast,
+        })
+    }
+}
 ///e1: Bullet, e2: Enemy
 pub enum Contacts {
     BulletEnemy(Entity, Entity),
-    //TODO: EnemyPlayer(Entity, Entity),
+    ///e1: Enemy, e2: Player
+    EnemyPlayer(Entity, Entity),
+    ///e: Bullet, normal: unit vector out of the face of the static collider it hit
+    BulletWall(Entity, Vec2),
 }
-pub struct ShootEvent(pub Entity);
-
-pub struct BulletTimer(pub Timer);
-
-pub struct BulletSpeedTimer(pub Timer);
+///shooter: player that fired, charge: 0.0 for an uncharged shot up to 1.0 for a fully charged one
+pub struct ShotFired {
+    pub shooter: Entity,
+    pub charge: f32,
+}
+///position: where a bomb went off, radius: how far it reached
+pub struct BombExploded {
+    pub position: Vec2,
+    pub radius: f32,
+}
+///e: enemy that was struck
+pub struct EnemyHitEvent(pub Entity);
+///e1: enemy that died and is playing its death animation, e2: player whose bullet killed it
+pub struct EnemyKilledEvent(pub Entity, pub Option<Entity>);
+///e: player that took damage
+pub struct PlayerHurtEvent(pub Entity);
+///wave number that just started
+pub struct WaveStartEvent(pub u32);
+///e: player that collected the pickup
+pub struct PickupEvent(pub Entity);
+///clears the arena and starts over with fresh players equipped from the `Loadout`
+pub struct NewRunEvent;
 
 pub struct BulletLifetime(pub Timer);
 
@@ -33,29 +118,499 @@ pub struct EnemyCount(pub i32);
 
 pub struct Enemy;
 
+/// speeds in pixels per second, acceleration in pixels per second squared
 pub struct Player{
     pub max_velocity: f32,
     pub acceleration: f32,
-    pub velocity: Vector2<f32>,
+    pub velocity: Vec2,
 }
+///speed in pixels per second
 pub struct Bullet(pub f32);
+///e: player that fired the bullet
+pub struct Owner(pub Entity);
 
-impl Default for Player {
+/// which local player an entity is, starting at 0
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PlayerId(pub u8);
+
+/// What a player wants to do this frame. Filled from the player's bindings and read by
+/// movement and firing instead of the keyboard.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PlayerInput {
+    /// -1..1 on each axis
+    pub movement: Vec2,
+    pub aim: Option<Direction>,
+    pub fire: bool,
+    /// drops a bomb, once per press
+    pub alt_fire: bool,
+    pub reload: bool,
+}
+
+/// Movement a network player's commands asked for since the last frame, one step per command
+/// with the frame time the client ran it with. The server moves remote players by these instead
+/// of its own frame time, so they end up where the client predicted.
+#[derive(Debug, Default)]
+pub struct RemoteSteps(pub Vec<(Vec2, f32)>);
+
+/// player ran out of health and can't move or fire until a teammate stands next to them
+/// long enough for `revive` to finish
+pub struct Downed {
+    pub revive: Timer,
+}
+
+/// player is driven by the bot AI instead of its input bindings
+pub struct Bot;
+
+/// something players collect by walking over it; bots go for these when no enemy is close
+pub struct Pickup;
+
+/// static box fencing the arena in, see `WallBundle`
+pub struct Wall;
+
+/// something enemies steer around, `radius` in pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Obstacle {
+    pub radius: f32,
+}
+
+/// kills credited to one player, only counted when scoring is split
+#[derive(Debug, Default)]
+pub struct PlayerScore(pub u32);
+
+/// which animation an entity should be showing, picked each frame from its state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Deserialize)]
+pub enum AnimationState {
+    Idle,
+    Walk,
+    Shoot,
+    Hurt,
+    Die,
+}
+/// entity belongs to an object pool and is recycled instead of despawned
+pub struct Pooled;
+/// hands a pooled entity back to its pool, which parks it until it is reused
+pub struct Recycle;
+/// pooled entity is parked: hidden, not simulated and not colliding
+pub struct Inactive;
+/// entity has been killed and is despawned once the timer runs out
+pub struct Dying(pub Timer);
+/// entity recently took damage
+pub struct Hurt(pub Timer);
+/// hit points left, the entity dies at 0
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Health(pub i32);
+/// enemies killed this run
+#[derive(Debug, Default)]
+pub struct Score(pub u32);
+/// current wave, starting at 1
+#[derive(Debug)]
+pub struct Wave(pub u32);
+/// Gameplay randomness (spawn positions, enemy kinds). Seedable and saved with the session
+/// so a restored run continues the same way; cosmetic randomness keeps using `thread_rng`.
+pub struct GameRng(pub ChaCha8Rng);
+
+impl Default for Wave {
     fn default() -> Self {
+        Wave(1)
+    }
+}
+
+impl GameRng {
+    pub fn from_seed(seed: u64) -> Self {
+        GameRng(ChaCha8Rng::seed_from_u64(seed))
+    }
+}
+
+impl Default for GameRng {
+    fn default() -> Self {
+        GameRng(ChaCha8Rng::from_entropy())
+    }
+}
+
+impl Player {
+    pub fn from_config(config: &PlayerConfig) -> Self {
         Player {
-            max_velocity: 20.0,
-            acceleration: 50.0,
-            velocity: Vector2::new(0.0, 0.0),
+            max_velocity: config.max_velocity,
+            acceleration: config.acceleration,
+            velocity: Vec2::ZERO,
         }
     }
 }
 
+impl Default for Player {
+    fn default() -> Self {
+        Player::from_config(&PlayerConfig::default())
+    }
+}
+
+/// Where the asset server finds `path`, which is relative to the asset folder: next to the game's
+/// manifest under `cargo run`, next to the executable otherwise. For files read without it.
+pub fn asset_path(path: impl AsRef<Path>) -> PathBuf {
+    bevy::asset::FileAssetIo::get_root_path().join("assets").join(path)
+}
+
 
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use bevy_rapier2d::physics::RapierConfiguration;
+    use std::time::Duration;
+
     #[test]
     fn it_works() {
         assert_eq!(2 + 2, 4);
     }
+
+    #[test]
+    fn projectile_speed_is_converted_to_physics_units() {
+        let rapier_config = RapierConfiguration { scale: 20.0, ..Default::default() };
+        let bundle = ProjectileBundle::new(&rapier_config, WorldPos(Vec2::new(40.0, 0.0)), PixelsPerSecond(Vec2::new(600.0, 0.0)), Direction::East, 1.5);
+        assert_eq!(bundle.bullet.0, 600.0);
+        let units = Units::new(&rapier_config);
+        assert_eq!(units.to_physics(WorldPos(Vec2::new(40.0, -20.0))), PhysicsPos(Vec2::new(2.0, -1.0)));
+    }
+
+    #[test]
+    fn world_speed_does_not_depend_on_scale() {
+        let speed = PixelsPerSecond(Vec2::new(300.0, -120.0));
+        for scale in [1.0, 20.0, 64.0].iter() {
+            let units = Units { scale: *scale };
+            let physics = units.velocity_to_physics(speed);
+            assert_eq!(physics.0, speed.0 / *scale);
+            assert!((units.velocity_to_world(physics).0 - speed.0).length() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn axes_snap_to_the_nearest_direction() {
+        assert_eq!(Direction::from_axes(Vec2::new(0.0, 1.0)), Some(Direction::North));
+        assert_eq!(Direction::from_axes(Vec2::new(-1.0, 1.0)), Some(Direction::NorthWest));
+        assert_eq!(Direction::from_axes(Vec2::new(0.9, -0.2)), Some(Direction::East));
+        assert_eq!(Direction::from_axes(Vec2::new(-0.1, -0.8)), Some(Direction::South));
+        assert_eq!(Direction::from_axes(Vec2::new(0.2, 0.1)), None);
+    }
+
+    #[test]
+    fn status_effects_stack_and_run_out() {
+        let slow = TimedEffect { effect: StatusEffect::Slow { fraction: 0.5 }, seconds: 2.0 };
+        let mut effects = StatusEffects::default();
+        for _ in 0..5 {
+            effects.apply(&slow, Vec2::ZERO);
+        }
+        assert_eq!(effects.iter().count(), 1);
+        assert!((effects.speed_multiplier() - 0.125).abs() < 1e-6);
+        assert!(effects.can_act());
+
+        effects.apply(&TimedEffect { effect: StatusEffect::Stun, seconds: 0.5 }, Vec2::ZERO);
+        effects.apply(&TimedEffect { effect: StatusEffect::Stun, seconds: 0.2 }, Vec2::ZERO);
+        assert_eq!(effects.speed_multiplier(), 0.0);
+        assert!(!effects.can_act());
+        effects.tick(Duration::from_secs_f32(0.3));
+        assert!(!effects.can_act());
+        effects.tick(Duration::from_secs_f32(0.3));
+        assert!(effects.can_act());
+
+        effects.apply(&TimedEffect { effect: StatusEffect::Knockback { speed: 100.0 }, seconds: 1.0 }, Vec2::new(0.0, 3.0));
+        effects.apply(&TimedEffect { effect: StatusEffect::Knockback { speed: 100.0 }, seconds: 1.0 }, Vec2::new(-1.0, 0.0));
+        assert_eq!(effects.knockback(), Vec2::new(-100.0, 100.0));
+        effects.tick(Duration::from_secs_f32(2.0));
+        assert!(effects.is_empty());
+    }
+
+    #[test]
+    fn burns_deal_whole_hit_points_per_stack() {
+        let burn = TimedEffect { effect: StatusEffect::Burn { damage_per_second: 1.0 }, seconds: 1.5 };
+        let mut effects = StatusEffects::default();
+        effects.apply(&burn, Vec2::ZERO);
+        effects.apply(&burn, Vec2::ZERO);
+        effects.tick(Duration::from_secs_f32(0.25));
+        assert_eq!(effects.take_damage(), 0);
+        effects.tick(Duration::from_secs_f32(0.25));
+        assert_eq!(effects.take_damage(), 1);
+        assert_eq!(effects.take_damage(), 0);
+        // only the 1 second the burn has left counts
+        effects.tick(Duration::from_secs_f32(5.0));
+        assert_eq!(effects.take_damage(), 2);
+        assert!(effects.is_empty());
+
+        assert!(TimedEffect { effect: StatusEffect::Slow { fraction: 1.0 }, seconds: 1.0 }.problem().is_some());
+        assert!(TimedEffect { effect: StatusEffect::Freeze, seconds: 0.0 }.problem().is_some());
+    }
+
+    #[test]
+    fn projectiles_turn_bounce_and_fan_out() {
+        let close = |a: Vec2, b: Vec2| (a - b).length() < 1e-4;
+        let quarter = std::f32::consts::FRAC_PI_2;
+        // a homing bullet can't turn further than its rate allows, but snaps on when it's close enough
+        let turned = turn_towards(Vec2::X, Vec2::new(0.0, -5.0), quarter / 2.0);
+        assert!(close(turned, Vec2::new(1.0, -1.0).normalize()));
+        assert!(close(turn_towards(Vec2::X, Vec2::new(1.0, 0.1), quarter), Vec2::new(1.0, 0.1).normalize()));
+        assert_eq!(turn_towards(Vec2::Y, Vec2::ZERO, quarter), Vec2::Y);
+
+        let normal = bounce_normal(Vec2::new(-12.0, 1.0), Vec2::new(-10.0, -5.0), Vec2::new(10.0, 5.0));
+        assert_eq!(normal, Vec2::new(-1.0, 0.0));
+        assert_eq!(bounce_normal(Vec2::new(3.0, 4.5), Vec2::new(-10.0, -5.0), Vec2::new(10.0, 5.0)), Vec2::new(0.0, 1.0));
+        assert!(close(reflect(Vec2::new(1.0, -1.0).normalize(), normal), Vec2::new(-1.0, -1.0).normalize()));
+
+        let fragments = fan(Vec2::X, 3, quarter);
+        assert_eq!(fragments.len(), 3);
+        assert!(close(fragments[0], Vec2::new(1.0, -1.0).normalize()));
+        assert!(close(fragments[1], Vec2::X));
+        assert!(close(fragments[2], Vec2::new(1.0, 1.0).normalize()));
+        assert_eq!(fan(Vec2::Y, 1, quarter), vec![Vec2::Y]);
+    }
+
+    #[test]
+    fn fire_cooldown_recovers_between_presses() {
+        let frame = Duration::from_millis(100);
+        let shots = |cooldown: &mut FireCooldown, input: &[bool]| {
+            input.iter().map(|&fire| cooldown.update(frame, fire)).collect::<Vec<_>>()
+        };
+
+        let mut hold = FireCooldown::new(FireMode::Hold, 0.15);
+        assert_eq!(shots(&mut hold, &[true, true, true, true]), vec![Some(0.0), None, Some(0.0), None]);
+        // not firing still lets the weapon cool down
+        shots(&mut hold, &[false, false, false]);
+        assert!(hold.is_ready());
+
+        // 100ms frames don't divide a 250ms interval, the time past it counts towards the next shot
+        let mut steady = FireCooldown::new(FireMode::Hold, 0.25);
+        let fired = shots(&mut steady, &[true; 60]).into_iter().flatten().count();
+        assert_eq!(fired, 24);
+
+        let mut tap = FireCooldown::new(FireMode::Tap, 0.15);
+        assert_eq!(shots(&mut tap, &[true, true, true, false, true]), vec![Some(0.0), None, None, None, Some(0.0)]);
+
+        let mut charged = FireCooldown::new(FireMode::Charged { seconds: 0.4 }, 0.15);
+        assert_eq!(shots(&mut charged, &[true, true]), vec![None, None]);
+        assert!((charged.charge() - 0.5).abs() < 1e-4);
+        assert_eq!(shots(&mut charged, &[true, true, true, false]), vec![None, None, None, Some(1.0)]);
+        assert_eq!(charged.charge(), 0.0);
+        charged.update(frame, true);
+        charged.cancel();
+        assert_eq!(charged.update(frame, false), None);
+    }
+
+    #[test]
+    fn bombs_drop_once_per_press_until_they_run_out() {
+        let mut bombs = Bombs::new(2);
+        let drops = [true, true, false, true, false, true]
+            .iter()
+            .map(|&pressed| bombs.trigger(pressed))
+            .collect::<Vec<_>>();
+        assert_eq!(drops, vec![true, false, false, true, false, false]);
+        assert_eq!(bombs.left, 0);
+    }
+
+    #[test]
+    fn ammo_reloads_from_a_limited_reserve() {
+        let config = AmmoConfig { magazine: Some(3), reserve: Some(4), max_reserve: 6, reload_time: 1.0, ..AmmoConfig::default() };
+        let mut ammo = Ammo::new(&config);
+        for _ in 0..3 {
+            assert!(ammo.can_fire(&config));
+            ammo.use_round(&config);
+        }
+        assert!(!ammo.can_fire(&config));
+        assert!(ammo.needs_reload(&config));
+        assert!(ammo.start_reload(&config));
+        assert!(!ammo.start_reload(&config));
+        assert!(!ammo.tick(Duration::from_millis(600), &config));
+        assert!(ammo.tick(Duration::from_millis(600), &config));
+        assert_eq!((ammo.loaded, ammo.reserve), (3, Some(1)));
+
+        // a full magazine doesn't reload, the last round in the reserve only half fills one
+        assert!(!ammo.start_reload(&config));
+        ammo.use_round(&config);
+        ammo.use_round(&config);
+        ammo.start_reload(&config);
+        ammo.tick(Duration::from_secs(1), &config);
+        assert_eq!((ammo.loaded, ammo.reserve), (2, Some(0)));
+        ammo.use_round(&config);
+        ammo.use_round(&config);
+        assert!(!ammo.needs_reload(&config));
+
+        assert!(ammo.refill(10, config.max_reserve));
+        assert_eq!(ammo.reserve, Some(6));
+        assert!(!ammo.refill(10, config.max_reserve));
+
+        let endless = AmmoConfig { magazine: None, reserve: None, ..config };
+        let mut ammo = Ammo::new(&endless);
+        ammo.use_round(&endless);
+        assert!(ammo.can_fire(&endless));
+        assert!(!ammo.start_reload(&endless));
+    }
+
+    #[test]
+    fn upgrades_change_player_and_weapon_stats() {
+        let config = GameConfig::default();
+        let upgrades = UpgradeDefinitions::default();
+        let mut stats = PlayerStats::new(&config);
+        let mut player = Player::from_config(&config.player);
+        let mut cooldown = FireCooldown::new(config.weapon.fire_mode, config.weapon.fire_interval);
+        let mut health = Health(2);
+        for name in ["rapid fire", "rapid fire", "quick feet", "heavy rounds", "multishot", "tough skin", "magnet"].iter() {
+            stats.upgrade(upgrades.get(name).unwrap(), &config, &mut player, &mut cooldown, &mut health);
+        }
+        assert!((cooldown.interval() - config.weapon.fire_interval / 1.44).abs() < 1e-5);
+        assert!((player.max_velocity - config.player.max_velocity * 1.1).abs() < 1e-3);
+        assert!((player.acceleration - config.player.acceleration * 1.1).abs() < 1e-3);
+        assert_eq!((stats.damage, stats.extra_projectiles), (1, 1));
+        assert_eq!((stats.max_health, health.0), (config.player.max_health + 1, 3));
+        assert_eq!(stats.pickup_radius, config.weapon.ammo.pickup_radius + 25.0);
+        assert_eq!(stats.stacks("rapid fire"), 2);
+
+        // healing never goes past the new max
+        let mut full = Health(stats.max_health);
+        stats.upgrade(upgrades.get("tough skin").unwrap(), &config, &mut player, &mut cooldown, &mut full);
+        assert_eq!(full.0, stats.max_health);
+    }
+
+    #[test]
+    fn levels_need_more_experience_each_time() {
+        let config = ProgressionConfig::default();
+        assert_eq!((1..=4).map(|level| config.xp_to_next(level)).collect::<Vec<_>>(), vec![5, 7, 10, 14]);
+        let mut experience = Experience::default();
+        assert_eq!(experience.gain(4, &config), 0);
+        assert_eq!(experience.gain(9, &config), 2);
+        assert_eq!(experience, Experience { level: 3, xp: 1 });
+    }
+
+    #[test]
+    fn offers_are_different_upgrades_that_are_not_maxed_out() {
+        let config = GameConfig::default();
+        let upgrades = UpgradeDefinitions::default();
+        let mut rng = GameRng::from_seed(3);
+        let mut stats = PlayerStats::new(&config);
+        for _ in 0..4 {
+            stats.apply(upgrades.get("multishot").unwrap());
+        }
+        let multishot = upgrades.upgrades.iter().position(|upgrade| upgrade.name == "multishot").unwrap();
+        for _ in 0..50 {
+            let mut offer = upgrades.offer(&stats, 3, &mut rng.0);
+            assert!(!offer.contains(&multishot));
+            offer.sort();
+            offer.dedup();
+            assert_eq!(offer.len(), 3);
+        }
+        assert_eq!(upgrades.offer(&stats, 10, &mut rng.0).len(), upgrades.upgrades.len() - 1);
+    }
+
+    #[test]
+    fn shipped_upgrade_definitions_are_valid() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../../assets/config/upgrades.upgrades.ron");
+        let definitions = UpgradeDefinitions::load(path).unwrap();
+        assert_eq!(definitions, UpgradeDefinitions::default());
+
+        let useless = r#"(upgrades: [(name: "nothing", description: "", effect: Damage(amount: 0), max_stacks: Some(0))])"#;
+        match UpgradeDefinitions::parse(useless) {
+            Err(ConfigError::Invalid(problems)) => assert_eq!(problems.len(), 2),
+            other => panic!("expected validation errors, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn shipped_weapons_characters_and_shop_are_valid() {
+        let weapons = WeaponDefinitions::load(concat!(env!("CARGO_MANIFEST_DIR"), "/../../assets/config/weapons.weapons.ron")).unwrap();
+        assert_eq!(weapons, WeaponDefinitions::default());
+        let characters = CharacterDefinitions::load(concat!(env!("CARGO_MANIFEST_DIR"), "/../../assets/config/characters.characters.ron")).unwrap();
+        assert_eq!(characters, CharacterDefinitions::default());
+        let shop = ShopDefinitions::load(concat!(env!("CARGO_MANIFEST_DIR"), "/../../assets/config/shop.shop.ron")).unwrap();
+        assert_eq!(shop, ShopDefinitions::default());
+        for item in shop.items.iter() {
+            match &item.effect {
+                ShopEffect::UnlockWeapon(name) => assert!(weapons.get(name).is_some(), "{} unlocks a weapon that doesn't exist", item.name),
+                ShopEffect::UnlockCharacter(name) => assert!(characters.get(name).is_some(), "{} unlocks a character that doesn't exist", item.name),
+                ShopEffect::Stat(_) => (),
+            }
+        }
+        for character in characters.characters.iter() {
+            assert!(weapons.get(&character.weapon).is_some(), "{} starts with a weapon that doesn't exist", character.name);
+        }
+
+        let vitality = &shop.items[0];
+        assert_eq!((0..3).map(|owned| vitality.cost_at(owned)).collect::<Vec<_>>(), vec![30, 45, 68]);
+        assert_eq!(shop.earnings(25, 3), 45);
+        assert_eq!(shop.earnings(0, 1), 0);
+    }
+
+    #[test]
+    fn loadouts_equip_the_character_weapon_and_shop_bonuses() {
+        let config = GameConfig::default();
+        let weapons = WeaponDefinitions::default();
+        let characters = CharacterDefinitions::default();
+        let loadout = Loadout {
+            character: characters.get("tank").cloned(),
+            weapon: weapons.get("scattergun").cloned(),
+            bonuses: vec![UpgradeEffect::MaxHealth { amount: 1 }, UpgradeEffect::MoveSpeed { fraction: 0.05 }],
+        };
+        let mut stats = PlayerStats::new(&config);
+        let mut player = Player::from_config(&config.player);
+        let mut cooldown = FireCooldown::new(config.weapon.fire_mode, config.weapon.fire_interval);
+        let mut health = Health(1);
+        loadout.equip(&config, &mut stats, &mut player, &mut cooldown, &mut health);
+        assert_eq!(cooldown.mode, FireMode::Tap);
+        assert_eq!(stats.extra_projectiles, 2);
+        assert_eq!((stats.max_health, health.0), (config.player.max_health + 3, config.player.max_health + 3));
+        assert!((player.max_velocity - config.player.max_velocity * 0.8 * 1.05).abs() < 1e-3);
+        assert!((player.acceleration - config.player.acceleration * 0.8 * 1.05 * 0.85).abs() < 1e-3);
+        assert!(characters.get("tank").unwrap().regeneration().is_some());
+        // shop bonuses aren't upgrades taken during the run
+        assert!(stats.taken.is_empty());
+    }
+
+    #[test]
+    fn shipped_config_is_valid() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../../assets/config/game.config.ron");
+        let config = GameConfig::load(path).unwrap();
+        assert_eq!(config, GameConfig::default());
+    }
+
+    #[test]
+    fn waves_end_every_few_kills_and_spawn_faster() {
+        let config = EnemyConfig { spawn_interval: 2.0, wave_kills: 10, wave_spawn_factor: 0.5, ..EnemyConfig::default() };
+        assert_eq!(config.wave_at(0), 1);
+        assert_eq!(config.wave_at(9), 1);
+        assert_eq!(config.wave_at(25), 3);
+        assert_eq!(config.spawn_interval_at(1), 2.0);
+        assert_eq!(config.spawn_interval_at(3), 0.5);
+    }
+
+    #[test]
+    fn invalid_config_names_every_bad_field() {
+        let text = "(weapon: (fire_interval: 0.0), enemies: (max_enemies: -1))";
+        match GameConfig::parse(text) {
+            Err(ConfigError::Invalid(problems)) => {
+                assert_eq!(problems.len(), 2);
+                assert!(problems[0].starts_with("weapon.fire_interval"));
+                assert!(problems[1].starts_with("enemies.max_enemies"));
+            },
+            other => panic!("expected validation errors, got {:?}", other),
+        }
+        assert!(matches!(GameConfig::parse("(player: 3)"), Err(ConfigError::Parse(..))));
+    }
+
+    #[test]
+    fn shipped_enemy_definitions_are_valid() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../../assets/config/enemies.enemies.ron");
+        let definitions = EnemyDefinitions::load(path).unwrap();
+        assert_eq!(definitions, EnemyDefinitions::default());
+    }
+
+    #[test]
+    fn enemy_kinds_are_picked_by_weight() {
+        let text = r#"(kinds: [(name: "grunt", speed: 100.0, spawn_weight: 3.0), (name: "runner", speed: 180.0)])"#;
+        let definitions = EnemyDefinitions::parse(text).unwrap();
+        assert_eq!(definitions.pick(0.0).name, "grunt");
+        assert_eq!(definitions.pick(0.74).name, "grunt");
+        assert_eq!(definitions.pick(0.76).name, "runner");
+        assert_eq!(definitions.pick(0.999).name, "runner");
+        assert_eq!(definitions.get("runner").unwrap().speed, 180.0);
+
+        let duplicate = r#"(kinds: [(name: "grunt", speed: 100.0), (name: "grunt", speed: 0.0)])"#;
+        match EnemyDefinitions::parse(duplicate) {
+            Err(ConfigError::Invalid(problems)) => assert_eq!(problems.len(), 2),
+            other => panic!("expected validation errors, got {:?}", other),
+        }
+    }
 }
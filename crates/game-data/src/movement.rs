@@ -0,0 +1,48 @@
+use bevy::prelude::*;
+use crate::*;
+
+
+impl Player {
+    /// Advances `velocity` by one frame of `movement` input (-1..1 per axis) lasting `dt` seconds.
+    /// The rigid body follows the result; network prediction replays it to stay in step with the server.
+    pub fn step(&mut self, movement: Vec2, dt: f32, config: &PlayerConfig) {
+        let (x, y) = (movement.x, movement.y);
+        //speed * dt * direction + current_velocity = some value. 
+        //if that value is larger than the max_velocity, set it to max velocity
+        //if a player stops pushing, the player will accelerate in the opposite direction of current movement
+        let mut x_velocity = self.velocity.x + self.acceleration * dt * x;
+        let mut y_velocity = self.velocity.y + self.acceleration * dt * y;
+        // if abs value of velocity is greater than
+        if self.max_velocity < x_velocity.abs() {
+            x_velocity = self.max_velocity * x;
+        }
+        if self.max_velocity < y_velocity.abs() {
+            y_velocity = self.max_velocity * y;
+        }
+        self.velocity.x = apply_frictions(x_velocity, config);
+        self.velocity.y = apply_frictions(y_velocity, config);
+    }
+}
+
+/// `velocity` in pixels per second
+pub fn apply_frictions( mut velocity: f32, config: &PlayerConfig ) -> f32 {
+    let friction_force = config.friction;
+    if velocity.abs() > 0.0 {
+        if velocity > 0. {
+            velocity -= config.friction_step_positive;
+            if velocity < 0. {
+                velocity = 0.0;
+            } else {
+                velocity -= friction_force * velocity;
+            }
+        } else {
+            velocity += config.friction_step_negative;
+            if velocity > 0. {
+                velocity = 0.0;
+            } else {
+                velocity += friction_force * velocity.abs();
+            }
+        }
+    }
+    velocity
+}
@@ -0,0 +1,313 @@
+use bevy::ecs::schedule::ShouldRun;
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use rand::Rng;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use crate::{ConfigError, FireCooldown, GameConfig, Health, Player, ProgressionConfig};
+
+
+/// relative to the asset folder, read with `asset_path` at startup and by the asset server for reloads
+pub const UPGRADE_DEFINITIONS_ASSET: &str = "config/upgrades.upgrades.ron";
+
+/// what taking an upgrade once does to a player
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum UpgradeEffect {
+    /// shots per second multiplied by `1 + fraction`
+    FireRate { fraction: f32 },
+    /// hit points added to every bullet
+    Damage { amount: i32 },
+    /// bullets added to every plain shot, fanned out around the aim
+    ExtraProjectile { count: u32 },
+    /// top speed and acceleration multiplied by `1 + fraction`
+    MoveSpeed { fraction: f32 },
+    /// max health raised by `amount`, and the player healed by as much
+    MaxHealth { amount: i32 },
+    /// pixels added to the radius pickups are collected from
+    PickupRadius { pixels: f32 },
+}
+
+impl UpgradeEffect {
+    /// false for effects that would do nothing or make the player worse
+    pub fn improves(&self) -> bool {
+        match *self {
+            UpgradeEffect::FireRate { fraction } | UpgradeEffect::MoveSpeed { fraction } => fraction > 0.0,
+            UpgradeEffect::Damage { amount } | UpgradeEffect::MaxHealth { amount } => amount > 0,
+            UpgradeEffect::ExtraProjectile { count } => count > 0,
+            UpgradeEffect::PickupRadius { pixels } => pixels > 0.0,
+        }
+    }
+}
+
+/// One upgrade offered on level-up.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct UpgradeDefinition {
+    pub name: String,
+    pub description: String,
+    pub effect: UpgradeEffect,
+    /// times one player can take it, `None` for no limit
+    #[serde(default)]
+    pub max_stacks: Option<u32>,
+    /// relative chance of being offered
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+}
+
+fn default_weight() -> f32 {
+    1.0
+}
+
+/// Every upgrade a level-up can offer, inserted as a resource.
+#[derive(Debug, Clone, PartialEq, Deserialize, TypeUuid)]
+#[uuid = "c4a91f07-2b6e-4d3a-9f58-7e1d0b3c6a24"]
+pub struct UpgradeDefinitions {
+    pub upgrades: Vec<UpgradeDefinition>,
+}
+
+impl Default for UpgradeDefinitions {
+    fn default() -> Self {
+        let upgrade = |name: &str, description: &str, effect, max_stacks| UpgradeDefinition {
+            name: name.to_owned(),
+            description: description.to_owned(),
+            effect,
+            max_stacks,
+            weight: 1.0,
+        };
+        UpgradeDefinitions {
+            upgrades: vec![
+                upgrade("rapid fire", "shoot 20% faster", UpgradeEffect::FireRate { fraction: 0.2 }, None),
+                upgrade("heavy rounds", "bullets deal 1 more damage", UpgradeEffect::Damage { amount: 1 }, None),
+                upgrade("multishot", "fire 1 more bullet", UpgradeEffect::ExtraProjectile { count: 1 }, Some(4)),
+                upgrade("quick feet", "move 10% faster", UpgradeEffect::MoveSpeed { fraction: 0.1 }, Some(5)),
+                upgrade("tough skin", "1 more max health", UpgradeEffect::MaxHealth { amount: 1 }, None),
+                upgrade("magnet", "collect pickups from 25 pixels further", UpgradeEffect::PickupRadius { pixels: 25.0 }, Some(6)),
+            ],
+        }
+    }
+}
+
+impl UpgradeDefinitions {
+    pub fn load(path: impl AsRef<Path>) -> Result<UpgradeDefinitions, ConfigError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_owned(), e))?;
+        let definitions = UpgradeDefinitions::parse(&text).map_err(|e| match e {
+            ConfigError::Parse(_, e) => ConfigError::Parse(path.to_owned(), e),
+            e => e,
+        })?;
+        Ok(definitions)
+    }
+
+    pub fn parse(text: &str) -> Result<UpgradeDefinitions, ConfigError> {
+        let definitions: UpgradeDefinitions = ron::from_str(text).map_err(|e| ConfigError::Parse(PathBuf::new(), e))?;
+        definitions.validate()?;
+        Ok(definitions)
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = vec![];
+        if self.upgrades.is_empty() {
+            problems.push("upgrades must contain at least one upgrade".to_owned());
+        }
+        for (i, upgrade) in self.upgrades.iter().enumerate() {
+            if upgrade.name.is_empty() {
+                problems.push(format!("upgrades[{}].name must not be empty", i));
+            }
+            if self.upgrades[..i].iter().any(|other| other.name == upgrade.name) {
+                problems.push(format!("upgrades[{}].name {:?} is defined twice", i, upgrade.name));
+            }
+            if !(upgrade.weight >= 0.0) {
+                problems.push(format!("upgrades[{}].weight must not be negative, got {}", i, upgrade.weight));
+            }
+            if upgrade.max_stacks == Some(0) {
+                problems.push(format!("upgrades[{}].max_stacks must be at least 1, or None for no limit", i));
+            }
+            if !upgrade.effect.improves() {
+                problems.push(format!("upgrades[{}].effect must improve something, got {:?}", i, upgrade.effect));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&UpgradeDefinition> {
+        self.upgrades.iter().find(|upgrade| upgrade.name == name)
+    }
+
+    /// Picks up to `count` different upgrades by weight, leaving out those `stats` already
+    /// has `max_stacks` of. Returns indices into `upgrades`.
+    pub fn offer(&self, stats: &PlayerStats, count: usize, rng: &mut impl Rng) -> Vec<usize> {
+        let mut candidates = self.upgrades.iter()
+            .enumerate()
+            .filter(|(_, upgrade)| upgrade.weight > 0.0)
+            .filter(|(_, upgrade)| upgrade.max_stacks.map_or(true, |max| stats.stacks(&upgrade.name) < max))
+            .map(|(i, upgrade)| (i, upgrade.weight))
+            .collect::<Vec<_>>();
+        let mut offered = vec![];
+        while offered.len() < count && !candidates.is_empty() {
+            let total: f32 = candidates.iter().map(|(_, weight)| weight).sum();
+            let mut remaining = rng.gen::<f32>() * total;
+            let mut picked = candidates.len() - 1;
+            for (j, (_, weight)) in candidates.iter().enumerate() {
+                if remaining < *weight {
+                    picked = j;
+                    break;
+                }
+                remaining -= weight;
+            }
+            offered.push(candidates.remove(picked).0);
+        }
+        offered
+    }
+}
+
+/// What a player's upgrades add up to, on top of the config they started from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerStats {
+    pub max_health: i32,
+    /// added to every bullet's damage
+    pub damage: i32,
+    pub extra_projectiles: u32,
+    /// multiplies shots per second
+    pub fire_rate: f32,
+    /// multiplies top speed and acceleration
+    pub move_speed: f32,
+    /// multiplies acceleration on top of `move_speed`
+    pub acceleration: f32,
+    /// pixels
+    pub pickup_radius: f32,
+    /// how often each upgrade was taken, by name
+    pub taken: HashMap<String, u32>,
+}
+
+impl PlayerStats {
+    pub fn new(config: &GameConfig) -> Self {
+        PlayerStats {
+            max_health: config.player.max_health,
+            damage: 0,
+            extra_projectiles: 0,
+            fire_rate: 1.0,
+            move_speed: 1.0,
+            acceleration: 1.0,
+            pickup_radius: config.weapon.ammo.pickup_radius,
+            taken: HashMap::new(),
+        }
+    }
+
+    pub fn stacks(&self, name: &str) -> u32 {
+        self.taken.get(name).copied().unwrap_or(0)
+    }
+
+    /// takes `upgrade` and counts it towards its `max_stacks`
+    pub fn apply(&mut self, upgrade: &UpgradeDefinition) {
+        *self.taken.entry(upgrade.name.clone()).or_insert(0) += 1;
+        self.apply_effect(upgrade.effect);
+    }
+
+    /// adds an effect that doesn't come from a level-up, e.g. a weapon's bonuses
+    pub fn apply_effect(&mut self, effect: UpgradeEffect) {
+        match effect {
+            UpgradeEffect::FireRate { fraction } => self.fire_rate *= 1.0 + fraction,
+            UpgradeEffect::Damage { amount } => self.damage += amount,
+            UpgradeEffect::ExtraProjectile { count } => self.extra_projectiles += count,
+            UpgradeEffect::MoveSpeed { fraction } => self.move_speed *= 1.0 + fraction,
+            UpgradeEffect::MaxHealth { amount } => self.max_health += amount,
+            UpgradeEffect::PickupRadius { pixels } => self.pickup_radius += pixels,
+        }
+    }
+
+    /// seconds between shots for a weapon that fires every `fire_interval` without upgrades
+    pub fn fire_interval(&self, fire_interval: f32) -> f32 {
+        fire_interval / self.fire_rate
+    }
+
+    /// Sets the speeds and cooldown the stats change, e.g. after an upgrade or a config reload.
+    pub fn update_player(&self, config: &GameConfig, player: &mut Player, cooldown: &mut FireCooldown) {
+        player.max_velocity = config.player.max_velocity * self.move_speed;
+        player.acceleration = config.player.acceleration * self.move_speed * self.acceleration;
+        cooldown.set_interval(self.fire_interval(config.weapon.fire_interval));
+    }
+
+    /// Takes `upgrade` and passes it on to the player's components. Raising max health also heals.
+    pub fn upgrade(&mut self, upgrade: &UpgradeDefinition, config: &GameConfig, player: &mut Player, cooldown: &mut FireCooldown, health: &mut Health) {
+        self.apply(upgrade);
+        if let UpgradeEffect::MaxHealth { amount } = upgrade.effect {
+            health.0 = (health.0 + amount).min(self.max_health);
+        }
+        self.update_player(config, player, cooldown);
+    }
+}
+
+/// level reached and experience gathered towards the next one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Experience {
+    /// starts at 1
+    pub level: u32,
+    pub xp: u32,
+}
+
+impl Default for Experience {
+    fn default() -> Self {
+        Experience { level: 1, xp: 0 }
+    }
+}
+
+impl Experience {
+    /// Adds `xp`, returns how many levels it was worth.
+    pub fn gain(&mut self, xp: u32, config: &ProgressionConfig) -> u32 {
+        self.xp += xp;
+        let mut levels = 0;
+        while self.xp >= config.xp_to_next(self.level) {
+            self.xp -= config.xp_to_next(self.level);
+            self.level += 1;
+            levels += 1;
+        }
+        levels
+    }
+}
+
+impl ProgressionConfig {
+    /// experience needed to go from `level` to the next one
+    pub fn xp_to_next(&self, level: u32) -> u32 {
+        let xp = self.first_level_xp as f32 * self.level_growth.powi(level.saturating_sub(1) as i32);
+        (xp.round() as u32).max(1)
+    }
+}
+
+/// experience orb dropped by a killed enemy, worth this much to whoever collects it
+pub struct XpPickup(pub u32);
+
+/// A player picking one of `options`, indices into `UpgradeDefinitions`. Gameplay is paused
+/// while this resource exists.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UpgradeChoice {
+    pub player: Entity,
+    pub options: Vec<usize>,
+    /// the player let go of aim since the choice opened, so holding it doesn't pick straight away
+    pub ready: bool,
+}
+
+/// players who levelled up and haven't been offered their upgrades yet, one entry per level
+#[derive(Debug, Default)]
+pub struct PendingLevelUps(pub Vec<Entity>);
+
+/// Inserted by the HUD when it has a font to draw with. Choices and menus only the HUD can show
+/// are made automatically without it instead of freezing the game on an empty screen.
+pub struct HudShown;
+
+/// gameplay is frozen, e.g. while an upgrade is being picked
+#[derive(Debug, Default)]
+pub struct Paused(pub bool);
+
+/// run criteria for systems that should stand still while the game is paused
+pub fn unpaused(paused: Option<Res<Paused>>) -> ShouldRun {
+    if paused.map_or(false, |paused| paused.0) {
+        ShouldRun::No
+    } else {
+        ShouldRun::Yes
+    }
+}
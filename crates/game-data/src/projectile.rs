@@ -0,0 +1,124 @@
+use bevy::prelude::*;
+use bevy::ecs::system::EntityCommands;
+use crate::*;
+
+
+/// unit vector a bullet travels along; its speed is in `Bullet`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Heading(pub Vec2);
+
+/// hit points a bullet takes off the enemy it hits, 1 without
+pub struct Damage(pub i32);
+
+/// bullet turns towards the nearest enemy within `range` pixels, at most `turn_rate` radians per second
+pub struct Homing {
+    pub turn_rate: f32,
+    pub range: f32,
+}
+/// enemies the bullet can still pass through
+pub struct Pierce(pub u32);
+/// walls the bullet can still bounce off
+pub struct Ricochet(pub u32);
+/// on hit, fires `fragments` plain bullets fanned over `spread` radians around its heading
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Split {
+    pub fragments: u32,
+    pub spread: f32,
+}
+/// on hit, deals `damage` to every other enemy within `radius` pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Explosive {
+    pub radius: f32,
+    pub damage: i32,
+}
+
+/// a bullet hit an enemy; split and explosive bullets act on it. Carries the bullet's modifiers
+/// since a used up bullet may already be recycled when the event is read.
+pub struct ProjectileHitEvent {
+    pub bullet: Entity,
+    pub enemy: Entity,
+    pub position: Vec2,
+    pub heading: Vec2,
+    pub owner: Option<Entity>,
+    pub split: Option<Split>,
+    pub explosive: Option<Explosive>,
+}
+
+/// Rotates `heading` towards `desired` by at most `max_angle` radians.
+pub fn turn_towards(heading: Vec2, desired: Vec2, max_angle: f32) -> Vec2 {
+    if desired == Vec2::ZERO {
+        return heading;
+    }
+    let angle = (heading.x * desired.y - heading.y * desired.x).atan2(heading.dot(desired));
+    if angle.abs() <= max_angle {
+        return desired.normalize();
+    }
+    rotate(heading, max_angle * angle.signum())
+}
+
+fn rotate(vector: Vec2, angle: f32) -> Vec2 {
+    let (sin, cos) = angle.sin_cos();
+    Vec2::new(vector.x * cos - vector.y * sin, vector.x * sin + vector.y * cos)
+}
+
+/// `heading` mirrored off a surface facing `normal`
+pub fn reflect(heading: Vec2, normal: Vec2) -> Vec2 {
+    heading - 2.0 * heading.dot(normal) * normal
+}
+
+/// Which way the face of the box from `min` to `max` that is closest to `position` points.
+pub fn bounce_normal(position: Vec2, min: Vec2, max: Vec2) -> Vec2 {
+    let faces = [
+        (position.x - min.x, Vec2::new(-1.0, 0.0)),
+        (max.x - position.x, Vec2::new(1.0, 0.0)),
+        (position.y - min.y, Vec2::new(0.0, -1.0)),
+        (max.y - position.y, Vec2::new(0.0, 1.0)),
+    ];
+    // outside the box the face it is beyond is the one it hit
+    faces.iter()
+        .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal))
+        .map_or(Vec2::ZERO, |face| face.1)
+}
+
+/// `count` unit vectors spread evenly over `spread` radians, centred on `heading`
+pub fn fan(heading: Vec2, count: u32, spread: f32) -> Vec<Vec2> {
+    if count <= 1 {
+        return (0..count).map(|_| heading).collect();
+    }
+    let step = spread / (count - 1) as f32;
+    (0..count)
+        .map(|i| rotate(heading, -spread / 2.0 + step * i as f32))
+        .collect()
+}
+
+impl ProjectileConfig {
+    /// Adds the configured modifiers to a bullet and removes the rest, so pooled bullets don't
+    /// keep the ones they were fired with last time.
+    pub fn arm(&self, entity_commands: &mut EntityCommands) {
+        if self.homing_turn_rate > 0.0 {
+            entity_commands.insert(Homing { turn_rate: self.homing_turn_rate.to_radians(), range: self.homing_range });
+        } else {
+            entity_commands.remove::<Homing>();
+        }
+        if self.ricochets > 0 {
+            entity_commands.insert(Ricochet(self.ricochets));
+        } else {
+            entity_commands.remove::<Ricochet>();
+        }
+        if self.pierce > 0 {
+            entity_commands.insert(Pierce(self.pierce));
+        } else {
+            entity_commands.remove::<Pierce>();
+        }
+        if self.split_fragments > 0 {
+            entity_commands.insert(Split { fragments: self.split_fragments, spread: self.split_spread.to_radians() });
+        } else {
+            entity_commands.remove::<Split>();
+        }
+        if self.explosion_radius > 0.0 && self.explosion_damage > 0 {
+            entity_commands.insert(Explosive { radius: self.explosion_radius, damage: self.explosion_damage });
+        } else {
+            entity_commands.remove::<Explosive>();
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use crate::{CharacterDefinition, ConfigError, FireCooldown, GameConfig, Health, Player, PlayerStats, UpgradeEffect, WeaponDefinition};
+
+
+/// relative to the asset folder, see `asset_path`
+pub const SHOP_DEFINITIONS_ASSET: &str = "config/shop.shop.ron";
+
+/// what buying a shop item does, for every run after it
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub enum ShopEffect {
+    /// every player starts each run with the effect, once per level bought
+    Stat(UpgradeEffect),
+    /// the named `WeaponDefinition` can be picked for new runs
+    UnlockWeapon(String),
+    /// the named `CharacterDefinition` can be picked for new runs
+    UnlockCharacter(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ShopItem {
+    pub name: String,
+    pub description: String,
+    /// price of the first level
+    pub cost: u32,
+    /// every level costs this many times the one before
+    #[serde(default = "default_cost_growth")]
+    pub cost_growth: f32,
+    #[serde(default = "default_max_level")]
+    pub max_level: u32,
+    pub effect: ShopEffect,
+}
+
+fn default_cost_growth() -> f32 {
+    1.0
+}
+
+fn default_max_level() -> u32 {
+    1
+}
+
+impl ShopItem {
+    /// price of the next level when `owned` levels are already bought
+    pub fn cost_at(&self, owned: u32) -> u32 {
+        (self.cost as f32 * self.cost_growth.powi(owned as i32)).round() as u32
+    }
+}
+
+/// What the between-runs shop sells and how much currency a run earns, inserted as a resource.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ShopDefinitions {
+    pub currency_per_kill: u32,
+    /// for every wave reached after the first
+    pub currency_per_wave: u32,
+    pub items: Vec<ShopItem>,
+}
+
+impl Default for ShopDefinitions {
+    fn default() -> Self {
+        let stat = |name: &str, description: &str, cost, max_level, effect| ShopItem {
+            name: name.to_owned(),
+            description: description.to_owned(),
+            cost,
+            cost_growth: 1.5,
+            max_level,
+            effect: ShopEffect::Stat(effect),
+        };
+        let unlock = |name: &str, description: &str, cost, effect| ShopItem {
+            name: name.to_owned(),
+            description: description.to_owned(),
+            cost,
+            cost_growth: 1.0,
+            max_level: 1,
+            effect,
+        };
+        ShopDefinitions {
+            currency_per_kill: 1,
+            currency_per_wave: 10,
+            items: vec![
+                stat("vitality", "start with 1 more max health", 30, 3, UpgradeEffect::MaxHealth { amount: 1 }),
+                stat("trigger finger", "start shooting 10% faster", 25, 5, UpgradeEffect::FireRate { fraction: 0.1 }),
+                stat("sprinter", "start moving 5% faster", 20, 5, UpgradeEffect::MoveSpeed { fraction: 0.05 }),
+                stat("scavenger", "start collecting pickups from 10 pixels further", 15, 3, UpgradeEffect::PickupRadius { pixels: 10.0 }),
+                unlock("repeater", "unlock the repeater: fires while held", 60, ShopEffect::UnlockWeapon("repeater".to_owned())),
+                unlock("scattergun", "unlock the scattergun: three bullets per tap", 120, ShopEffect::UnlockWeapon("scattergun".to_owned())),
+                unlock("scout", "unlock the scout: fast and fragile", 80, ShopEffect::UnlockCharacter("scout".to_owned())),
+                unlock("tank", "unlock the tank: slow, sturdy and regenerating", 150, ShopEffect::UnlockCharacter("tank".to_owned())),
+            ],
+        }
+    }
+}
+
+impl ShopDefinitions {
+    pub fn load(path: impl AsRef<Path>) -> Result<ShopDefinitions, ConfigError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_owned(), e))?;
+        let definitions = ShopDefinitions::parse(&text).map_err(|e| match e {
+            ConfigError::Parse(_, e) => ConfigError::Parse(path.to_owned(), e),
+            e => e,
+        })?;
+        Ok(definitions)
+    }
+
+    pub fn parse(text: &str) -> Result<ShopDefinitions, ConfigError> {
+        let definitions: ShopDefinitions = ron::from_str(text).map_err(|e| ConfigError::Parse(PathBuf::new(), e))?;
+        definitions.validate()?;
+        Ok(definitions)
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = vec![];
+        for (i, item) in self.items.iter().enumerate() {
+            if item.name.is_empty() {
+                problems.push(format!("items[{}].name must not be empty", i));
+            }
+            if self.items[..i].iter().any(|other| other.name == item.name) {
+                problems.push(format!("items[{}].name {:?} is sold twice", i, item.name));
+            }
+            if item.max_level < 1 {
+                problems.push(format!("items[{}].max_level must be at least 1", i));
+            }
+            if !(item.cost_growth >= 1.0) {
+                problems.push(format!("items[{}].cost_growth must be at least 1.0, got {}", i, item.cost_growth));
+            }
+            match &item.effect {
+                ShopEffect::Stat(effect) if !effect.improves() => {
+                    problems.push(format!("items[{}].effect must improve something, got {:?}", i, effect));
+                },
+                ShopEffect::UnlockWeapon(_) | ShopEffect::UnlockCharacter(_) if item.max_level != 1 => {
+                    problems.push(format!("items[{}].max_level must be 1 for an unlock", i));
+                },
+                _ => (),
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+
+    /// currency a run that ended with `score` kills at `wave` pays out
+    pub fn earnings(&self, score: u32, wave: u32) -> u32 {
+        score * self.currency_per_kill + wave.saturating_sub(1) * self.currency_per_wave
+    }
+}
+
+/// What the players of the next run start with. Worked out from the profile between runs;
+/// without one, runs start as the configured player with the configured weapon and no bonuses.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Loadout {
+    pub character: Option<CharacterDefinition>,
+    pub weapon: Option<WeaponDefinition>,
+    /// permanent upgrades bought in the shop
+    pub bonuses: Vec<UpgradeEffect>,
+}
+
+impl Loadout {
+    /// Puts the character, weapon and bonuses on a freshly spawned player, who starts at full health.
+    pub fn equip(&self, config: &GameConfig, stats: &mut PlayerStats, player: &mut Player, cooldown: &mut FireCooldown, health: &mut Health) {
+        if let Some(character) = &self.character {
+            character.apply(stats);
+        }
+        let weapon_bonuses = self.weapon.iter().flat_map(|weapon| weapon.bonuses.iter());
+        for bonus in weapon_bonuses.chain(self.bonuses.iter()) {
+            stats.apply_effect(*bonus);
+        }
+        if let Some(weapon) = &self.weapon {
+            cooldown.mode = weapon.fire_mode;
+        }
+        // however fragile the character, they start with some health
+        stats.max_health = stats.max_health.max(1);
+        stats.update_player(config, player, cooldown);
+        health.0 = stats.max_health;
+    }
+}
+
+/// A between-runs screen, the shop or the character select, is open. Kept up to date by the
+/// profile; audio plays the menu track while it is set.
+#[derive(Debug, Default)]
+pub struct MenuOpen(pub bool);
@@ -0,0 +1,171 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::mem::discriminant;
+use std::time::Duration;
+
+
+/// slows and burns from several sources add up to this many stacks
+pub const MAX_SLOW_STACKS: u32 = 3;
+pub const MAX_BURN_STACKS: u32 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum StatusEffect {
+    /// speed is multiplied by `1 - fraction` once per stack
+    Slow { fraction: f32 },
+    /// health lost per second and stack
+    Burn { damage_per_second: f32 },
+    /// can't move
+    Freeze,
+    /// can't move or fire
+    Stun,
+    /// pushed away from whatever applied it at `speed` pixels per second, fading out
+    Knockback { speed: f32 },
+}
+
+/// an effect and how many seconds it lasts, as written in the config files
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct TimedEffect {
+    pub effect: StatusEffect,
+    pub seconds: f32,
+}
+
+impl TimedEffect {
+    /// what is wrong with this effect, for config validation
+    pub fn problem(&self) -> Option<String> {
+        if !(self.seconds > 0.0) {
+            return Some(format!("seconds must be greater than 0, got {}", self.seconds));
+        }
+        match self.effect {
+            StatusEffect::Slow { fraction } if !(0.0..1.0).contains(&fraction) => {
+                Some(format!("slow fraction must be in 0.0..1.0, got {}", fraction))
+            },
+            StatusEffect::Burn { damage_per_second } if !(damage_per_second >= 0.0) => {
+                Some(format!("burn damage_per_second must not be negative, got {}", damage_per_second))
+            },
+            StatusEffect::Knockback { speed } if !(speed >= 0.0) => {
+                Some(format!("knockback speed must not be negative, got {}", speed))
+            },
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ActiveEffect {
+    pub effect: StatusEffect,
+    pub stacks: u32,
+    pub timer: Timer,
+    /// unit vector a knockback pushes along
+    pub push: Vec2,
+}
+
+/// Timed effects on a player or enemy. Slows and burns stack up to their limit and keep the
+/// strongest value, freezes and stuns only extend, and every knockback pushes on its own.
+/// Reapplying an effect restarts it if the new one lasts longer than what is left.
+#[derive(Debug, Clone, Default)]
+pub struct StatusEffects {
+    active: Vec<ActiveEffect>,
+    /// burn damage that hasn't added up to a whole hit point yet
+    burn: f32,
+    pending_damage: i32,
+}
+
+impl StatusEffects {
+    /// `push` is the direction a knockback sends the entity, ignored by other effects
+    pub fn apply(&mut self, timed: &TimedEffect, push: Vec2) {
+        let timer = Timer::from_seconds(timed.seconds, false);
+        let max_stacks = match timed.effect {
+            StatusEffect::Slow { .. } => MAX_SLOW_STACKS,
+            StatusEffect::Burn { .. } => MAX_BURN_STACKS,
+            StatusEffect::Freeze | StatusEffect::Stun => 1,
+            StatusEffect::Knockback { .. } => {
+                self.active.push(ActiveEffect { effect: timed.effect, stacks: 1, timer, push: push.normalize_or_zero() });
+                return;
+            }
+        };
+        let existing = self.active.iter_mut().find(|active| discriminant(&active.effect) == discriminant(&timed.effect));
+        match existing {
+            Some(active) => {
+                active.stacks = (active.stacks + 1).min(max_stacks);
+                active.effect = stronger(active.effect, timed.effect);
+                let left = active.timer.duration().as_secs_f32() - active.timer.elapsed_secs();
+                if timed.seconds > left {
+                    active.timer = timer;
+                }
+            },
+            None => self.active.push(ActiveEffect { effect: timed.effect, stacks: 1, timer, push: Vec2::ZERO }),
+        }
+    }
+
+    /// Advances every effect by `delta` and drops the ones that ran out.
+    pub fn tick(&mut self, delta: Duration) {
+        for active in self.active.iter_mut() {
+            if let StatusEffect::Burn { damage_per_second } = active.effect {
+                let seconds = delta.min(active.timer.duration().saturating_sub(active.timer.elapsed()));
+                self.burn += damage_per_second * active.stacks as f32 * seconds.as_secs_f32();
+            }
+            active.timer.tick(delta);
+        }
+        self.active.retain(|active| !active.timer.finished());
+        if self.burn >= 1.0 {
+            let whole = self.burn.floor();
+            self.pending_damage += whole as i32;
+            self.burn -= whole;
+        }
+    }
+
+    /// whole hit points burned away since the last call
+    pub fn take_damage(&mut self) -> i32 {
+        std::mem::take(&mut self.pending_damage)
+    }
+
+    pub fn clear(&mut self) {
+        *self = StatusEffects::default();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.active.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ActiveEffect> {
+        self.active.iter()
+    }
+
+    fn has(&self, effect: StatusEffect) -> bool {
+        self.active.iter().any(|active| discriminant(&active.effect) == discriminant(&effect))
+    }
+
+    /// 0 while frozen or stunned
+    pub fn speed_multiplier(&self) -> f32 {
+        if self.has(StatusEffect::Freeze) || self.has(StatusEffect::Stun) {
+            return 0.0;
+        }
+        self.active.iter().fold(1.0, |multiplier, active| match active.effect {
+            StatusEffect::Slow { fraction } => multiplier * (1.0 - fraction).powi(active.stacks as i32),
+            _ => multiplier,
+        })
+    }
+
+    /// stunned entities can't fire
+    pub fn can_act(&self) -> bool {
+        !self.has(StatusEffect::Stun)
+    }
+
+    /// pixels per second added on top of normal movement, even while frozen
+    pub fn knockback(&self) -> Vec2 {
+        self.active.iter().fold(Vec2::ZERO, |total, active| match active.effect {
+            StatusEffect::Knockback { speed } => total + active.push * speed * active.timer.percent_left(),
+            _ => total,
+        })
+    }
+}
+
+fn stronger(old: StatusEffect, new: StatusEffect) -> StatusEffect {
+    match (old, new) {
+        (StatusEffect::Slow { fraction: a }, StatusEffect::Slow { fraction: b }) => StatusEffect::Slow { fraction: a.max(b) },
+        (StatusEffect::Burn { damage_per_second: a }, StatusEffect::Burn { damage_per_second: b }) => {
+            StatusEffect::Burn { damage_per_second: a.max(b) }
+        },
+        _ => new,
+    }
+}
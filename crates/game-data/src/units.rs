@@ -0,0 +1,83 @@
+use bevy::prelude::*;
+use bevy_rapier2d::physics::RapierConfiguration;
+use bevy_rapier2d::rapier::na::Vector2;
+
+
+// Gameplay values (positions, sizes, speeds, accelerations) are written in world units, i.e. pixels
+// and pixels per second. Rapier works in smaller units to avoid float rounding problems, so anything
+// handed to or read from a rigid body is converted with `Units`. Changing `RapierConfiguration::scale`
+// then only changes what the physics engine sees, never how fast things move on screen.
+
+/// pixels per physics unit, what `setup` sets `RapierConfiguration::scale` to
+pub const PHYSICS_SCALE: f32 = 20.0;
+
+/// position in world space, pixels
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct WorldPos(pub Vec2);
+
+/// position in physics space, world / scale
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PhysicsPos(pub Vec2);
+
+/// velocity in world space
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PixelsPerSecond(pub Vec2);
+
+/// velocity in physics space, what `RigidBody::linvel` holds
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PhysicsVelocity(pub Vec2);
+
+impl PhysicsPos {
+    pub fn vector(&self) -> Vector2<f32> {
+        Vector2::new(self.0.x, self.0.y)
+    }
+}
+
+impl PhysicsVelocity {
+    pub fn vector(&self) -> Vector2<f32> {
+        Vector2::new(self.0.x, self.0.y)
+    }
+
+    pub fn from_vector(velocity: &Vector2<f32>) -> Self {
+        PhysicsVelocity(Vec2::new(velocity.x, velocity.y))
+    }
+}
+
+/// Converts between world and physics space with the configured `RapierConfiguration::scale`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Units {
+    pub scale: f32,
+}
+
+impl Units {
+    pub fn new(rapier_config: &RapierConfiguration) -> Self {
+        Units { scale: rapier_config.scale }
+    }
+
+    pub fn to_physics(&self, position: WorldPos) -> PhysicsPos {
+        PhysicsPos(position.0 / self.scale)
+    }
+
+    pub fn to_world(&self, position: PhysicsPos) -> WorldPos {
+        WorldPos(position.0 * self.scale)
+    }
+
+    pub fn velocity_to_physics(&self, velocity: PixelsPerSecond) -> PhysicsVelocity {
+        PhysicsVelocity(velocity.0 / self.scale)
+    }
+
+    pub fn velocity_to_world(&self, velocity: PhysicsVelocity) -> PixelsPerSecond {
+        PixelsPerSecond(velocity.0 * self.scale)
+    }
+
+    /// sizes and distances, e.g. collider half extents
+    pub fn length_to_physics(&self, pixels: f32) -> f32 {
+        pixels / self.scale
+    }
+}
+
+impl From<&RapierConfiguration> for Units {
+    fn from(rapier_config: &RapierConfiguration) -> Self {
+        Units::new(rapier_config)
+    }
+}
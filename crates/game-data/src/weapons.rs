@@ -0,0 +1,109 @@
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use crate::{ConfigError, FireMode, UpgradeEffect};
+
+
+/// relative to the asset folder, see `asset_path`
+pub const WEAPON_DEFINITIONS_ASSET: &str = "config/weapons.weapons.ron";
+
+/// A weapon a run can start with. It picks the fire mode and adds its bonuses on top of
+/// `weapon` in the game config; bullets, ammo and bombs stay as configured there.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct WeaponDefinition {
+    pub name: String,
+    pub description: String,
+    pub fire_mode: FireMode,
+    #[serde(default)]
+    pub bonuses: Vec<UpgradeEffect>,
+}
+
+/// Every weapon, inserted as a resource. The first one is unlocked from the start.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct WeaponDefinitions {
+    pub weapons: Vec<WeaponDefinition>,
+}
+
+impl Default for WeaponDefinitions {
+    fn default() -> Self {
+        WeaponDefinitions {
+            weapons: vec![
+                WeaponDefinition {
+                    name: "charger".to_owned(),
+                    description: "hold to charge a bigger shot".to_owned(),
+                    fire_mode: FireMode::Charged { seconds: 1.0 },
+                    bonuses: vec![],
+                },
+                WeaponDefinition {
+                    name: "repeater".to_owned(),
+                    description: "fires while held".to_owned(),
+                    fire_mode: FireMode::Hold,
+                    bonuses: vec![],
+                },
+                WeaponDefinition {
+                    name: "scattergun".to_owned(),
+                    description: "three bullets per tap".to_owned(),
+                    fire_mode: FireMode::Tap,
+                    bonuses: vec![UpgradeEffect::ExtraProjectile { count: 2 }],
+                },
+            ],
+        }
+    }
+}
+
+impl WeaponDefinitions {
+    pub fn load(path: impl AsRef<Path>) -> Result<WeaponDefinitions, ConfigError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_owned(), e))?;
+        let definitions = WeaponDefinitions::parse(&text).map_err(|e| match e {
+            ConfigError::Parse(_, e) => ConfigError::Parse(path.to_owned(), e),
+            e => e,
+        })?;
+        Ok(definitions)
+    }
+
+    pub fn parse(text: &str) -> Result<WeaponDefinitions, ConfigError> {
+        let definitions: WeaponDefinitions = ron::from_str(text).map_err(|e| ConfigError::Parse(PathBuf::new(), e))?;
+        definitions.validate()?;
+        Ok(definitions)
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = vec![];
+        if self.weapons.is_empty() {
+            problems.push("weapons must contain at least one weapon".to_owned());
+        }
+        for (i, weapon) in self.weapons.iter().enumerate() {
+            if weapon.name.is_empty() {
+                problems.push(format!("weapons[{}].name must not be empty", i));
+            }
+            if self.weapons[..i].iter().any(|other| other.name == weapon.name) {
+                problems.push(format!("weapons[{}].name {:?} is defined twice", i, weapon.name));
+            }
+            if let FireMode::Charged { seconds } = weapon.fire_mode {
+                if !(seconds > 0.0) {
+                    problems.push(format!("weapons[{}].fire_mode charge seconds must be greater than 0, got {}", i, seconds));
+                }
+            }
+            for (j, bonus) in weapon.bonuses.iter().enumerate() {
+                if !bonus.improves() {
+                    problems.push(format!("weapons[{}].bonuses[{}] must improve something, got {:?}", i, j, bonus));
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&WeaponDefinition> {
+        self.weapons.iter().find(|weapon| weapon.name == name)
+    }
+
+    /// the weapon every profile starts with
+    pub fn starter(&self) -> &WeaponDefinition {
+        self.weapons.first().expect("validated definitions are never empty")
+    }
+}
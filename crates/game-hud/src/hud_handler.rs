@@ -0,0 +1,87 @@
+use bevy::prelude::*;
+use game_data::*;
+use game_profile::*;
+use crate::*;
+
+
+pub fn spawn_hud(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    commands.spawn_bundle(UiCameraBundle::default());
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(8.0),
+                    left: Val::Px(8.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: asset_server.load(HUD_FONT),
+                    font_size: 20.0,
+                    color: Color::WHITE,
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(HudText);
+}
+
+pub fn update_hud(
+    mut hud: Query<&mut Text, With<HudText>>,
+    players: Query<(&PlayerId, Option<&Ammo>, Option<&Bombs>, Option<&Downed>, Option<&Experience>)>,
+    choice: Option<Res<UpgradeChoice>>,
+    upgrades: Option<Res<UpgradeDefinitions>>,
+    shop_screen: Option<Res<ShopScreen>>,
+    profile: Option<Res<Profile>>,
+    shop: Option<Res<ShopDefinitions>>,
+    weapons: Option<Res<WeaponDefinitions>>,
+    character_select: Option<Res<CharacterSelect>>,
+    characters: Option<Res<CharacterDefinitions>>,
+    config: Res<GameConfig>,
+) {
+    let mut text = match hud.iter_mut().next() {
+        Some(text) => text,
+        None => return,
+    };
+    let mut choosing = None;
+    if let (Some(choice), Some(upgrades)) = (choice.as_ref(), upgrades.as_ref()) {
+        if let Ok((id, ..)) = players.get(choice.player) {
+            let options = choice.options.iter().filter_map(|&index| upgrades.upgrades.get(index)).collect::<Vec<_>>();
+            choosing = Some(choice_text(*id, &options));
+        }
+    }
+    let mut players = players.iter().collect::<Vec<_>>();
+    players.sort_by_key(|(id, ..)| id.0);
+    let mut lines = players
+        .into_iter()
+        .map(|(id, ammo, bombs, downed, experience)| {
+            let mut line = hud_line(*id, ammo, bombs, downed.is_some(), &config.weapon.ammo);
+            if let Some(experience) = experience {
+                line.push_str("  ");
+                line.push_str(&level_text(experience, &config.progression));
+            }
+            line
+        })
+        .collect::<Vec<_>>();
+    lines.extend(choosing);
+    if let (Some(profile), Some(shop), Some(weapons), Some(characters)) = (profile, shop, weapons, characters) {
+        if let Some(screen) = shop_screen {
+            lines.push(shop_text(&screen, &profile, &shop, &weapons, &characters));
+        }
+        if let Some(screen) = character_select {
+            lines.push(character_text(&screen, &profile, &characters));
+        }
+    }
+    let lines = lines.join("\n");
+    if text.sections[0].value != lines {
+        text.sections[0].value = lines;
+    }
+}
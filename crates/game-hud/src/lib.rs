@@ -0,0 +1,182 @@
+use bevy::prelude::*;
+use game_data::*;
+use game_profile::*;
+use hud_handler::*;
+mod hud_handler;
+
+
+/// Text in the top left corner with every player's ammo, reloads, bombs and level, the
+/// upgrades on offer while someone levels up, and the shop and character select between runs. Needs the font at
+/// `HUD_FONT` in the asset folder; without it the game runs without a HUD and `HudShown` is
+/// left out, so level-ups and menus don't wait on a screen nobody can see.
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        if !asset_path(HUD_FONT).exists() {
+            warn!("font {} is missing, playing without a HUD", HUD_FONT);
+            return;
+        }
+        app.insert_resource(HudShown)
+            .add_startup_system(spawn_hud.system())
+            .add_system(update_hud.system());
+    }
+}
+
+/// relative to the asset folder
+pub const HUD_FONT: &str = "fonts/hud.ttf";
+
+/// the text the HUD writes into
+pub struct HudText;
+
+/// One player's line, e.g. `P1  7/96  bombs 2`. A dash stands for endless rounds.
+pub fn hud_line(id: PlayerId, ammo: Option<&Ammo>, bombs: Option<&Bombs>, downed: bool, config: &AmmoConfig) -> String {
+    let mut line = format!("P{}", id.0 + 1);
+    if downed {
+        line.push_str("  down");
+        return line;
+    }
+    let reserve = |ammo: &Ammo| ammo.reserve.map_or("-".to_owned(), |reserve| reserve.to_string());
+    match (ammo, config.magazine) {
+        (Some(ammo), _) if ammo.is_reloading() => {
+            let progress = ammo.reload_progress().unwrap_or(0.0);
+            line.push_str(&format!("  reloading {:.0}%", progress * 100.0));
+        },
+        (Some(ammo), Some(_)) => line.push_str(&format!("  {}/{}", ammo.loaded, reserve(ammo))),
+        (Some(ammo), None) => line.push_str(&format!("  {}", reserve(ammo))),
+        (None, _) => (),
+    }
+    if let Some(bombs) = bombs {
+        line.push_str(&format!("  bombs {}", bombs.left));
+    }
+    line
+}
+
+/// e.g. `lv 3  4/10`, experience gathered and needed for the next level
+pub fn level_text(experience: &Experience, config: &ProgressionConfig) -> String {
+    format!("lv {}  {}/{}", experience.level, experience.xp, config.xp_to_next(experience.level))
+}
+
+/// Which way to aim for each upgrade on offer, in the order `choose_upgrade` reads them.
+pub fn choice_text(id: PlayerId, options: &[&UpgradeDefinition]) -> String {
+    let mut text = format!("P{} levelled up! aim to pick an upgrade", id.0 + 1);
+    for (aim, upgrade) in ["left", "up", "right"].iter().zip(options.iter()) {
+        text.push_str(&format!("\n  {}: {} - {}", aim, upgrade.name, upgrade.description));
+    }
+    text
+}
+
+/// The between-runs shop, one `shop_lines` line each with the selected one marked, e.g.
+/// `> vitality 1/3 - start with 1 more max health - 45`.
+pub fn shop_text(screen: &ShopScreen, profile: &Profile, shop: &ShopDefinitions, weapons: &WeaponDefinitions, characters: &CharacterDefinitions) -> String {
+    let mut text = format!("run over, earned {} ({} to spend)", screen.earned, profile.currency);
+    for (i, line) in shop_lines(shop, weapons).into_iter().enumerate() {
+        let cursor = if i == screen.selected { ">" } else { " " };
+        let entry = match line {
+            ShopLine::Item(index) => {
+                let item = &shop.items[index];
+                let owned = profile.level(&item.name);
+                let levels = if item.max_level > 1 { format!(" {}/{}", owned, item.max_level) } else { String::new() };
+                let price = match (owned >= item.max_level, item.max_level > 1) {
+                    (true, true) => "maxed".to_owned(),
+                    (true, false) => "owned".to_owned(),
+                    (false, _) => item.cost_at(owned).to_string(),
+                };
+                format!("{}{} - {} - {}", item.name, levels, item.description, price)
+            },
+            ShopLine::Weapon(index) => {
+                let weapon = &weapons.weapons[index];
+                let mut entry = format!("weapon {} - {}", weapon.name, weapon.description);
+                if profile.weapon(weapons, characters).name == weapon.name {
+                    entry.push_str(" - equipped");
+                } else if !profile.has_weapon(&weapon.name, weapons) {
+                    entry.push_str(" - locked");
+                }
+                entry
+            },
+        };
+        text.push_str(&format!("\n{} {}", cursor, entry));
+    }
+    text.push_str("\naim up and down to choose, fire to buy or equip, bomb to pick a character");
+    text
+}
+
+/// Every character with the selected one marked, e.g. `> scout - fast and fragile - locked`.
+pub fn character_text(screen: &CharacterSelect, profile: &Profile, characters: &CharacterDefinitions) -> String {
+    let mut text = "choose a character".to_owned();
+    for (i, character) in characters.characters.iter().enumerate() {
+        let cursor = if i == screen.selected { ">" } else { " " };
+        text.push_str(&format!("\n{} {} - {}", cursor, character.name, character.description));
+        if !profile.has_character(&character.name, characters) {
+            text.push_str(" - locked");
+        }
+    }
+    text.push_str("\naim up and down to choose, fire to start the run");
+    text
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn hud_shows_rounds_reloads_and_bombs() {
+        let config = AmmoConfig { magazine: Some(12), reserve: Some(96), ..AmmoConfig::default() };
+        let mut ammo = Ammo::new(&config);
+        ammo.loaded = 7;
+        let bombs = Bombs::new(2);
+        assert_eq!(hud_line(PlayerId(0), Some(&ammo), Some(&bombs), false, &config), "P1  7/96  bombs 2");
+
+        ammo.start_reload(&config);
+        ammo.tick(Duration::from_secs_f32(config.reload_time / 4.0), &config);
+        assert_eq!(hud_line(PlayerId(1), Some(&ammo), None, false, &config), "P2  reloading 25%");
+        assert_eq!(hud_line(PlayerId(1), Some(&ammo), None, true, &config), "P2  down");
+
+        let progression = GameConfig::default().progression;
+        assert_eq!(level_text(&Experience { level: 2, xp: 3 }, &progression), "lv 2  3/7");
+
+        let endless = AmmoConfig { magazine: None, reserve: None, ..config };
+        assert_eq!(hud_line(PlayerId(0), Some(&Ammo::new(&endless)), None, false, &endless), "P1  -");
+    }
+
+    #[test]
+    fn choices_are_listed_in_aim_order() {
+        let upgrades = UpgradeDefinitions::default();
+        let options = [upgrades.get("magnet").unwrap(), upgrades.get("multishot").unwrap()];
+        assert_eq!(
+            choice_text(PlayerId(1), &options),
+            "P2 levelled up! aim to pick an upgrade\n  left: magnet - collect pickups from 25 pixels further\n  up: multishot - fire 1 more bullet",
+        );
+    }
+
+    #[test]
+    fn shop_marks_the_selection_prices_and_weapons() {
+        let shop = ShopDefinitions::default();
+        let weapons = WeaponDefinitions::default();
+        let mut profile = Profile { currency: 100, ..Profile::default() };
+        profile.buy(&shop.items[0]).unwrap();
+        let screen = ShopScreen { selected: 0, ..ShopScreen::new(Entity::new(0), 25) };
+        let lines = shop_text(&screen, &profile, &shop, &weapons, &CharacterDefinitions::default()).lines().map(str::to_owned).collect::<Vec<_>>();
+        assert_eq!(lines[0], "run over, earned 25 (70 to spend)");
+        assert_eq!(lines[1], "> vitality 1/3 - start with 1 more max health - 45");
+        assert_eq!(lines[5], "  repeater - unlock the repeater: fires while held - 60");
+        assert_eq!(lines[7], "  scout - unlock the scout: fast and fragile - 80");
+        assert_eq!(lines[9], "  weapon charger - hold to charge a bigger shot - equipped");
+        assert_eq!(lines[10], "  weapon repeater - fires while held - locked");
+        assert_eq!(lines.len(), 13);
+    }
+
+    #[test]
+    fn character_select_marks_locked_characters() {
+        let characters = CharacterDefinitions::default();
+        let profile = Profile::default();
+        let screen = CharacterSelect { selected: 1, ready: false };
+        let lines = character_text(&screen, &profile, &characters).lines().map(str::to_owned).collect::<Vec<_>>();
+        assert_eq!(lines[0], "choose a character");
+        assert_eq!(lines[1], "  soldier - steady all-rounder with the charger");
+        assert_eq!(lines[2], "> scout - fast and fragile, grabs pickups from further away, starts with the repeater - locked");
+        assert_eq!(lines.len(), 5);
+    }
+}
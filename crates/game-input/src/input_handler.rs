@@ -0,0 +1,50 @@
+use bevy::prelude::*;
+use game_data::*;
+use crate::*;
+
+
+pub fn create_bindings(
+    mut commands: Commands,
+    config: Res<GameConfig>,
+) {
+    commands.insert_resource(Bindings::for_players(config.coop.players));
+}
+
+pub fn read_player_input(
+    bindings: Res<Bindings>,
+    keyboard: Res<Input<KeyCode>>,
+    buttons: Res<Input<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    mut players: Query<(&PlayerId, &mut PlayerInput), Without<Bot>>,
+) {
+    for (id, mut input) in players.iter_mut() {
+        let devices = match bindings.0.get(id) {
+            Some(devices) => devices,
+            None => continue,
+        };
+        *input = devices.iter().fold(PlayerInput::default(), |combined, device| {
+            let read = match device {
+                InputBinding::Keyboard(layout) => layout.read(&keyboard),
+                InputBinding::Gamepad(gamepad) => read_gamepad(*gamepad, &buttons, &axes),
+            };
+            merge(combined, read)
+        });
+    }
+}
+
+/// drives the local players from the `InputScript`, remote players get theirs from the network
+pub fn play_input_script(
+    script: Res<InputScript>,
+    mut clock: ResMut<ScriptClock>,
+    config: Res<GameConfig>,
+    mut players: Query<(&PlayerId, &mut PlayerInput), Without<Bot>>,
+    time: Res<Time>,
+) {
+    clock.0 += time.delta();
+    let input = script.input_at(clock.0);
+    for (id, mut player_input) in players.iter_mut() {
+        if id.0 < config.coop.players {
+            *player_input = input;
+        }
+    }
+}
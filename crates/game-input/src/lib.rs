@@ -0,0 +1,225 @@
+use bevy::prelude::*;
+use bevy::input::InputSystem;
+use std::collections::HashMap;
+use game_data::*;
+pub use script::*;
+use input_handler::*;
+mod input_handler;
+mod script;
+
+
+/// Turns keyboard and gamepad state into a `PlayerInput` on every player, following each
+/// player's `Bindings`. Runs right after Bevy's input systems so gameplay sees this frame's input.
+pub struct PlayerInputPlugin;
+
+impl Plugin for PlayerInputPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_startup_system_to_stage(StartupStage::PreStartup, create_bindings.system())
+            .add_system_to_stage(CoreStage::PreUpdate, read_player_input.system().after(InputSystem));
+    }
+}
+
+/// Plays an `InputScript` on the local players instead of reading devices, for headless runs.
+pub struct InputScriptPlugin(pub InputScript);
+
+impl Plugin for InputScriptPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(self.0.clone())
+            .init_resource::<ScriptClock>()
+            .add_system_to_stage(CoreStage::PreUpdate, play_input_script.system());
+    }
+}
+
+/// one device a player can be controlled with
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputBinding {
+    Keyboard(KeyboardLayout),
+    Gamepad(Gamepad),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyboardLayout {
+    pub up: KeyCode,
+    pub down: KeyCode,
+    pub left: KeyCode,
+    pub right: KeyCode,
+    pub aim_up: KeyCode,
+    pub aim_down: KeyCode,
+    pub aim_left: KeyCode,
+    pub aim_right: KeyCode,
+    pub fire: KeyCode,
+    pub alt_fire: KeyCode,
+    pub reload: KeyCode,
+}
+
+/// devices per player, every bound device is read and combined
+pub struct Bindings(pub HashMap<PlayerId, Vec<InputBinding>>);
+
+impl KeyboardLayout {
+    /// single player: WASD to move, arrow keys to aim, space to fire, left shift for bombs, R to reload
+    pub fn full() -> Self {
+        KeyboardLayout {
+            up: KeyCode::W,
+            down: KeyCode::S,
+            left: KeyCode::A,
+            right: KeyCode::D,
+            aim_up: KeyCode::Up,
+            aim_down: KeyCode::Down,
+            aim_left: KeyCode::Left,
+            aim_right: KeyCode::Right,
+            fire: KeyCode::Space,
+            alt_fire: KeyCode::LShift,
+            reload: KeyCode::R,
+        }
+    }
+
+    /// first co-op player: WASD to move, TFGH to aim, space to fire, left shift for bombs, R to reload
+    pub fn left_half() -> Self {
+        KeyboardLayout {
+            aim_up: KeyCode::T,
+            aim_down: KeyCode::G,
+            aim_left: KeyCode::F,
+            aim_right: KeyCode::H,
+            ..KeyboardLayout::full()
+        }
+    }
+
+    /// second co-op player: arrow keys to move, numpad 8456 to aim, numpad 0 to fire, numpad enter
+    /// for bombs, numpad plus to reload
+    pub fn right_half() -> Self {
+        KeyboardLayout {
+            up: KeyCode::Up,
+            down: KeyCode::Down,
+            left: KeyCode::Left,
+            right: KeyCode::Right,
+            aim_up: KeyCode::Numpad8,
+            aim_down: KeyCode::Numpad5,
+            aim_left: KeyCode::Numpad4,
+            aim_right: KeyCode::Numpad6,
+            fire: KeyCode::Numpad0,
+            alt_fire: KeyCode::NumpadEnter,
+            reload: KeyCode::NumpadAdd,
+        }
+    }
+
+    pub fn read(&self, keyboard: &Input<KeyCode>) -> PlayerInput {
+        let axis = |positive: KeyCode, negative: KeyCode| {
+            if keyboard.pressed(positive) {
+                1.0
+            } else if keyboard.pressed(negative) {
+                -1.0
+            } else {
+                0.0
+            }
+        };
+        let aim = Vec2::new(axis(self.aim_right, self.aim_left), axis(self.aim_up, self.aim_down));
+        PlayerInput {
+            movement: Vec2::new(axis(self.right, self.left), axis(self.up, self.down)),
+            aim: Direction::from_axes(aim),
+            fire: keyboard.pressed(self.fire),
+            alt_fire: keyboard.pressed(self.alt_fire),
+            reload: keyboard.pressed(self.reload),
+        }
+    }
+}
+
+impl Bindings {
+    /// One player gets the whole keyboard, two split it in halves. Player n can always use gamepad n.
+    pub fn for_players(players: u8) -> Self {
+        let mut bindings = HashMap::new();
+        for id in 0..players {
+            let mut devices = vec![];
+            match (players, id) {
+                (1, _) => devices.push(InputBinding::Keyboard(KeyboardLayout::full())),
+                (_, 0) => devices.push(InputBinding::Keyboard(KeyboardLayout::left_half())),
+                (_, 1) => devices.push(InputBinding::Keyboard(KeyboardLayout::right_half())),
+                _ => (),
+            }
+            devices.push(InputBinding::Gamepad(Gamepad(id as usize)));
+            bindings.insert(PlayerId(id), devices);
+        }
+        Bindings(bindings)
+    }
+}
+
+/// reads a gamepad: left stick moves, right stick aims, right trigger or south button fires,
+/// left trigger or east button drops bombs, west button reloads
+pub fn read_gamepad(gamepad: Gamepad, buttons: &Input<GamepadButton>, axes: &Axis<GamepadAxis>) -> PlayerInput {
+    let axis = |axis_type| axes.get(GamepadAxis(gamepad, axis_type)).unwrap_or(0.0);
+    let aim = Vec2::new(axis(GamepadAxisType::RightStickX), axis(GamepadAxisType::RightStickY));
+    PlayerInput {
+        movement: Vec2::new(axis(GamepadAxisType::LeftStickX), axis(GamepadAxisType::LeftStickY)),
+        aim: Direction::from_axes(aim),
+        fire: buttons.pressed(GamepadButton(gamepad, GamepadButtonType::RightTrigger2))
+            || buttons.pressed(GamepadButton(gamepad, GamepadButtonType::South)),
+        alt_fire: buttons.pressed(GamepadButton(gamepad, GamepadButtonType::LeftTrigger2))
+            || buttons.pressed(GamepadButton(gamepad, GamepadButtonType::East)),
+        reload: buttons.pressed(GamepadButton(gamepad, GamepadButtonType::West)),
+    }
+}
+
+/// Combines two devices bound to the same player: movement adds up, the first aim wins
+/// and either can fire, drop bombs or reload.
+pub fn merge(a: PlayerInput, b: PlayerInput) -> PlayerInput {
+    PlayerInput {
+        movement: (a.movement + b.movement).clamp(Vec2::splat(-1.0), Vec2::ONE),
+        aim: a.aim.or(b.aim),
+        fire: a.fire || b.fire,
+        alt_fire: a.alt_fire || b.alt_fire,
+        reload: a.reload || b.reload,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn keyboard_halves_do_not_overlap() {
+        let mut keyboard = Input::<KeyCode>::default();
+        keyboard.press(KeyCode::W);
+        keyboard.press(KeyCode::F);
+        keyboard.press(KeyCode::Space);
+
+        let left = KeyboardLayout::left_half().read(&keyboard);
+        assert_eq!(left.movement, Vec2::new(0.0, 1.0));
+        assert_eq!(left.aim, Some(Direction::West));
+        assert!(left.fire);
+        assert_eq!(KeyboardLayout::right_half().read(&keyboard), PlayerInput::default());
+    }
+
+    #[test]
+    fn two_players_get_their_own_devices() {
+        let bindings = Bindings::for_players(2);
+        assert_eq!(bindings.0[&PlayerId(0)], vec![
+            InputBinding::Keyboard(KeyboardLayout::left_half()),
+            InputBinding::Gamepad(Gamepad(0)),
+        ]);
+        assert_eq!(bindings.0[&PlayerId(1)], vec![
+            InputBinding::Keyboard(KeyboardLayout::right_half()),
+            InputBinding::Gamepad(Gamepad(1)),
+        ]);
+        assert_eq!(Bindings::for_players(1).0[&PlayerId(0)][0], InputBinding::Keyboard(KeyboardLayout::full()));
+    }
+
+    #[test]
+    fn scripts_play_their_steps_in_order() {
+        let script = InputScript::parse("(
+            looping: true,
+            steps: [
+                (seconds: 1.0, movement: (1.0, 0.0)),
+                (seconds: 0.5, aim: Some(North), fire: true),
+            ],
+        )").unwrap();
+        let at = |seconds: f32| script.input_at(Duration::from_secs_f32(seconds));
+        assert_eq!(at(0.5).movement, Vec2::new(1.0, 0.0));
+        assert_eq!(at(1.2), PlayerInput { movement: Vec2::ZERO, aim: Some(Direction::North), fire: true, alt_fire: false, reload: false });
+        assert_eq!(at(2.0).movement, Vec2::new(1.0, 0.0));
+
+        let once = InputScript { looping: false, ..script };
+        assert_eq!(once.input_at(Duration::from_secs(2)), PlayerInput::default());
+        assert!(InputScript::parse("(steps: [])").is_err());
+    }
+}
@@ -0,0 +1,87 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use game_data::*;
+
+
+/// One step of an `InputScript`: hold this input for `seconds`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ScriptStep {
+    pub seconds: f32,
+    #[serde(default)]
+    pub movement: (f32, f32),
+    #[serde(default)]
+    pub aim: Option<Direction>,
+    #[serde(default)]
+    pub fire: bool,
+    #[serde(default)]
+    pub alt_fire: bool,
+    #[serde(default)]
+    pub reload: bool,
+}
+
+/// Timed inputs for the local players when nobody is at the keyboard, e.g. on a headless server
+/// or in a soak test. Every local player follows the same script.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct InputScript {
+    pub steps: Vec<ScriptStep>,
+    /// start over after the last step instead of standing still
+    #[serde(default)]
+    pub looping: bool,
+}
+
+/// how far into its `InputScript` the run is
+#[derive(Default)]
+pub struct ScriptClock(pub Duration);
+
+impl InputScript {
+    pub fn load(path: impl AsRef<Path>) -> Result<InputScript, ConfigError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_owned(), e))?;
+        InputScript::parse(&text).map_err(|e| match e {
+            ConfigError::Parse(_, e) => ConfigError::Parse(path.to_owned(), e),
+            e => e,
+        })
+    }
+
+    pub fn parse(text: &str) -> Result<InputScript, ConfigError> {
+        let script: InputScript = ron::from_str(text).map_err(|e| ConfigError::Parse(PathBuf::new(), e))?;
+        let mut problems = vec![];
+        if script.steps.is_empty() {
+            problems.push("steps must contain at least one step".to_owned());
+        }
+        for (i, step) in script.steps.iter().enumerate() {
+            if !(step.seconds > 0.0) {
+                problems.push(format!("steps[{}].seconds must be greater than 0, got {}", i, step.seconds));
+            }
+        }
+        if problems.is_empty() {
+            Ok(script)
+        } else {
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+
+    /// The input `elapsed` into the script. Finished scripts without `looping` stand still.
+    pub fn input_at(&self, elapsed: Duration) -> PlayerInput {
+        let length: f32 = self.steps.iter().map(|step| step.seconds).sum();
+        let mut t = elapsed.as_secs_f32();
+        if self.looping && length > 0.0 {
+            t %= length;
+        }
+        for step in self.steps.iter() {
+            if t < step.seconds {
+                return PlayerInput {
+                    movement: Vec2::new(step.movement.0, step.movement.1).clamp(Vec2::splat(-1.0), Vec2::ONE),
+                    aim: step.aim,
+                    fire: step.fire,
+                    alt_fire: step.alt_fire,
+                    reload: step.reload,
+                };
+            }
+            t -= step.seconds;
+        }
+        PlayerInput::default()
+    }
+}
@@ -0,0 +1,350 @@
+use bevy::prelude::*;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use navigation_handler::*;
+mod navigation_handler;
+
+
+/// Walkability grid built from static colliders and a flow field towards the living players,
+/// recomputed whenever one of them changes cell. Enemy movement samples it through `Navigation`
+/// so enemies walk around walls instead of into them. Needs `SpatialPlugin`.
+pub struct NavigationPlugin;
+
+impl Plugin for NavigationPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(Navigation::default())
+            .add_system_to_stage(CoreStage::PreUpdate, update_navigation.system());
+    }
+}
+
+/// pixels, a bit wider than an enemy
+pub const NAV_CELL_SIZE: f32 = 32.0;
+/// cost of a straight step; diagonal steps cost `DIAGONAL_COST`
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+
+/// column and row, counted from the bottom left of the grid
+pub type Cell = (i32, i32);
+
+/// Which cells of the arena can be walked through.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NavGrid {
+    cell_size: f32,
+    /// world position of the bottom left corner
+    origin: Vec2,
+    width: i32,
+    height: i32,
+    blocked: Vec<bool>,
+}
+
+impl Default for NavGrid {
+    fn default() -> Self {
+        NavGrid::new(Vec2::ZERO, NAV_CELL_SIZE)
+    }
+}
+
+impl NavGrid {
+    /// An open grid covering ±`half_size` around the origin.
+    pub fn new(half_size: Vec2, cell_size: f32) -> Self {
+        assert!(cell_size > 0.0, "cells need a size");
+        let width = (half_size.x * 2.0 / cell_size).ceil().max(0.0) as i32;
+        let height = (half_size.y * 2.0 / cell_size).ceil().max(0.0) as i32;
+        NavGrid {
+            cell_size,
+            origin: -Vec2::new(width as f32, height as f32) * cell_size / 2.0,
+            width,
+            height,
+            blocked: vec![false; (width * height) as usize],
+        }
+    }
+
+    /// A grid centred on the origin from rows of `.` (open) and `#` (blocked), top row first.
+    pub fn from_map(map: &str, cell_size: f32) -> Self {
+        let rows = map.lines().map(str::trim).filter(|row| !row.is_empty()).collect::<Vec<_>>();
+        let width = rows.iter().map(|row| row.len()).max().unwrap_or(0) as f32;
+        let mut grid = NavGrid::new(Vec2::new(width, rows.len() as f32) * cell_size / 2.0, cell_size);
+        for (row, line) in rows.iter().enumerate() {
+            let y = grid.height - 1 - row as i32;
+            for (x, tile) in line.chars().enumerate() {
+                if tile == '#' {
+                    let index = grid.index((x as i32, y));
+                    grid.blocked[index] = true;
+                }
+            }
+        }
+        grid
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    fn index(&self, cell: Cell) -> usize {
+        (cell.1 * self.width + cell.0) as usize
+    }
+
+    fn contains(&self, cell: Cell) -> bool {
+        cell.0 >= 0 && cell.1 >= 0 && cell.0 < self.width && cell.1 < self.height
+    }
+
+    /// cell under `position`, `None` outside the grid
+    pub fn cell_at(&self, position: Vec2) -> Option<Cell> {
+        let local = (position - self.origin) / self.cell_size;
+        let cell = (local.x.floor() as i32, local.y.floor() as i32);
+        if self.contains(cell) { Some(cell) } else { None }
+    }
+
+    pub fn center(&self, cell: Cell) -> Vec2 {
+        self.origin + (Vec2::new(cell.0 as f32, cell.1 as f32) + Vec2::splat(0.5)) * self.cell_size
+    }
+
+    pub fn is_walkable(&self, cell: Cell) -> bool {
+        self.contains(cell) && !self.blocked[self.index(cell)]
+    }
+
+    pub fn blocked_count(&self) -> usize {
+        self.blocked.iter().filter(|blocked| **blocked).count()
+    }
+
+    /// Blocks every cell the rectangle from `min` to `max` touches.
+    pub fn block_rect(&mut self, min: Vec2, max: Vec2) {
+        let low = ((min - self.origin) / self.cell_size).floor();
+        let high = ((max - self.origin) / self.cell_size).ceil();
+        for x in (low.x as i32).max(0)..(high.x as i32).min(self.width) {
+            for y in (low.y as i32).max(0)..(high.y as i32).min(self.height) {
+                let index = self.index((x, y));
+                self.blocked[index] = true;
+            }
+        }
+    }
+
+    /// Walkable cells one step away and what the step costs. Diagonal steps need both cells
+    /// beside them open, so paths never cut a wall's corner.
+    fn steps(&self, cell: Cell) -> impl Iterator<Item = (Cell, u32)> + '_ {
+        const OFFSETS: [(i32, i32); 8] = [(1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (1, -1), (-1, 1), (-1, -1)];
+        OFFSETS.iter().filter_map(move |(dx, dy)| {
+            let next = (cell.0 + dx, cell.1 + dy);
+            if !self.is_walkable(next) {
+                return None;
+            }
+            if *dx != 0 && *dy != 0 {
+                if !self.is_walkable((cell.0 + dx, cell.1)) || !self.is_walkable((cell.0, cell.1 + dy)) {
+                    return None;
+                }
+                Some((next, DIAGONAL_COST))
+            } else {
+                Some((next, STRAIGHT_COST))
+            }
+        })
+    }
+}
+
+/// Cost of the cheapest walk from every cell to the nearest goal. Following the cheapest
+/// neighbour from anywhere leads to a goal along a shortest path.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlowField {
+    width: i32,
+    costs: Vec<Option<u32>>,
+}
+
+impl FlowField {
+    pub fn towards(grid: &NavGrid, goals: &[Cell]) -> Self {
+        let mut costs = vec![None; grid.blocked.len()];
+        let mut open = BinaryHeap::new();
+        for goal in goals.iter().copied().filter(|goal| grid.is_walkable(*goal)) {
+            costs[grid.index(goal)] = Some(0);
+            open.push(Reverse((0, goal)));
+        }
+        while let Some(Reverse((cost, cell))) = open.pop() {
+            if costs[grid.index(cell)].map_or(false, |best| best < cost) {
+                continue;
+            }
+            for (next, step) in grid.steps(cell) {
+                let next_cost = cost + step;
+                let best = &mut costs[grid.index(next)];
+                if best.map_or(true, |best| next_cost < best) {
+                    *best = Some(next_cost);
+                    open.push(Reverse((next_cost, next)));
+                }
+            }
+        }
+        FlowField { width: grid.width, costs }
+    }
+
+    /// `None` for blocked cells and ones no goal can be reached from
+    pub fn cost(&self, cell: Cell) -> Option<u32> {
+        if cell.0 < 0 || cell.1 < 0 || cell.0 >= self.width {
+            return None;
+        }
+        self.costs.get((cell.1 * self.width + cell.0) as usize).copied().flatten()
+    }
+
+    /// the neighbour to walk to from `cell`, `None` on a goal or where no goal can be reached
+    pub fn next_cell(&self, grid: &NavGrid, cell: Cell) -> Option<Cell> {
+        let cost = self.cost(cell)?;
+        grid.steps(cell)
+            .filter_map(|(next, _)| self.cost(next).map(|next_cost| (next_cost, next)))
+            .filter(|(next_cost, _)| *next_cost < cost)
+            .min()
+            .map(|(_, next)| next)
+    }
+}
+
+/// The arena's `NavGrid` and the `FlowField` towards the players standing in `goals`.
+#[derive(Default)]
+pub struct Navigation {
+    pub grid: NavGrid,
+    pub field: Option<FlowField>,
+    pub goals: Vec<Cell>,
+}
+
+impl Navigation {
+    /// Unit vector along the flow field at `position`. `None` where it has no advice: outside the
+    /// grid, in a goal's cell or where walls cut the position off, so callers head straight at
+    /// their target instead.
+    pub fn direction(&self, position: Vec2) -> Option<Vec2> {
+        let field = self.field.as_ref()?;
+        let cell = self.grid.cell_at(position)?;
+        let next = field.next_cell(&self.grid, cell)?;
+        Some((self.grid.center(next) - position).normalize_or_zero())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_rapier2d::physics::{RapierConfiguration, RapierPhysicsPlugin};
+    use bevy_rapier2d::rapier::dynamics::RigidBodyBuilder;
+    use bevy_rapier2d::rapier::geometry::ColliderBuilder;
+    use game_data::*;
+    use game_spatial::*;
+
+    /// follows the field from `start` and returns every cell on the way
+    fn walk(grid: &NavGrid, field: &FlowField, start: Cell) -> Vec<Cell> {
+        let mut path = vec![start];
+        while let Some(next) = field.next_cell(grid, *path.last().unwrap()) {
+            assert!(grid.is_walkable(next));
+            path.push(next);
+            assert!(path.len() < 100, "the field loops");
+        }
+        path
+    }
+
+    #[test]
+    fn maps_are_read_top_row_first() {
+        let grid = NavGrid::from_map("
+            #..
+            ...
+        ", 10.0);
+        assert_eq!((grid.width(), grid.height()), (3, 2));
+        assert!(!grid.is_walkable((0, 1)));
+        assert!(grid.is_walkable((0, 0)));
+        assert!(!grid.is_walkable((3, 0)));
+        assert_eq!(grid.cell_at(Vec2::new(-14.0, 9.0)), Some((0, 1)));
+        assert_eq!(grid.center((0, 1)), Vec2::new(-10.0, 5.0));
+        assert_eq!(grid.cell_at(Vec2::new(20.0, 0.0)), None);
+    }
+
+    #[test]
+    fn flow_leads_around_walls() {
+        let grid = NavGrid::from_map("
+            .......
+            .#####.
+            .#...#.
+            .#.#.#.
+            ...#...
+        ", 10.0);
+        let goal = (2, 2);
+        let field = FlowField::towards(&grid, &[goal]);
+
+        // the pocket only opens at the bottom, so this has to go down the right side and back up
+        let path = walk(&grid, &field, (6, 4));
+        assert_eq!(path.last(), Some(&goal));
+        assert!(path.contains(&(4, 1)));
+        assert_eq!(field.cost((6, 4)), Some(10 * STRAIGHT_COST));
+        assert_eq!(field.cost((1, 3)), None);
+        assert_eq!(field.next_cell(&grid, goal), None);
+    }
+
+    #[test]
+    fn diagonal_steps_do_not_cut_corners() {
+        let grid = NavGrid::from_map("
+            .#
+            ..
+        ", 10.0);
+        let field = FlowField::towards(&grid, &[(1, 0)]);
+        assert_eq!(walk(&grid, &field, (0, 1)), vec![(0, 1), (0, 0), (1, 0)]);
+    }
+
+    #[test]
+    fn walled_off_cells_and_missing_fields_give_no_direction() {
+        let grid = NavGrid::from_map("
+            ..#.
+            ..#.
+        ", 10.0);
+        let mut navigation = Navigation { grid, field: None, goals: vec![(0, 0)] };
+        assert_eq!(navigation.direction(Vec2::new(15.0, 0.0)), None);
+
+        navigation.field = Some(FlowField::towards(&navigation.grid, &navigation.goals));
+        assert_eq!(navigation.direction(Vec2::new(15.0, 0.0)), None);
+        let direction = navigation.direction(Vec2::new(-5.0, 5.0)).unwrap();
+        assert!(direction.x < 0.0 && direction.y < 0.0);
+        assert_eq!(navigation.direction(Vec2::new(-15.0, -5.0)), None);
+    }
+
+    #[test]
+    fn colliders_block_every_cell_they_touch() {
+        let mut grid = NavGrid::new(Vec2::new(50.0, 50.0), 10.0);
+        grid.block_rect(Vec2::new(-5.0, -5.0), Vec2::new(12.0, 5.0));
+        assert_eq!(grid.blocked_count(), 6);
+        assert!(!grid.is_walkable(grid.cell_at(Vec2::new(11.0, 0.0)).unwrap()));
+        assert!(grid.is_walkable(grid.cell_at(Vec2::new(-11.0, 0.0)).unwrap()));
+    }
+
+    #[test]
+    fn static_colliders_are_rasterised_and_walked_around() {
+        let mut config = GameConfig::default();
+        // 10 by 10 cells
+        config.enemies.arena_half_size = (5.0 * NAV_CELL_SIZE, 5.0 * NAV_CELL_SIZE);
+        let mut app_builder = App::build();
+        app_builder
+            .add_plugins(MinimalPlugins)
+            .add_plugin(bevy::transform::TransformPlugin)
+            .add_plugin(RapierPhysicsPlugin)
+            .add_plugin(SpatialPlugin)
+            .add_plugin(NavigationPlugin)
+            .insert_resource(config);
+        let mut app = app_builder.app;
+        let scale = app.world.get_resource::<RapierConfiguration>().unwrap().scale;
+
+        // a wall 120 by 20 pixels across the middle, with the player above it
+        app.world.spawn()
+            .insert(RigidBodyBuilder::new_static().translation(0.0, 0.0))
+            .insert(ColliderBuilder::cuboid(60.0 / scale, 10.0 / scale));
+        app.world.spawn()
+            .insert(Transform::from_xyz(0.0, 100.0, 0.0))
+            .insert(Player::default());
+        for _ in 0..3 {
+            app.update();
+        }
+
+        let navigation = app.world.get_resource::<Navigation>().unwrap();
+        let grid = &navigation.grid;
+        assert_eq!(grid.blocked_count(), 8);
+        for x in 3..7 {
+            assert!(!grid.is_walkable((x, 4)) && !grid.is_walkable((x, 5)));
+        }
+        assert!(grid.is_walkable((2, 4)) && grid.is_walkable((7, 5)));
+
+        // from straight below the wall the path has to go round one of its ends
+        let field = navigation.field.as_ref().expect("the player is a goal");
+        let path = walk(grid, field, grid.cell_at(Vec2::new(0.0, -100.0)).unwrap());
+        assert_eq!(path.last(), grid.cell_at(Vec2::new(0.0, 100.0)).as_ref());
+        assert!(path.iter().any(|&(x, y)| (y == 4 || y == 5) && (x < 3 || x > 6)));
+    }
+}
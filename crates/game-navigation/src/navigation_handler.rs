@@ -0,0 +1,54 @@
+use bevy::prelude::*;
+use bevy_rapier2d::physics::{ColliderHandleComponent, RapierConfiguration};
+use bevy_rapier2d::rapier::dynamics::RigidBodySet;
+use bevy_rapier2d::rapier::geometry::ColliderSet;
+use game_data::*;
+use game_spatial::*;
+use crate::*;
+
+
+/// Rebuilds the grid when colliders come or go or the arena is resized, and the flow field when
+/// the grid changed or a living player moved to another cell.
+pub fn update_navigation(
+    mut navigation: ResMut<Navigation>,
+    players: Res<SpatialIndex<Player>>,
+    config: Res<GameConfig>,
+    rapier_config: Res<RapierConfiguration>,
+    bodies: Res<RigidBodySet>,
+    colliders: Res<ColliderSet>,
+    added: Query<(), Added<ColliderHandleComponent>>,
+    removed: RemovedComponents<ColliderHandleComponent>,
+) {
+    let half_size = Vec2::new(config.enemies.arena_half_size.0, config.enemies.arena_half_size.1);
+    let rebuild = config.is_changed() || added.iter().next().is_some() || removed.iter().next().is_some();
+    if rebuild {
+        let units = Units::new(&rapier_config);
+        let mut grid = NavGrid::new(half_size, NAV_CELL_SIZE);
+        for (_, collider) in colliders.iter() {
+            let is_static = bodies.get(collider.parent()).map_or(false, |body| body.is_static());
+            if !is_static || collider.is_sensor() {
+                continue;
+            }
+            let aabb = collider.compute_aabb();
+            let min = units.to_world(PhysicsPos(Vec2::new(aabb.mins.x, aabb.mins.y))).0;
+            let max = units.to_world(PhysicsPos(Vec2::new(aabb.maxs.x, aabb.maxs.y))).0;
+            grid.block_rect(min, max);
+        }
+        if grid != navigation.grid {
+            debug!("navigation grid rebuilt: {} of {} cells blocked", grid.blocked_count(), grid.width() * grid.height());
+            navigation.grid = grid;
+            navigation.field = None;
+        }
+    }
+
+    let mut goals = players
+        .within_rect(-half_size, half_size)
+        .filter_map(|entry| navigation.grid.cell_at(entry.position))
+        .collect::<Vec<_>>();
+    goals.sort_unstable();
+    goals.dedup();
+    if navigation.field.is_none() || goals != navigation.goals {
+        navigation.field = if goals.is_empty() { None } else { Some(FlowField::towards(&navigation.grid, &goals)) };
+        navigation.goals = goals;
+    }
+}
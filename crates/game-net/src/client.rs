@@ -0,0 +1,175 @@
+use bevy::prelude::*;
+use bevy::app::AppExit;
+use std::collections::HashMap;
+use game_animation::*;
+use game_data::*;
+use game_data::Direction as Direction;
+use game_input::*;
+use crate::*;
+
+
+/// Client side state next to the `ClientSession`: entities mirrored from the server by
+/// `NetId`, and prediction for our own player once the server has placed it.
+pub struct NetClient {
+    pub session: ClientSession,
+    pub prediction: Option<Prediction>,
+    mirrored: HashMap<NetId, Entity>,
+    connect_timer: Timer,
+}
+
+impl NetClient {
+    pub fn new(session: ClientSession) -> Self {
+        // already due, so the first request goes out on the first frame
+        let mut connect_timer = Timer::new(CONNECT_RETRY, true);
+        connect_timer.set_elapsed(CONNECT_RETRY);
+        NetClient {
+            session,
+            prediction: None,
+            mirrored: HashMap::new(),
+            connect_timer,
+        }
+    }
+}
+
+/// Asks to join until the server answers.
+pub fn connect_to_server(mut client: ResMut<NetClient>, time: Res<Time>) {
+    if client.session.player().is_some() {
+        return;
+    }
+    client.connect_timer.tick(time.delta());
+    if client.connect_timer.finished() {
+        client.session.connect();
+    }
+}
+
+/// Applies server messages: binds the local devices to our player on welcome, mirrors
+/// every snapshot into entities and opens the upgrade choice the server is paused on.
+pub fn receive_snapshots(
+    mut commands: Commands,
+    mut client: ResMut<NetClient>,
+    mut mirrored: Query<(&mut Transform, &mut Direction, &mut Health)>,
+    mirrored_players: Query<(Entity, &PlayerId)>,
+    current_choice: Option<Res<UpgradeChoice>>,
+    mut exit: EventWriter<AppExit>,
+    sheets: Res<SpriteSheets>,
+    game_materials: Res<GameMaterials>,
+    config: Res<GameConfig>,
+) {
+    let client = &mut *client;
+    for event in client.session.poll() {
+        match event {
+            ClientEvent::Welcome { player } => {
+                info!("joined as player {}", player);
+                let mut bindings = HashMap::new();
+                bindings.insert(PlayerId(player), vec![
+                    InputBinding::Keyboard(KeyboardLayout::full()),
+                    InputBinding::Gamepad(Gamepad(0)),
+                ]);
+                commands.insert_resource(Bindings(bindings));
+            },
+            ClientEvent::Rejected { reason } => {
+                error!("server refused to let us join: {}", reason);
+                exit.send(AppExit);
+            },
+            ClientEvent::Snapshot { score, wave, last_input, choice } => {
+                commands.insert_resource(Score(score));
+                commands.insert_resource(Wave(wave));
+                // players spawned by this snapshot are picked up by the next one
+                let choice = choice.and_then(|offer| {
+                    let (player, _) = mirrored_players.iter().find(|(_, id)| id.0 == offer.player)?;
+                    Some(UpgradeChoice { player, options: offer.options, ready: false })
+                });
+                if choice.as_ref() != current_choice.as_deref() {
+                    match choice {
+                        Some(choice) => commands.insert_resource(choice),
+                        None => commands.remove_resource::<UpgradeChoice>(),
+                    }
+                }
+                let state = match client.session.state() {
+                    Some(state) => state,
+                    None => continue,
+                };
+                let own = client.session.player().map(EntityKind::Player);
+
+                let gone = client.mirrored
+                    .keys()
+                    .filter(|id| !state.entities.contains_key(id))
+                    .copied()
+                    .collect::<Vec<_>>();
+                for id in gone {
+                    if let Some(entity) = client.mirrored.remove(&id) {
+                        commands.entity(entity).despawn();
+                    }
+                }
+
+                for (id, entity_state) in state.entities.iter() {
+                    let mut position = entity_state.position();
+                    if Some(entity_state.kind) == own {
+                        let prediction = client.prediction
+                            .get_or_insert_with(|| Prediction::new(Player::from_config(&config.player), position));
+                        prediction.reconcile(position, entity_state.velocity(), last_input, &config.player);
+                        position = prediction.position;
+                    }
+                    let direction = entity_state.direction.unwrap_or(Direction::East);
+                    match client.mirrored.get(id).and_then(|entity| mirrored.get_mut(*entity).ok()) {
+                        Some((mut transform, mut mirrored_direction, mut health)) => {
+                            transform.translation = position.extend(transform.translation.z);
+                            *mirrored_direction = direction;
+                            health.0 = entity_state.health;
+                        },
+                        None => {
+                            let (sheet, size, material) = match entity_state.kind {
+                                EntityKind::Player(_) => ("player", PLAYER_SIZE, game_materials.player.clone()),
+                                EntityKind::Enemy => ("enemy", ENEMY_SIZE, game_materials.enemy.clone()),
+                                EntityKind::Bullet => ("bullet", BULLET_SIZE, game_materials.bullet.clone()),
+                                EntityKind::Bomb => ("bomb", BOMB_SIZE, game_materials.bomb.clone()),
+                                EntityKind::AmmoPickup => ("pickup", PICKUP_SIZE, game_materials.pickup.clone()),
+                                EntityKind::XpOrb => ("xp", XP_ORB_SIZE, game_materials.xp.clone()),
+                            };
+                            let mut entity_commands = spawn_sprite(&mut commands, &sheets, sheet, position.extend(0.0), Vec2::splat(size), material);
+                            entity_commands
+                                .insert(*id)
+                                .insert(direction)
+                                .insert(Health(entity_state.health));
+                            if let EntityKind::Player(player) = entity_state.kind {
+                                entity_commands.insert(PlayerId(player)).insert(PlayerInput::default());
+                            }
+                            client.mirrored.insert(*id, entity_commands.id());
+                        },
+                    }
+                }
+            },
+        }
+    }
+}
+
+/// Sends this frame's input for our player and moves it right away instead of waiting for the server.
+pub fn send_input(
+    mut client: ResMut<NetClient>,
+    mut players: Query<(&PlayerId, &PlayerInput, &mut Transform)>,
+    config: Res<GameConfig>,
+    time: Res<Time>,
+) {
+    let client = &mut *client;
+    let own = match client.session.player() {
+        Some(player) => PlayerId(player),
+        None => return,
+    };
+    for (id, input, mut transform) in players.iter_mut() {
+        if *id != own {
+            continue;
+        }
+        let command = client.session.send_input(input, time.delta_seconds());
+        if let Some(prediction) = client.prediction.as_mut() {
+            prediction.predict(command, &config.player);
+            transform.translation = prediction.position.extend(transform.translation.z);
+        }
+    }
+}
+
+/// Tells the server we are leaving instead of letting it time us out.
+pub fn disconnect_on_exit(mut client: ResMut<NetClient>, mut exits: EventReader<AppExit>) {
+    if exits.iter().next().is_some() {
+        client.session.disconnect();
+    }
+}
@@ -0,0 +1,277 @@
+use bevy::prelude::*;
+use std::net::SocketAddr;
+use std::time::Duration;
+use game_data::*;
+pub use prediction::*;
+pub use protocol::*;
+pub use session::*;
+pub use transport::*;
+use client::*;
+use server::*;
+mod client;
+mod prediction;
+mod protocol;
+mod server;
+mod session;
+mod transport;
+
+
+/// Client/server multiplayer over UDP. The server runs the whole game and sends deltas of the
+/// world to every client; clients only send their inputs, draw what the server tells them and
+/// predict their own player so movement doesn't wait a round trip.
+pub struct NetPlugin(pub NetMode);
+
+/// snapshots per second sent by the server
+pub const SNAPSHOT_RATE: f32 = 20.0;
+/// how often a client repeats its join request
+pub const CONNECT_RETRY: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NetMode {
+    Offline,
+    /// simulate the game and accept clients on this address
+    Server(SocketAddr),
+    /// join the server at this address
+    Client(SocketAddr),
+}
+
+impl NetMode {
+    /// `--server <addr>` or `--client <addr>`, offline without either
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<NetMode, String> {
+        let mut args = args.into_iter();
+        let mut mode = NetMode::Offline;
+        while let Some(arg) = args.next() {
+            let variant: fn(SocketAddr) -> NetMode = match arg.as_str() {
+                "--server" => NetMode::Server,
+                "--client" => NetMode::Client,
+                _ => continue,
+            };
+            let addr = args.next().ok_or_else(|| format!("{} needs an address like 127.0.0.1:7777", arg))?;
+            let addr = addr.parse().map_err(|e| format!("{} {}: {}", arg, addr, e))?;
+            mode = variant(addr);
+        }
+        Ok(mode)
+    }
+
+    /// whether this process runs the gameplay systems, clients only mirror the server
+    pub fn simulates(&self) -> bool {
+        !matches!(self, NetMode::Client(_))
+    }
+}
+
+impl Plugin for NetPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        match self.0 {
+            NetMode::Offline => (),
+            NetMode::Server(addr) => {
+                let transport = bind_or_exit(addr);
+                info!("serving on {}", transport.local_addr());
+                // the server's own players come first
+                let local_players = app.world().get_resource::<GameConfig>().map_or(1, |config| config.coop.players);
+                app.insert_resource(ServerSession::new(Box::new(transport), local_players))
+                    .add_system_to_stage(CoreStage::PreUpdate, receive_clients.system())
+                    .add_system_to_stage(CoreStage::PostUpdate, send_snapshots.system());
+            },
+            NetMode::Client(server) => {
+                // any free port, the server answers wherever we send from
+                let transport = bind_or_exit(SocketAddr::from(([0, 0, 0, 0], 0)));
+                app.insert_resource(NetClient::new(ClientSession::new(Box::new(transport), server)))
+                    .insert_resource(Score::default())
+                    .insert_resource(Wave::default())
+                    .add_system_to_stage(CoreStage::PreUpdate, connect_to_server.system())
+                    .add_system_to_stage(CoreStage::PreUpdate, receive_snapshots.system())
+                    .add_system(send_input.system())
+                    .add_system_to_stage(CoreStage::Last, disconnect_on_exit.system());
+            },
+        }
+    }
+}
+
+fn bind_or_exit(addr: SocketAddr) -> UdpTransport {
+    match UdpTransport::bind(addr) {
+        Ok(transport) => transport,
+        Err(e) => {
+            eprintln!("could not open a UDP socket on {}: {}", addr, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use game_data::Direction as Direction;
+
+    const FRAME: Duration = Duration::from_millis(16);
+
+    fn state(tick: u32, entities: &[(u64, f32)]) -> WorldState {
+        let entities = entities
+            .iter()
+            .map(|(id, x)| (NetId(*id), EntityState::new(EntityKind::Enemy, Vec2::new(*x, 0.0), Vec2::ZERO, None, 1, false)))
+            .collect::<BTreeMap<_, _>>();
+        WorldState { tick, entities }
+    }
+
+    #[test]
+    fn deltas_rebuild_the_full_state() {
+        let base = state(1, &[(1, 0.0), (2, 10.0), (3, 20.0)]);
+        let next = state(2, &[(1, 0.0), (3, 25.0), (4, 30.0)]);
+        let delta = next.delta_from(Some(&base));
+        assert_eq!(delta.changed.iter().map(|(id, _)| id.0).collect::<Vec<_>>(), vec![3, 4]);
+        assert_eq!(delta.removed, vec![NetId(2)]);
+
+        let offer = UpgradeOffer { player: 1, options: vec![4, 0, 2] };
+        let packet = encode(&ServerMessage::Snapshot { delta, score: 0, wave: 1, last_input: None, choice: Some(offer.clone()) });
+        let delta = match decode::<ServerMessage>(&packet) {
+            Some(ServerMessage::Snapshot { delta, choice, .. }) => {
+                assert_eq!(choice, Some(offer));
+                delta
+            },
+            other => panic!("unexpected {:?}", other),
+        };
+        assert_eq!(WorldState::apply(Some(&base), &delta), next);
+        assert_eq!(WorldState::apply(None, &next.delta_from(None)), next);
+    }
+
+    /// Runs a server that moves one remote player by its commands against a client over
+    /// `conditions`: `frames` frames of zigzagging, then standing still until the player stops
+    /// and everything in flight settles. Returns both positions and the commands the server applied.
+    fn play(conditions: LinkConditions, frames: u32) -> (Vec2, Vec2, Vec<u32>) {
+        let config = PlayerConfig::default();
+        let network = SimulatedNetwork::new(conditions, 7);
+        let mut server = ServerSession::new(Box::new(network.endpoint()), 1);
+        let server_addr = server.local_addr();
+        let mut client = ClientSession::new(Box::new(network.endpoint()), server_addr);
+
+        let mut server_player = Player::from_config(&config);
+        let mut server_position = Vec2::ZERO;
+        let mut prediction: Option<Prediction> = None;
+        let mut applied = vec![];
+
+        for frame in 0..frames + 180 {
+            if client.player().is_none() && frame % 6 == 0 {
+                client.connect();
+            }
+            server.poll(network.now());
+            for command in server.take_commands(1) {
+                applied.push(command.sequence);
+                simulate(&mut server_player, &mut server_position, &command, &config);
+            }
+            if frame % 3 == 0 {
+                let mut world = WorldState::default();
+                let player = EntityState::new(EntityKind::Player(1), server_position, server_player.velocity, Some(Direction::East), 3, false);
+                world.entities.insert(NetId(1), player);
+                server.broadcast(world, 0, 1, None);
+            }
+
+            for event in client.poll() {
+                if let ClientEvent::Snapshot { last_input, .. } = event {
+                    let own = client.state().unwrap().entities[&NetId(1)];
+                    prediction
+                        .get_or_insert_with(|| Prediction::new(Player::from_config(&config), own.position()))
+                        .reconcile(own.position(), own.velocity(), last_input, &config);
+                }
+            }
+            if client.player().is_some() {
+                let movement = if frame < frames {
+                    Vec2::new(1.0, if frame % 40 < 20 { 1.0 } else { -1.0 })
+                } else {
+                    Vec2::ZERO
+                };
+                let input = PlayerInput { movement, ..Default::default() };
+                let command = client.send_input(&input, FRAME.as_secs_f32());
+                if let Some(prediction) = prediction.as_mut() {
+                    prediction.predict(command, &config);
+                }
+            }
+            network.advance(FRAME);
+        }
+        (server_position, prediction.expect("client never got a snapshot").position, applied)
+    }
+
+    #[test]
+    fn lossy_links_apply_every_input_once_and_in_order() {
+        let conditions = LinkConditions {
+            loss: 0.2,
+            latency: Duration::from_millis(50),
+            jitter: Duration::from_millis(30),
+        };
+        let (server_position, client_position, applied) = play(conditions, 240);
+        assert_eq!(applied, (1..=applied.len() as u32).collect::<Vec<_>>());
+        assert!(server_position.x > 100.0);
+        assert!(server_position.distance(client_position) < 1.0, "{} vs {}", server_position, client_position);
+    }
+
+    #[test]
+    fn prediction_matches_the_server_on_a_perfect_link() {
+        let (server_position, client_position, applied) = play(LinkConditions::perfect(), 120);
+        assert_eq!(applied, (1..=applied.len() as u32).collect::<Vec<_>>());
+        assert!(server_position.distance(client_position) < 1.0);
+    }
+
+    #[test]
+    fn commands_between_server_frames_are_merged_and_capped() {
+        let tap = PlayerInput { fire: true, ..Default::default() };
+        let walk = PlayerInput { movement: Vec2::X, ..Default::default() };
+        let merged = InputCommand::merge(&[InputCommand::new(1, &tap, 0.01), InputCommand::new(2, &walk, 0.01)]).unwrap();
+        assert!(merged.fire);
+        assert_eq!(merged.movement, Vec2::X);
+        assert_eq!(InputCommand::merge(&[]), None);
+
+        let network = SimulatedNetwork::new(LinkConditions::perfect(), 3);
+        let mut server = ServerSession::new(Box::new(network.endpoint()), 1);
+        let mut client = ClientSession::new(Box::new(network.endpoint()), server.local_addr());
+        client.connect();
+        server.poll(network.now());
+        client.poll();
+        // a client far ahead of the server only keeps its newest commands waiting
+        for _ in 0..MAX_PENDING_INPUTS * 2 {
+            client.send_input(&walk, 0.01);
+            server.poll(network.now());
+        }
+        let taken = server.take_commands(1);
+        assert_eq!(taken.len(), MAX_PENDING_INPUTS);
+        assert_eq!(taken.last().unwrap().sequence, MAX_PENDING_INPUTS as u32 * 2);
+        assert!(server.take_commands(1).is_empty());
+    }
+
+    #[test]
+    fn full_servers_and_old_clients_are_refused() {
+        let network = SimulatedNetwork::new(LinkConditions::perfect(), 1);
+        let mut server = ServerSession::new(Box::new(network.endpoint()), MAX_PLAYERS - 1);
+        let mut first = ClientSession::new(Box::new(network.endpoint()), server.local_addr());
+        let mut second = ClientSession::new(Box::new(network.endpoint()), server.local_addr());
+        let mut outdated = network.endpoint();
+        first.connect();
+        second.connect();
+        outdated.send(server.local_addr(), &encode(&ClientMessage::Connect { version: PROTOCOL_VERSION + 1 }));
+        server.poll(network.now());
+
+        assert_eq!(first.poll(), vec![ClientEvent::Welcome { player: MAX_PLAYERS - 1 }]);
+        assert!(matches!(second.poll()[..], [ClientEvent::Rejected { .. }]));
+        let (_, packet) = outdated.receive().unwrap();
+        assert!(matches!(decode::<ServerMessage>(&packet), Some(ServerMessage::Rejected { .. })));
+    }
+
+    #[test]
+    fn clients_join_over_localhost_udp() {
+        let server_transport = UdpTransport::bind(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap();
+        let mut server = ServerSession::new(Box::new(server_transport), 1);
+        let client_transport = UdpTransport::bind(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap();
+        let mut client = ClientSession::new(Box::new(client_transport), server.local_addr());
+
+        let mut events = vec![];
+        for _ in 0..100 {
+            client.connect();
+            server.poll(Duration::from_secs(0));
+            events.extend(client.poll());
+            if !events.is_empty() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(events, vec![ClientEvent::Welcome { player: 1 }]);
+    }
+}
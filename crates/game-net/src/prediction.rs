@@ -0,0 +1,46 @@
+use bevy::prelude::*;
+use std::collections::VecDeque;
+use game_data::*;
+use crate::*;
+
+
+/// Moves a player by one command the same way on server and client.
+pub fn simulate(player: &mut Player, position: &mut Vec2, command: &InputCommand, config: &PlayerConfig) {
+    player.step(command.input().movement, command.dt(), config);
+    *position += player.velocity * command.dt();
+}
+
+/// Client-side prediction for the local player: inputs move it immediately, and every server
+/// state resets it to the authoritative position and replays the commands the server hasn't
+/// processed yet.
+pub struct Prediction {
+    pub player: Player,
+    pub position: Vec2,
+    pending: VecDeque<InputCommand>,
+}
+
+impl Prediction {
+    pub fn new(player: Player, position: Vec2) -> Self {
+        Prediction { player, position, pending: VecDeque::new() }
+    }
+
+    pub fn predict(&mut self, command: InputCommand, config: &PlayerConfig) {
+        simulate(&mut self.player, &mut self.position, &command, config);
+        self.pending.push_back(command);
+        while self.pending.len() > MAX_PENDING_INPUTS {
+            self.pending.pop_front();
+        }
+    }
+
+    /// `position` and `velocity` are the server's after applying `last_input`.
+    pub fn reconcile(&mut self, position: Vec2, velocity: Vec2, last_input: Option<u32>, config: &PlayerConfig) {
+        if let Some(last_input) = last_input {
+            self.pending.retain(|command| command.sequence > last_input);
+        }
+        self.position = position;
+        self.player.velocity = velocity;
+        for command in self.pending.iter() {
+            simulate(&mut self.player, &mut self.position, command, config);
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::collections::BTreeMap;
+use game_data::*;
+use game_data::Direction as Direction;
+
+
+/// bumped whenever a message changes shape; clients with another version are rejected
+pub const PROTOCOL_VERSION: u16 = 6;
+/// positions and velocities travel as tenths of a pixel
+const QUANTUM: f32 = 10.0;
+
+fn quantize(v: Vec2) -> (i32, i32) {
+    ((v.x * QUANTUM).round() as i32, (v.y * QUANTUM).round() as i32)
+}
+
+fn dequantize(v: (i32, i32)) -> Vec2 {
+    Vec2::new(v.0 as f32 / QUANTUM, v.1 as f32 / QUANTUM)
+}
+
+/// Identifies an entity across the network, the server's `Entity` bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct NetId(pub u64);
+
+impl From<Entity> for NetId {
+    fn from(entity: Entity) -> Self {
+        NetId(entity.to_bits())
+    }
+}
+
+/// One frame of a player's input, numbered so the server applies each exactly once and in order.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct InputCommand {
+    pub sequence: u32,
+    /// -127..127 per axis
+    pub movement: (i8, i8),
+    pub aim: Option<Direction>,
+    pub fire: bool,
+    pub alt_fire: bool,
+    pub reload: bool,
+    /// frame length in milliseconds
+    pub dt_ms: u16,
+}
+
+impl InputCommand {
+    pub fn new(sequence: u32, input: &PlayerInput, dt: f32) -> Self {
+        let axis = |v: f32| (v.max(-1.0).min(1.0) * 127.0).round() as i8;
+        InputCommand {
+            sequence,
+            movement: (axis(input.movement.x), axis(input.movement.y)),
+            aim: input.aim,
+            fire: input.fire,
+            alt_fire: input.alt_fire,
+            reload: input.reload,
+            dt_ms: (dt * 1000.0).round().max(0.0).min(u16::MAX as f32) as u16,
+        }
+    }
+
+    pub fn input(&self) -> PlayerInput {
+        PlayerInput {
+            movement: Vec2::new(self.movement.0 as f32 / 127.0, self.movement.1 as f32 / 127.0),
+            aim: self.aim,
+            fire: self.fire,
+            alt_fire: self.alt_fire,
+            reload: self.reload,
+        }
+    }
+
+    pub fn dt(&self) -> f32 {
+        self.dt_ms as f32 / 1000.0
+    }
+
+    /// The input of the newest of `commands`, with a press of fire, alt fire or reload in any of
+    /// them kept so a tap between two server frames isn't lost. `None` without commands.
+    pub fn merge(commands: &[InputCommand]) -> Option<PlayerInput> {
+        let mut input = commands.last()?.input();
+        input.fire |= commands.iter().any(|command| command.fire);
+        input.alt_fire |= commands.iter().any(|command| command.alt_fire);
+        input.reload |= commands.iter().any(|command| command.reload);
+        Some(input)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EntityKind {
+    Player(u8),
+    Enemy,
+    Bullet,
+    Bomb,
+    AmmoPickup,
+    XpOrb,
+}
+
+/// What clients need to draw an entity. Quantized, so unchanged entities compare equal
+/// and are left out of deltas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntityState {
+    pub kind: EntityKind,
+    position: (i32, i32),
+    velocity: (i32, i32),
+    pub direction: Option<Direction>,
+    pub health: i32,
+    /// downed player or dying enemy
+    pub down: bool,
+}
+
+impl EntityState {
+    pub fn new(kind: EntityKind, position: Vec2, velocity: Vec2, direction: Option<Direction>, health: i32, down: bool) -> Self {
+        EntityState {
+            kind,
+            position: quantize(position),
+            velocity: quantize(velocity),
+            direction,
+            health,
+            down,
+        }
+    }
+
+    pub fn position(&self) -> Vec2 {
+        dequantize(self.position)
+    }
+
+    /// pixels per second
+    pub fn velocity(&self) -> Vec2 {
+        dequantize(self.velocity)
+    }
+}
+
+/// Every networked entity at one server tick.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct WorldState {
+    pub tick: u32,
+    pub entities: BTreeMap<NetId, EntityState>,
+}
+
+/// `WorldState` relative to an older state the client has acknowledged, or the whole state
+/// when `base_tick` is `None`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StateDelta {
+    pub tick: u32,
+    pub base_tick: Option<u32>,
+    pub changed: Vec<(NetId, EntityState)>,
+    pub removed: Vec<NetId>,
+}
+
+impl WorldState {
+    pub fn delta_from(&self, base: Option<&WorldState>) -> StateDelta {
+        let empty = BTreeMap::new();
+        let base_entities = base.map_or(&empty, |base| &base.entities);
+        StateDelta {
+            tick: self.tick,
+            base_tick: base.map(|base| base.tick),
+            changed: self.entities
+                .iter()
+                .filter(|(id, state)| base_entities.get(id) != Some(state))
+                .map(|(id, state)| (*id, *state))
+                .collect(),
+            removed: base_entities
+                .keys()
+                .filter(|id| !self.entities.contains_key(id))
+                .copied()
+                .collect(),
+        }
+    }
+
+    /// Rebuilds the full state from `delta` and the state it was made against.
+    pub fn apply(base: Option<&WorldState>, delta: &StateDelta) -> WorldState {
+        let mut entities = base.map(|base| base.entities.clone()).unwrap_or_default();
+        for id in delta.removed.iter() {
+            entities.remove(id);
+        }
+        entities.extend(delta.changed.iter().copied());
+        WorldState { tick: delta.tick, entities }
+    }
+}
+
+/// The upgrades a player is picking from while the game is paused, indices into
+/// `UpgradeDefinitions`, which clients load the same as the server.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UpgradeOffer {
+    pub player: u8,
+    pub options: Vec<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ClientMessage {
+    Connect { version: u16 },
+    /// Unconfirmed commands, oldest first, so a lost packet is covered by the next one.
+    /// `ack` is the newest snapshot tick the client has, the server deltas against it.
+    Inputs { ack: Option<u32>, commands: Vec<InputCommand> },
+    Disconnect,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ServerMessage {
+    Welcome { player: u8 },
+    Rejected { reason: String },
+    Snapshot {
+        delta: StateDelta,
+        score: u32,
+        wave: u32,
+        /// newest command of this client the state includes
+        last_input: Option<u32>,
+        choice: Option<UpgradeOffer>,
+    },
+}
+
+pub fn encode<T: Serialize>(message: &T) -> Vec<u8> {
+    bincode::serialize(message).expect("protocol messages always serialize")
+}
+
+/// `None` for packets that aren't a valid message, they are dropped like lost ones
+pub fn decode<T: DeserializeOwned>(packet: &[u8]) -> Option<T> {
+    bincode::deserialize(packet).ok()
+}
@@ -0,0 +1,119 @@
+use bevy::prelude::*;
+use bevy_rapier2d::physics::{RapierConfiguration, RigidBodyHandleComponent};
+use bevy_rapier2d::rapier::dynamics::RigidBodySet;
+use std::time::Duration;
+use game_animation::*;
+use game_data::*;
+use game_data::Direction as Direction;
+use crate::*;
+
+
+/// Joins and drops remote players, then hands each of them every input command that arrived
+/// since the last frame. Their movement steps once per command with the client's frame time,
+/// the way the client predicted it; players whose commands haven't arrived keep their last input.
+pub fn receive_clients(
+    mut commands: Commands,
+    mut server: ResMut<ServerSession>,
+    mut players: Query<(Entity, &PlayerId, &mut PlayerInput, Option<&mut RemoteSteps>)>,
+    rapier_config: Res<RapierConfiguration>,
+    sheets: Option<Res<SpriteSheets>>,
+    game_materials: Option<Res<GameMaterials>>,
+    config: Res<GameConfig>,
+    loadout: Option<Res<Loadout>>,
+    time: Res<Time>,
+) {
+    let visuals = sheets.as_deref().zip(game_materials.as_deref());
+    let now = Duration::from_secs_f64(time.seconds_since_startup());
+    for event in server.poll(now) {
+        match event {
+            ServerEvent::Joined { addr, player } => {
+                info!("player {} joined from {}", player, addr);
+                spawn_player(&mut commands, &rapier_config, visuals, &config, loadout.as_deref(), PlayerId(player), WorldPos(Vec2::ZERO))
+                    .insert(RemoteSteps::default());
+            },
+            ServerEvent::Left { player } => {
+                info!("player {} left", player);
+                for (entity, id, ..) in players.iter_mut() {
+                    if id.0 == player {
+                        commands.entity(entity).despawn();
+                    }
+                }
+            },
+        }
+    }
+
+    for (entity, id, mut input, remote_steps) in players.iter_mut() {
+        let received = server.take_commands(id.0);
+        let merged = match InputCommand::merge(&received) {
+            Some(merged) => merged,
+            None => continue,
+        };
+        *input = merged;
+        let steps = received.iter().map(|command| (command.input().movement, command.dt()));
+        match remote_steps {
+            Some(mut remote_steps) => remote_steps.0.extend(steps),
+            // respawned by a new run without it
+            None => {
+                commands.entity(entity).insert(RemoteSteps(steps.collect()));
+            },
+        }
+    }
+}
+
+/// Sends every client the world at `SNAPSHOT_RATE`.
+pub fn send_snapshots(
+    mut server: ResMut<ServerSession>,
+    mut timer: Local<Option<Timer>>,
+    players: Query<(Entity, &Transform, &PlayerId, &Player, &Health, &Direction, Option<&Downed>)>,
+    enemies: Query<(Entity, &Transform, &Health, &RigidBodyHandleComponent, Option<&Dying>), (With<Enemy>, Without<Inactive>)>,
+    bullets: Query<(Entity, &Transform, &Direction, &Heading, &Bullet), Without<Inactive>>,
+    bombs: Query<(Entity, &Transform), With<Bomb>>,
+    ammo_pickups: Query<(Entity, &Transform), With<AmmoPickup>>,
+    xp_orbs: Query<(Entity, &Transform), With<XpPickup>>,
+    rigid_bodies: Res<RigidBodySet>,
+    rapier_config: Res<RapierConfiguration>,
+    score: Res<Score>,
+    wave: Res<Wave>,
+    choice: Option<Res<UpgradeChoice>>,
+    time: Res<Time>,
+) {
+    let timer = timer.get_or_insert_with(|| Timer::from_seconds(1.0 / SNAPSHOT_RATE, true));
+    timer.tick(time.delta());
+    if !timer.finished() {
+        return;
+    }
+
+    let units = Units::new(&rapier_config);
+    let mut state = WorldState::default();
+    for (entity, transform, id, player, health, direction, downed) in players.iter() {
+        let kind = EntityKind::Player(id.0);
+        let entity_state = EntityState::new(kind, transform.translation.truncate(), player.velocity, Some(*direction), health.0, downed.is_some());
+        state.entities.insert(entity.into(), entity_state);
+    }
+    for (entity, transform, health, rigid_body_handle, dying) in enemies.iter() {
+        let velocity = rigid_bodies
+            .get(rigid_body_handle.handle())
+            .map_or(Vec2::ZERO, |rb| units.velocity_to_world(PhysicsVelocity::from_vector(rb.linvel())).0);
+        let entity_state = EntityState::new(EntityKind::Enemy, transform.translation.truncate(), velocity, None, health.0, dying.is_some());
+        state.entities.insert(entity.into(), entity_state);
+    }
+    for (entity, transform, direction, heading, bullet) in bullets.iter() {
+        // homing, ricochets and fragments fly along their heading, not one of the eight directions
+        let velocity = heading.0 * bullet.0;
+        let entity_state = EntityState::new(EntityKind::Bullet, transform.translation.truncate(), velocity, Some(*direction), 0, false);
+        state.entities.insert(entity.into(), entity_state);
+    }
+    // bombs and pickups lie still where they were dropped
+    let still = bombs.iter().map(|(entity, transform)| (entity, transform, EntityKind::Bomb))
+        .chain(ammo_pickups.iter().map(|(entity, transform)| (entity, transform, EntityKind::AmmoPickup)))
+        .chain(xp_orbs.iter().map(|(entity, transform)| (entity, transform, EntityKind::XpOrb)));
+    for (entity, transform, kind) in still {
+        let entity_state = EntityState::new(kind, transform.translation.truncate(), Vec2::ZERO, None, 0, false);
+        state.entities.insert(entity.into(), entity_state);
+    }
+    let choice = choice.and_then(|choice| {
+        let (_, _, id, ..) = players.get(choice.player).ok()?;
+        Some(UpgradeOffer { player: id.0, options: choice.options.clone() })
+    });
+    server.broadcast(state, score.0, wave.0, choice);
+}
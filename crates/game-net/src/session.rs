@@ -0,0 +1,304 @@
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::time::Duration;
+use game_data::*;
+use crate::*;
+
+
+/// server states kept as delta bases, about three seconds at `SNAPSHOT_RATE`
+pub const HISTORY: usize = 64;
+/// unconfirmed commands a client resends with every packet
+pub const MAX_PENDING_INPUTS: usize = 32;
+/// clients that send nothing for this long are dropped
+pub const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+/// local and remote players together
+pub const MAX_PLAYERS: u8 = 4;
+
+pub struct RemoteClient {
+    pub addr: SocketAddr,
+    pub player: u8,
+    commands: VecDeque<InputCommand>,
+    last_input: Option<u32>,
+    ack: Option<u32>,
+    last_heard: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerEvent {
+    Joined { addr: SocketAddr, player: u8 },
+    Left { player: u8 },
+}
+
+/// The authoritative side of the protocol, independent of the ECS: accepts clients, queues
+/// their input commands in order and sends each client a delta against the last state it
+/// acknowledged.
+pub struct ServerSession {
+    transport: Box<dyn Transport>,
+    clients: HashMap<SocketAddr, RemoteClient>,
+    history: VecDeque<WorldState>,
+    tick: u32,
+    /// ids below this belong to local players
+    first_remote_player: u8,
+}
+
+impl ServerSession {
+    pub fn new(transport: Box<dyn Transport>, first_remote_player: u8) -> Self {
+        ServerSession {
+            transport,
+            clients: HashMap::new(),
+            history: VecDeque::new(),
+            tick: 0,
+            first_remote_player,
+        }
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.transport.local_addr()
+    }
+
+    pub fn clients(&self) -> impl Iterator<Item = &RemoteClient> {
+        self.clients.values()
+    }
+
+    fn send(&mut self, to: SocketAddr, message: &ServerMessage) {
+        self.transport.send(to, &encode(message));
+    }
+
+    fn free_player(&self) -> Option<u8> {
+        (self.first_remote_player..MAX_PLAYERS).find(|player| self.clients.values().all(|client| client.player != *player))
+    }
+
+    /// Handles every packet that has arrived and drops silent clients. `now` is any monotonic clock.
+    pub fn poll(&mut self, now: Duration) -> Vec<ServerEvent> {
+        let mut events = vec![];
+        while let Some((from, packet)) = self.transport.receive() {
+            let message = match decode::<ClientMessage>(&packet) {
+                Some(message) => message,
+                None => continue,
+            };
+            match message {
+                ClientMessage::Connect { version } => {
+                    if version != PROTOCOL_VERSION {
+                        let reason = format!("server speaks protocol {}, client {}", PROTOCOL_VERSION, version);
+                        self.send(from, &ServerMessage::Rejected { reason });
+                        continue;
+                    }
+                    // the client resends Connect until a Welcome gets through
+                    if let Some(client) = self.clients.get(&from) {
+                        let player = client.player;
+                        self.send(from, &ServerMessage::Welcome { player });
+                        continue;
+                    }
+                    match self.free_player() {
+                        Some(player) => {
+                            self.clients.insert(from, RemoteClient {
+                                addr: from,
+                                player,
+                                commands: VecDeque::new(),
+                                last_input: None,
+                                ack: None,
+                                last_heard: now,
+                            });
+                            self.send(from, &ServerMessage::Welcome { player });
+                            events.push(ServerEvent::Joined { addr: from, player });
+                        },
+                        None => self.send(from, &ServerMessage::Rejected { reason: "server is full".to_owned() }),
+                    }
+                },
+                ClientMessage::Inputs { ack, commands } => {
+                    if let Some(client) = self.clients.get_mut(&from) {
+                        client.last_heard = now;
+                        // packets can arrive out of order, only ever move the ack forward
+                        if ack > client.ack {
+                            client.ack = ack;
+                        }
+                        let mut newest = client.commands.back().map(|command| command.sequence).or(client.last_input);
+                        for command in commands {
+                            if newest.map_or(true, |newest| command.sequence > newest) {
+                                newest = Some(command.sequence);
+                                client.commands.push_back(command);
+                            }
+                        }
+                        // a client running ahead of the server would otherwise pile up input latency
+                        while client.commands.len() > MAX_PENDING_INPUTS {
+                            client.commands.pop_front();
+                        }
+                    }
+                },
+                ClientMessage::Disconnect => {
+                    if let Some(client) = self.clients.remove(&from) {
+                        events.push(ServerEvent::Left { player: client.player });
+                    }
+                },
+            }
+        }
+
+        let timed_out = self.clients
+            .values()
+            .filter(|client| now - client.last_heard > CLIENT_TIMEOUT)
+            .map(|client| client.addr)
+            .collect::<Vec<_>>();
+        for addr in timed_out {
+            if let Some(client) = self.clients.remove(&addr) {
+                events.push(ServerEvent::Left { player: client.player });
+            }
+        }
+        events
+    }
+
+    /// Every command of `player` that hasn't been applied yet, oldest first. Call once per
+    /// simulated frame; the next snapshot tells the client they were all applied.
+    pub fn take_commands(&mut self, player: u8) -> Vec<InputCommand> {
+        let client = match self.clients.values_mut().find(|client| client.player == player) {
+            Some(client) => client,
+            None => return vec![],
+        };
+        let commands = client.commands.drain(..).collect::<Vec<_>>();
+        if let Some(command) = commands.last() {
+            client.last_input = Some(command.sequence);
+        }
+        commands
+    }
+
+    /// Stamps `state` with the next tick and sends it to every client, along with the upgrade
+    /// choice that has the game paused, if any.
+    pub fn broadcast(&mut self, mut state: WorldState, score: u32, wave: u32, choice: Option<UpgradeOffer>) {
+        self.tick += 1;
+        state.tick = self.tick;
+        let mut packets = vec![];
+        for client in self.clients.values() {
+            let base = client.ack.and_then(|ack| self.history.iter().find(|old| old.tick == ack));
+            let message = ServerMessage::Snapshot {
+                delta: state.delta_from(base),
+                score,
+                wave,
+                last_input: client.last_input,
+                choice: choice.clone(),
+            };
+            packets.push((client.addr, encode(&message)));
+        }
+        for (addr, packet) in packets {
+            self.transport.send(addr, &packet);
+        }
+        self.history.push_back(state);
+        while self.history.len() > HISTORY {
+            self.history.pop_front();
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClientEvent {
+    Welcome { player: u8 },
+    Rejected { reason: String },
+    /// `ClientSession::state` holds the new state
+    Snapshot { score: u32, wave: u32, last_input: Option<u32>, choice: Option<UpgradeOffer> },
+}
+
+/// The client side of the protocol: numbers and resends inputs until the server confirms
+/// them and rebuilds full states from the deltas it receives.
+pub struct ClientSession {
+    transport: Box<dyn Transport>,
+    server: SocketAddr,
+    player: Option<u8>,
+    states: VecDeque<WorldState>,
+    pending: VecDeque<InputCommand>,
+    sequence: u32,
+}
+
+impl ClientSession {
+    pub fn new(transport: Box<dyn Transport>, server: SocketAddr) -> Self {
+        ClientSession {
+            transport,
+            server,
+            player: None,
+            states: VecDeque::new(),
+            pending: VecDeque::new(),
+            sequence: 0,
+        }
+    }
+
+    /// which player the server gave us, once welcomed
+    pub fn player(&self) -> Option<u8> {
+        self.player
+    }
+
+    /// newest state from the server
+    pub fn state(&self) -> Option<&WorldState> {
+        self.states.back()
+    }
+
+    fn send(&mut self, message: &ClientMessage) {
+        self.transport.send(self.server, &encode(message));
+    }
+
+    /// Asks to join. Repeat until `ClientEvent::Welcome` arrives, the request may get lost.
+    pub fn connect(&mut self) {
+        self.send(&ClientMessage::Connect { version: PROTOCOL_VERSION });
+    }
+
+    pub fn disconnect(&mut self) {
+        self.send(&ClientMessage::Disconnect);
+    }
+
+    pub fn poll(&mut self) -> Vec<ClientEvent> {
+        let mut events = vec![];
+        while let Some((from, packet)) = self.transport.receive() {
+            if from != self.server {
+                continue;
+            }
+            let message = match decode::<ServerMessage>(&packet) {
+                Some(message) => message,
+                None => continue,
+            };
+            match message {
+                ServerMessage::Welcome { player } => {
+                    if self.player.is_none() {
+                        self.player = Some(player);
+                        events.push(ClientEvent::Welcome { player });
+                    }
+                },
+                ServerMessage::Rejected { reason } => events.push(ClientEvent::Rejected { reason }),
+                ServerMessage::Snapshot { delta, score, wave, last_input, choice } => {
+                    // late or duplicated packets are older than what we already have
+                    if self.state().map_or(false, |state| state.tick >= delta.tick) {
+                        continue;
+                    }
+                    let base = match delta.base_tick {
+                        Some(base_tick) => match self.states.iter().find(|state| state.tick == base_tick) {
+                            Some(base) => Some(base),
+                            None => continue,
+                        },
+                        None => None,
+                    };
+                    let state = WorldState::apply(base, &delta);
+                    self.states.push_back(state);
+                    while self.states.len() > HISTORY {
+                        self.states.pop_front();
+                    }
+                    if let Some(last_input) = last_input {
+                        self.pending.retain(|command| command.sequence > last_input);
+                    }
+                    events.push(ClientEvent::Snapshot { score, wave, last_input, choice });
+                },
+            }
+        }
+        events
+    }
+
+    /// Numbers this frame's input and sends it along with every command the server hasn't confirmed.
+    pub fn send_input(&mut self, input: &PlayerInput, dt: f32) -> InputCommand {
+        self.sequence += 1;
+        let command = InputCommand::new(self.sequence, input, dt);
+        self.pending.push_back(command);
+        while self.pending.len() > MAX_PENDING_INPUTS {
+            self.pending.pop_front();
+        }
+        let message = ClientMessage::Inputs {
+            ack: self.state().map(|state| state.tick),
+            commands: self.pending.iter().copied().collect(),
+        };
+        self.send(&message);
+        command
+    }
+}
@@ -0,0 +1,155 @@
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+
+/// largest UDP payload. Full snapshots can get this big, deltas stay far smaller.
+pub const MAX_PACKET: usize = 65_507;
+
+/// Unreliable, unordered datagrams. Sending never blocks and may silently lose packets.
+pub trait Transport: Send + Sync {
+    fn send(&mut self, to: SocketAddr, packet: &[u8]);
+    /// next packet that has arrived, if any
+    fn receive(&mut self) -> Option<(SocketAddr, Vec<u8>)>;
+    fn local_addr(&self) -> SocketAddr;
+}
+
+pub struct UdpTransport {
+    socket: UdpSocket,
+    buffer: Vec<u8>,
+}
+
+impl UdpTransport {
+    pub fn bind(addr: SocketAddr) -> io::Result<Self> {
+        let socket = UdpSocket::bind(addr)?;
+        socket.set_nonblocking(true)?;
+        Ok(UdpTransport { socket, buffer: vec![0; MAX_PACKET] })
+    }
+}
+
+impl Transport for UdpTransport {
+    fn send(&mut self, to: SocketAddr, packet: &[u8]) {
+        // a full send buffer or an unreachable peer is just another lost packet
+        let _ = self.socket.send_to(packet, to);
+    }
+
+    fn receive(&mut self) -> Option<(SocketAddr, Vec<u8>)> {
+        loop {
+            match self.socket.recv_from(&mut self.buffer) {
+                Ok((len, from)) => return Some((from, self.buffer[..len].to_vec())),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return None,
+                // e.g. ICMP port unreachable from a client that went away, try the next packet
+                Err(_) => continue,
+            }
+        }
+    }
+
+    fn local_addr(&self) -> SocketAddr {
+        self.socket.local_addr().expect("bound sockets have an address")
+    }
+}
+
+/// How a `SimulatedNetwork` mistreats packets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LinkConditions {
+    /// chance of dropping each packet, 0..1
+    pub loss: f32,
+    pub latency: Duration,
+    /// extra random delay up to this much, which also reorders packets
+    pub jitter: Duration,
+}
+
+impl LinkConditions {
+    pub fn perfect() -> Self {
+        LinkConditions { loss: 0.0, latency: Duration::from_secs(0), jitter: Duration::from_secs(0) }
+    }
+}
+
+struct InFlight {
+    deliver_at: Duration,
+    from: SocketAddr,
+    to: SocketAddr,
+    packet: Vec<u8>,
+}
+
+struct SimulatedLinks {
+    conditions: LinkConditions,
+    rng: ChaCha8Rng,
+    now: Duration,
+    next_port: u16,
+    in_flight: Vec<InFlight>,
+}
+
+/// In-process network for tests: endpoints exchange packets through shared queues with
+/// seeded loss, latency and jitter. Time only moves when `advance` is called.
+#[derive(Clone)]
+pub struct SimulatedNetwork {
+    links: Arc<Mutex<SimulatedLinks>>,
+}
+
+pub struct SimulatedTransport {
+    links: Arc<Mutex<SimulatedLinks>>,
+    addr: SocketAddr,
+}
+
+impl SimulatedNetwork {
+    pub fn new(conditions: LinkConditions, seed: u64) -> Self {
+        SimulatedNetwork {
+            links: Arc::new(Mutex::new(SimulatedLinks {
+                conditions,
+                rng: ChaCha8Rng::seed_from_u64(seed),
+                now: Duration::from_secs(0),
+                next_port: 10_000,
+                in_flight: vec![],
+            })),
+        }
+    }
+
+    pub fn endpoint(&self) -> SimulatedTransport {
+        let mut links = self.links.lock().unwrap();
+        let addr = SocketAddr::from(([127, 0, 0, 1], links.next_port));
+        links.next_port += 1;
+        SimulatedTransport { links: self.links.clone(), addr }
+    }
+
+    pub fn advance(&self, dt: Duration) {
+        self.links.lock().unwrap().now += dt;
+    }
+
+    pub fn now(&self) -> Duration {
+        self.links.lock().unwrap().now
+    }
+}
+
+impl Transport for SimulatedTransport {
+    fn send(&mut self, to: SocketAddr, packet: &[u8]) {
+        let mut links = self.links.lock().unwrap();
+        let conditions = links.conditions;
+        if links.rng.gen::<f32>() < conditions.loss {
+            return;
+        }
+        let jitter = conditions.jitter.mul_f32(links.rng.gen::<f32>());
+        let deliver_at = links.now + conditions.latency + jitter;
+        links.in_flight.push(InFlight { deliver_at, from: self.addr, to, packet: packet.to_vec() });
+    }
+
+    fn receive(&mut self) -> Option<(SocketAddr, Vec<u8>)> {
+        let mut links = self.links.lock().unwrap();
+        let now = links.now;
+        let index = links.in_flight
+            .iter()
+            .enumerate()
+            .filter(|(_, in_flight)| in_flight.to == self.addr && in_flight.deliver_at <= now)
+            .min_by_key(|(_, in_flight)| in_flight.deliver_at)
+            .map(|(index, _)| index)?;
+        let in_flight = links.in_flight.remove(index);
+        Some((in_flight.from, in_flight.packet))
+    }
+
+    fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
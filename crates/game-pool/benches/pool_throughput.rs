@@ -0,0 +1,102 @@
+//! Compares firing through the pool against spawning and despawning a fresh bullet per shot.
+//! Both paths run the Rapier plugin so body and collider creation is part of the cost.
+use bevy::prelude::*;
+use bevy_rapier2d::physics::RapierPhysicsPlugin;
+use bevy_rapier2d::rapier::dynamics::RigidBodyBuilder;
+use bevy_rapier2d::rapier::geometry::ColliderBuilder;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use game_data::*;
+use game_pool::*;
+
+const SHOTS_PER_FRAME: usize = 8;
+const LIFETIME_FRAMES: u32 = 30;
+const FRAMES: usize = 240;
+
+/// frames since the bullet was fired
+struct Age(u32);
+
+fn spawn_bullet(commands: &mut Commands, i: usize) -> Entity {
+    commands.spawn()
+        .insert(Transform::default())
+        .insert(GlobalTransform::default())
+        .insert(Bullet(30.))
+        .insert(RigidBodyBuilder::new_dynamic().translation(i as f32, 0.0))
+        .insert(ColliderBuilder::cuboid(0.125, 0.125))
+        .insert(Age(0))
+        .id()
+}
+
+fn spawn_despawn(
+    mut commands: Commands,
+    mut bullets: Query<(Entity, &mut Age)>,
+) {
+    for (entity, mut age) in bullets.iter_mut() {
+        age.0 += 1;
+        if age.0 > LIFETIME_FRAMES {
+            commands.entity(entity).despawn();
+        }
+    }
+    for i in 0..SHOTS_PER_FRAME {
+        spawn_bullet(&mut commands, i);
+    }
+}
+
+fn pooled(
+    mut commands: Commands,
+    mut pool: ResMut<EntityPool<Bullet>>,
+    mut bullets: Query<(Entity, &mut Age), Without<Inactive>>,
+) {
+    for (entity, mut age) in bullets.iter_mut() {
+        age.0 += 1;
+        if age.0 > LIFETIME_FRAMES {
+            commands.entity(entity).remove::<Age>().insert(Recycle);
+        }
+    }
+    for i in 0..SHOTS_PER_FRAME {
+        match pool.acquire() {
+            Some(entity) => {
+                commands.entity(entity)
+                    .remove::<Inactive>()
+                    .insert(Activate { position: WorldPos(Vec2::new(i as f32, 0.0)), velocity: PixelsPerSecond::default() })
+                    .insert(Age(0));
+            },
+            None => {
+                pool.add();
+                let entity = spawn_bullet(&mut commands, i);
+                commands.entity(entity).insert(Pooled);
+            }
+        }
+    }
+}
+
+fn build_app(use_pool: bool) -> App {
+    let mut app_builder = App::build();
+    app_builder
+        .add_plugins(MinimalPlugins)
+        .add_plugin(RapierPhysicsPlugin)
+        .add_plugin(PoolPlugin);
+    if use_pool {
+        app_builder.add_system(pooled.system());
+    } else {
+        app_builder.add_system(spawn_despawn.system());
+    }
+    app_builder.app
+}
+
+fn run(mut app: App) {
+    for _ in 0..FRAMES {
+        app.update();
+    }
+}
+
+fn bullet_throughput(c: &mut Criterion) {
+    c.bench_function("spawn and despawn bullets", |b| {
+        b.iter_batched(|| build_app(false), run, BatchSize::LargeInput)
+    });
+    c.bench_function("pooled bullets", |b| {
+        b.iter_batched(|| build_app(true), run, BatchSize::LargeInput)
+    });
+}
+
+criterion_group!(benches, bullet_throughput);
+criterion_main!(benches);
@@ -0,0 +1,128 @@
+use bevy::prelude::*;
+use std::marker::PhantomData;
+use game_data::*;
+use pool_handler::*;
+mod pool_handler;
+
+
+/// Recycles bullets and enemies instead of despawning them, so firing doesn't keep
+/// creating and destroying Rapier bodies and colliders.
+pub struct PoolPlugin;
+
+impl Plugin for PoolPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(EntityPool::<Bullet>::default())
+            .insert_resource(EntityPool::<Enemy>::default())
+            .insert_resource(PoolMetricsTimer(Timer::from_seconds(5.0, true)))
+            .add_system(recycle_entities::<Bullet>.system())
+            .add_system(recycle_entities::<Enemy>.system())
+            .add_system(activate_entities::<Bullet>.system())
+            .add_system(activate_entities::<Enemy>.system())
+            .add_system(log_pool_metrics.system());
+    }
+}
+
+/// where parked bodies wait, in physics units, far outside the arena
+pub const PARK_POSITION: (f32, f32) = (-10_000.0, -10_000.0);
+
+/// Reactivates a parked entity: moves its body to `position`, sets `velocity`
+/// and turns collisions and visibility back on.
+pub struct Activate {
+    pub position: WorldPos,
+    pub velocity: PixelsPerSecond,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PoolMetrics {
+    /// every entity the pool owns, parked or not
+    pub capacity: usize,
+    pub free: usize,
+    pub in_use: usize,
+    pub peak_in_use: usize,
+    /// acquires that found no parked entity and had to spawn
+    pub misses: u64,
+}
+
+/// Free list of parked entities with marker component `T`.
+pub struct EntityPool<T> {
+    free: Vec<Entity>,
+    capacity: usize,
+    peak_in_use: usize,
+    misses: u64,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Default for EntityPool<T> {
+    fn default() -> Self {
+        EntityPool {
+            free: Vec::new(),
+            capacity: 0,
+            peak_in_use: 0,
+            misses: 0,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T> EntityPool<T> {
+    /// Takes a parked entity. The caller removes `Inactive` and inserts `Activate`.
+    /// On `None` the caller spawns a new entity with `Pooled` and calls `add`.
+    pub fn acquire(&mut self) -> Option<Entity> {
+        let entity = self.free.pop();
+        if entity.is_none() {
+            self.misses += 1;
+        }
+        self.peak_in_use = self.peak_in_use.max(self.in_use());
+        entity
+    }
+
+    /// counts a freshly spawned `Pooled` entity as owned by this pool
+    pub fn add(&mut self) {
+        self.capacity += 1;
+        self.peak_in_use = self.peak_in_use.max(self.in_use());
+    }
+
+    pub fn in_use(&self) -> usize {
+        self.capacity.saturating_sub(self.free.len())
+    }
+
+    pub fn metrics(&self) -> PoolMetrics {
+        PoolMetrics {
+            capacity: self.capacity,
+            free: self.free.len(),
+            in_use: self.in_use(),
+            peak_in_use: self.peak_in_use,
+            misses: self.misses,
+        }
+    }
+
+    pub(crate) fn release(&mut self, entity: Entity) {
+        self.free.push(entity);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metrics_follow_acquire_and_release() {
+        let mut pool = EntityPool::<Bullet>::default();
+        assert!(pool.acquire().is_none());
+        pool.add();
+        pool.add();
+        assert_eq!(pool.metrics().in_use, 2);
+
+        pool.release(Entity::new(0));
+        pool.release(Entity::new(1));
+        assert_eq!(pool.metrics().free, 2);
+
+        assert!(pool.acquire().is_some());
+        let metrics = pool.metrics();
+        assert_eq!(metrics.capacity, 2);
+        assert_eq!(metrics.in_use, 1);
+        assert_eq!(metrics.peak_in_use, 2);
+        assert_eq!(metrics.misses, 1);
+    }
+}
@@ -0,0 +1,80 @@
+use bevy::prelude::*;
+use bevy_rapier2d::physics::{RapierConfiguration, RigidBodyHandleComponent, ColliderHandleComponent};
+use bevy_rapier2d::rapier::dynamics::RigidBodySet;
+use bevy_rapier2d::rapier::geometry::{ColliderSet, InteractionGroups};
+use bevy_rapier2d::rapier::math::Isometry;
+use bevy_rapier2d::rapier::na::Vector2;
+use std::time::Duration;
+use game_data::*;
+use crate::*;
+
+
+pub struct PoolMetricsTimer(pub Timer);
+
+/// parks entities marked with `Recycle` and puts them back on the free list
+pub fn recycle_entities<T: Component>(
+    mut commands: Commands,
+    mut pool: ResMut<EntityPool<T>>,
+    mut bodies: ResMut<RigidBodySet>,
+    mut colliders: ResMut<ColliderSet>,
+    mut query: Query<
+        (Entity, &RigidBodyHandleComponent, &ColliderHandleComponent, Option<&mut Visible>),
+        (With<T>, With<Recycle>, Without<Inactive>),
+    >,
+) {
+    for (entity, rigid_body_handle, collider_handle, visible) in query.iter_mut() {
+        if let Some(rb) = bodies.get_mut(rigid_body_handle.handle()) {
+            rb.set_linvel(Vector2::zeros(), false);
+            rb.set_position(Isometry::translation(PARK_POSITION.0, PARK_POSITION.1), false);
+            rb.sleep();
+        }
+        if let Some(collider) = colliders.get_mut(collider_handle.handle()) {
+            collider.set_collision_groups(InteractionGroups::none());
+        }
+        if let Some(mut visible) = visible {
+            visible.is_visible = false;
+        }
+        commands.entity(entity).remove::<Recycle>().insert(Inactive);
+        pool.release(entity);
+    }
+}
+
+pub fn activate_entities<T: Component>(
+    mut commands: Commands,
+    rapier_config: Res<RapierConfiguration>,
+    mut bodies: ResMut<RigidBodySet>,
+    mut colliders: ResMut<ColliderSet>,
+    mut query: Query<
+        (Entity, &Activate, &RigidBodyHandleComponent, &ColliderHandleComponent, Option<&mut Visible>),
+        With<T>,
+    >,
+) {
+    let units = Units::new(&rapier_config);
+    for (entity, activate, rigid_body_handle, collider_handle, visible) in query.iter_mut() {
+        if let Some(rb) = bodies.get_mut(rigid_body_handle.handle()) {
+            let position = units.to_physics(activate.position).0;
+            rb.set_position(Isometry::translation(position.x, position.y), true);
+            rb.set_linvel(units.velocity_to_physics(activate.velocity).vector(), true);
+        }
+        if let Some(collider) = colliders.get_mut(collider_handle.handle()) {
+            collider.set_collision_groups(InteractionGroups::all());
+        }
+        if let Some(mut visible) = visible {
+            visible.is_visible = true;
+        }
+        commands.entity(entity).remove::<Activate>();
+    }
+}
+
+pub fn log_pool_metrics(
+    bullet_pool: Res<EntityPool<Bullet>>,
+    enemy_pool: Res<EntityPool<Enemy>>,
+    mut timer: ResMut<PoolMetricsTimer>,
+    time: Res<Time>,
+) {
+    timer.0.tick(Duration::from_secs_f32(time.delta_seconds()));
+    if timer.0.finished() {
+        debug!("bullet pool: {:?}", bullet_pool.metrics());
+        debug!("enemy pool: {:?}", enemy_pool.metrics());
+    }
+}
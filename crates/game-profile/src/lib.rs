@@ -0,0 +1,483 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use game_data::*;
+use profile_handler::*;
+mod profile_handler;
+
+
+/// Progress that outlives a run. The first run starts as the character the profile played last;
+/// when every player is down the run's earnings go into the `Profile` and the between-runs shop
+/// opens, and leaving it goes to the character select for the next run. Both are skipped without
+/// `HudShown`. Needs `Profile`, `ShopDefinitions`, `WeaponDefinitions` and
+/// `CharacterDefinitions` resources.
+pub struct ProfilePlugin;
+
+impl Plugin for ProfilePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<MenuOpen>()
+            .add_startup_system_to_stage(StartupStage::PreStartup, equip_profile.system())
+            .add_system(end_run.system())
+            .add_system(browse_shop.system())
+            .add_system(select_character.system())
+            .add_system(track_menus.system());
+    }
+}
+
+pub const PROFILE_PATH: &str = "saves/profile.ron";
+
+/// Turns the fields of a profile one version older into the next version's.
+pub type Migration = fn(&mut ron::Map);
+
+/// `MIGRATIONS[n]` upgrades a version `n + 1` profile to version `n + 2`. New fields with a
+/// sensible default don't need a step, `#[serde(default)]` fills them in; renamed, moved or
+/// reinterpreted fields do.
+pub const MIGRATIONS: &[Migration] = &[];
+
+/// the `Profile` layout this build writes; older ones are migrated on load
+pub const PROFILE_VERSION: u32 = 1 + MIGRATIONS.len() as u32;
+
+/// Everything that carries over between runs, written after every run and every purchase.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub version: u32,
+    /// unspent
+    pub currency: u32,
+    /// oldest first
+    pub runs: Vec<RunRecord>,
+    /// weapons bought in the shop; the starter weapon is always unlocked
+    pub unlocked_weapons: BTreeSet<String>,
+    /// characters bought in the shop; the starter character is always unlocked
+    pub unlocked_characters: BTreeSet<String>,
+    /// shop item names and how many levels of each were bought
+    pub purchases: BTreeMap<String, u32>,
+    /// weapon new runs start with, the character's own while `None`
+    pub weapon: Option<String>,
+    /// character picked for the last run, the starter while `None`
+    pub character: Option<String>,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Profile {
+            version: PROFILE_VERSION,
+            currency: 0,
+            runs: vec![],
+            unlocked_weapons: BTreeSet::new(),
+            unlocked_characters: BTreeSet::new(),
+            purchases: BTreeMap::new(),
+            weapon: None,
+            character: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunRecord {
+    pub score: u32,
+    pub wave: u32,
+    /// currency the run paid out
+    pub earned: u32,
+}
+
+#[derive(Debug)]
+pub enum ProfileError {
+    Io(PathBuf, std::io::Error),
+    Parse(PathBuf, ron::Error),
+    Serialize(ron::Error),
+    /// the file was written by a newer build
+    Version { found: u32, expected: u32 },
+}
+
+impl fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProfileError::Io(path, e) => write!(f, "could not access {}: {}", path.display(), e),
+            ProfileError::Parse(path, e) => write!(f, "could not parse {}: {}", path.display(), e),
+            ProfileError::Serialize(e) => write!(f, "could not serialize profile: {}", e),
+            ProfileError::Version { found, expected } => {
+                write!(f, "profile is version {}, this build reads up to version {}", found, expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProfileError {}
+
+/// why the shop didn't sell an item
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PurchaseError {
+    MaxedOut,
+    TooExpensive { cost: u32, currency: u32 },
+}
+
+impl fmt::Display for PurchaseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PurchaseError::MaxedOut => write!(f, "already at the highest level"),
+            PurchaseError::TooExpensive { cost, currency } => write!(f, "costs {}, only {} to spend", cost, currency),
+        }
+    }
+}
+
+/// only the version, read first to know which migrations the rest needs
+#[derive(Deserialize)]
+struct VersionHeader {
+    version: u32,
+}
+
+impl Profile {
+    pub fn to_ron(&self) -> Result<String, ProfileError> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).map_err(ProfileError::Serialize)
+    }
+
+    pub fn from_ron(text: &str) -> Result<Profile, ProfileError> {
+        Profile::migrate(text, MIGRATIONS)
+    }
+
+    /// Reads a profile of any version up to `1 + migrations.len()`, running the steps from its
+    /// version onwards on the raw fields before they are read into a `Profile`.
+    fn migrate(text: &str, migrations: &[Migration]) -> Result<Profile, ProfileError> {
+        let latest = 1 + migrations.len() as u32;
+        let parse_error = |e| ProfileError::Parse(PathBuf::new(), e);
+        let header: VersionHeader = ron::from_str(text).map_err(parse_error)?;
+        if header.version < 1 || header.version > latest {
+            return Err(ProfileError::Version { found: header.version, expected: latest });
+        }
+        let mut value: ron::Value = ron::from_str(text).map_err(parse_error)?;
+        if let ron::Value::Map(fields) = &mut value {
+            for step in migrations[header.version as usize - 1..].iter() {
+                step(fields);
+            }
+            fields.insert(ron::Value::String("version".to_owned()), ron::Value::Number(ron::Number::new(latest as i64)));
+        }
+        value.into_rust().map_err(parse_error)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ProfileError> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| ProfileError::Io(parent.to_owned(), e))?;
+        }
+        std::fs::write(path, self.to_ron()?).map_err(|e| ProfileError::Io(path.to_owned(), e))
+    }
+
+    /// Reads the profile at `path`, or starts a new one if there is none yet.
+    pub fn load(path: impl AsRef<Path>) -> Result<Profile, ProfileError> {
+        let path = path.as_ref();
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Profile::default()),
+            Err(e) => return Err(ProfileError::Io(path.to_owned(), e)),
+        };
+        Profile::from_ron(&text).map_err(|e| match e {
+            ProfileError::Parse(_, e) => ProfileError::Parse(path.to_owned(), e),
+            e => e,
+        })
+    }
+
+    /// levels of the shop item called `name` bought so far
+    pub fn level(&self, name: &str) -> u32 {
+        self.purchases.get(name).copied().unwrap_or(0)
+    }
+
+    /// Pays for the next level of `item`, returning what it cost.
+    pub fn buy(&mut self, item: &ShopItem) -> Result<u32, PurchaseError> {
+        let owned = self.level(&item.name);
+        if owned >= item.max_level {
+            return Err(PurchaseError::MaxedOut);
+        }
+        let cost = item.cost_at(owned);
+        if cost > self.currency {
+            return Err(PurchaseError::TooExpensive { cost, currency: self.currency });
+        }
+        self.currency -= cost;
+        self.purchases.insert(item.name.clone(), owned + 1);
+        match &item.effect {
+            ShopEffect::UnlockWeapon(weapon) => {
+                self.unlocked_weapons.insert(weapon.clone());
+            },
+            ShopEffect::UnlockCharacter(character) => {
+                self.unlocked_characters.insert(character.clone());
+            },
+            ShopEffect::Stat(_) => (),
+        }
+        Ok(cost)
+    }
+
+    pub fn has_weapon(&self, name: &str, weapons: &WeaponDefinitions) -> bool {
+        weapons.starter().name == name || self.unlocked_weapons.contains(name)
+    }
+
+    /// Makes new runs start with the weapon called `name`, if it is unlocked.
+    pub fn equip_weapon(&mut self, name: &str, weapons: &WeaponDefinitions) -> bool {
+        if weapons.get(name).is_none() || !self.has_weapon(name, weapons) {
+            return false;
+        }
+        self.weapon = Some(name.to_owned());
+        true
+    }
+
+    /// The equipped weapon, or the character's own if none is equipped or it was removed or
+    /// never unlocked. Falls back to the starter if the character's weapon doesn't exist.
+    pub fn weapon<'a>(&self, weapons: &'a WeaponDefinitions, characters: &CharacterDefinitions) -> &'a WeaponDefinition {
+        self.weapon
+            .as_deref()
+            .filter(|name| self.has_weapon(name, weapons))
+            .and_then(|name| weapons.get(name))
+            .or_else(|| weapons.get(&self.character(characters).weapon))
+            .unwrap_or_else(|| weapons.starter())
+    }
+
+    pub fn has_character(&self, name: &str, characters: &CharacterDefinitions) -> bool {
+        characters.starter().name == name || self.unlocked_characters.contains(name)
+    }
+
+    /// Makes new runs start as the character called `name`, if it is unlocked.
+    pub fn choose_character(&mut self, name: &str, characters: &CharacterDefinitions) -> bool {
+        if characters.get(name).is_none() || !self.has_character(name, characters) {
+            return false;
+        }
+        self.character = Some(name.to_owned());
+        true
+    }
+
+    /// the chosen character, falling back to the starter if it was removed or never unlocked
+    pub fn character<'a>(&self, characters: &'a CharacterDefinitions) -> &'a CharacterDefinition {
+        self.character
+            .as_deref()
+            .filter(|name| self.has_character(name, characters))
+            .and_then(|name| characters.get(name))
+            .unwrap_or_else(|| characters.starter())
+    }
+
+    /// Pays out a finished run and keeps it on record, returning the earnings.
+    pub fn record_run(&mut self, score: u32, wave: u32, shop: &ShopDefinitions) -> u32 {
+        let earned = shop.earnings(score, wave);
+        self.currency += earned;
+        self.runs.push(RunRecord { score, wave, earned });
+        earned
+    }
+
+    /// What the next run starts with. Items no longer sold are ignored.
+    pub fn loadout(&self, shop: &ShopDefinitions, weapons: &WeaponDefinitions, characters: &CharacterDefinitions) -> Loadout {
+        let mut bonuses = vec![];
+        for item in shop.items.iter() {
+            if let ShopEffect::Stat(effect) = item.effect {
+                let levels = self.level(&item.name).min(item.max_level);
+                bonuses.extend(std::iter::repeat(effect).take(levels as usize));
+            }
+        }
+        Loadout {
+            character: Some(self.character(characters).clone()),
+            weapon: Some(self.weapon(weapons, characters).clone()),
+            bonuses,
+        }
+    }
+}
+
+/// what a line of the shop offers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShopLine {
+    /// index into `ShopDefinitions::items`, bought with fire
+    Item(usize),
+    /// index into `WeaponDefinitions::weapons`, equipped with fire once unlocked
+    Weapon(usize),
+}
+
+/// every item for sale, then every weapon
+pub fn shop_lines(shop: &ShopDefinitions, weapons: &WeaponDefinitions) -> Vec<ShopLine> {
+    (0..shop.items.len()).map(ShopLine::Item)
+        .chain((0..weapons.weapons.len()).map(ShopLine::Weapon))
+        .collect()
+}
+
+/// The between-runs shop, open while this resource exists. Its player aims up and down to pick
+/// a line, fires to buy or equip it and drops a bomb to start the next run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShopScreen {
+    pub player: Entity,
+    /// index into `shop_lines`
+    pub selected: usize,
+    /// what the run that just ended paid out
+    pub earned: u32,
+    /// the player has let go of every button since the last action, so one press is one action
+    pub ready: bool,
+}
+
+impl ShopScreen {
+    pub fn new(player: Entity, earned: u32) -> Self {
+        ShopScreen { player, selected: 0, earned, ready: false }
+    }
+}
+
+/// Picking who to play before a run, open while this resource exists. The first human player
+/// aims up and down through `CharacterDefinitions` and fires to start the run as the selected one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CharacterSelect {
+    /// index into `CharacterDefinitions::characters`
+    pub selected: usize,
+    /// the player has let go of every button since the last action, so one press is one action
+    pub ready: bool,
+}
+
+impl CharacterSelect {
+    /// opens on the character the profile played last
+    pub fn new(profile: &Profile, characters: &CharacterDefinitions) -> Self {
+        let name = &profile.character(characters).name;
+        let selected = characters.characters.iter().position(|character| &character.name == name).unwrap_or(0);
+        CharacterSelect { selected, ready: false }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profiles_survive_a_round_trip() {
+        let shop = ShopDefinitions::default();
+        let mut profile = Profile::default();
+        profile.record_run(40, 4, &shop);
+        profile.buy(&shop.items[0]).unwrap();
+        profile.weapon = Some("repeater".to_owned());
+        assert_eq!(Profile::from_ron(&profile.to_ron().unwrap()).unwrap(), profile);
+    }
+
+    #[test]
+    fn old_profiles_are_migrated() {
+        // as if version 2 had renamed `cash` to `currency`
+        fn rename_cash(fields: &mut ron::Map) {
+            if let Some(cash) = fields.remove(&ron::Value::String("cash".to_owned())) {
+                fields.insert(ron::Value::String("currency".to_owned()), cash);
+            }
+        }
+        let old = r#"(version: 1, cash: 75, purchases: {"vitality": 2})"#;
+        let profile = Profile::migrate(old, &[rename_cash]).unwrap();
+        assert_eq!(profile.version, 2);
+        assert_eq!(profile.currency, 75);
+        assert_eq!(profile.level("vitality"), 2);
+
+        let current = r#"(version: 2, currency: 10)"#;
+        assert_eq!(Profile::migrate(current, &[rename_cash]).unwrap().currency, 10);
+    }
+
+    #[test]
+    fn newer_profiles_are_refused() {
+        let profile = Profile { version: PROFILE_VERSION + 1, ..Profile::default() };
+        match Profile::from_ron(&profile.to_ron().unwrap()) {
+            Err(ProfileError::Version { found, expected }) => {
+                assert_eq!(found, PROFILE_VERSION + 1);
+                assert_eq!(expected, PROFILE_VERSION);
+            },
+            other => panic!("expected a version error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn purchases_cost_currency_and_unlock_weapons() {
+        let shop = ShopDefinitions::default();
+        let weapons = WeaponDefinitions::default();
+        let characters = CharacterDefinitions::default();
+        let item = |name: &str| shop.items.iter().find(|item| item.name == name).unwrap();
+        let mut profile = Profile { currency: 150, ..Profile::default() };
+
+        assert!(!profile.equip_weapon("repeater", &weapons));
+        assert_eq!(profile.buy(item("repeater")), Ok(60));
+        assert_eq!(profile.buy(item("repeater")), Err(PurchaseError::MaxedOut));
+        assert!(profile.equip_weapon("repeater", &weapons));
+        assert_eq!(profile.buy(item("vitality")), Ok(30));
+        assert_eq!(profile.buy(item("vitality")), Ok(45));
+        assert_eq!(profile.buy(item("vitality")), Err(PurchaseError::TooExpensive { cost: 68, currency: 15 }));
+
+        let loadout = profile.loadout(&shop, &weapons, &characters);
+        assert_eq!(loadout.character.unwrap().name, "soldier");
+        assert_eq!(loadout.weapon.unwrap().name, "repeater");
+        assert_eq!(loadout.bonuses, vec![UpgradeEffect::MaxHealth { amount: 1 }; 2]);
+        assert_eq!(Profile::default().loadout(&shop, &weapons, &characters).weapon.unwrap().name, "charger");
+    }
+
+    #[test]
+    fn characters_are_unlocked_and_bring_their_own_weapon() {
+        let shop = ShopDefinitions::default();
+        let weapons = WeaponDefinitions::default();
+        let characters = CharacterDefinitions::default();
+        let tank = shop.items.iter().find(|item| item.name == "tank").unwrap();
+        let mut profile = Profile { currency: 200, ..Profile::default() };
+
+        assert!(!profile.choose_character("tank", &characters));
+        assert_eq!(CharacterSelect::new(&profile, &characters).selected, 0);
+        profile.buy(tank).unwrap();
+        assert!(profile.choose_character("tank", &characters));
+        assert_eq!(CharacterSelect::new(&profile, &characters).selected, 2);
+
+        // the scattergun isn't unlocked, but it is the tank's own
+        let loadout = profile.loadout(&shop, &weapons, &characters);
+        assert_eq!(loadout.character.unwrap().name, "tank");
+        assert_eq!(loadout.weapon.unwrap().name, "scattergun");
+        assert!(!profile.has_weapon("scattergun", &weapons));
+
+        // an equipped weapon goes with every character
+        assert!(profile.equip_weapon("charger", &weapons));
+        assert_eq!(profile.weapon(&weapons, &characters).name, "charger");
+    }
+
+    #[test]
+    fn the_first_run_is_equipped_before_startup() {
+        fn spawn_players(loadout: Res<Loadout>) {
+            assert_eq!(loadout.character.as_ref().unwrap().name, "tank");
+        }
+
+        let characters = CharacterDefinitions::default();
+        let mut profile = Profile { currency: 200, ..Profile::default() };
+        profile.buy(ShopDefinitions::default().items.iter().find(|item| item.name == "tank").unwrap()).unwrap();
+        assert!(profile.choose_character("tank", &characters));
+
+        let mut app_builder = App::build();
+        app_builder
+            .add_plugins(MinimalPlugins)
+            .insert_resource(profile)
+            .insert_resource(Paused::default())
+            .insert_resource(ShopDefinitions::default())
+            .insert_resource(WeaponDefinitions::default())
+            .insert_resource(characters)
+            .add_startup_system_to_stage(StartupStage::PreStartup, equip_profile.system())
+            .add_startup_system(spawn_players.system());
+        let mut app = app_builder.app;
+        app.update();
+
+        assert!(!app.world.get_resource::<Paused>().unwrap().0);
+        assert!(app.world.get_resource::<CharacterSelect>().is_none());
+    }
+
+    #[test]
+    fn bot_runs_pay_nothing_and_start_over() {
+        let mut app_builder = App::build();
+        app_builder
+            .add_plugins(MinimalPlugins)
+            .add_event::<game_coop::AllPlayersDownEvent>()
+            .add_event::<NewRunEvent>()
+            .insert_resource(Profile::default())
+            .insert_resource(Loadout::default())
+            .insert_resource(Paused::default())
+            .insert_resource(ShopDefinitions::default())
+            .insert_resource(WeaponDefinitions::default())
+            .insert_resource(CharacterDefinitions::default())
+            .insert_resource(Score(40))
+            .insert_resource(Wave(3))
+            .add_system(end_run.system());
+        let mut app = app_builder.app;
+        app.world.spawn().insert(PlayerId(0)).insert(Bot);
+        app.world.get_resource_mut::<Events<game_coop::AllPlayersDownEvent>>().unwrap().send(game_coop::AllPlayersDownEvent);
+        app.update();
+
+        assert_eq!(*app.world.get_resource::<Profile>().unwrap(), Profile::default());
+        assert!(app.world.get_resource::<ShopScreen>().is_none());
+        let new_runs = app.world.get_resource::<Events<NewRunEvent>>().unwrap();
+        assert_eq!(new_runs.get_reader().iter(new_runs).count(), 1);
+    }
+}
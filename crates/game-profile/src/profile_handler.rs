@@ -0,0 +1,225 @@
+use bevy::prelude::*;
+use game_coop::AllPlayersDownEvent;
+use game_data::*;
+use game_data::Direction as Direction;
+use crate::*;
+
+
+fn save_profile(profile: &Profile) {
+    match profile.save(PROFILE_PATH) {
+        Ok(()) => debug!("profile saved to {}", PROFILE_PATH),
+        Err(e) => error!("{}", e),
+    }
+}
+
+/// true once per press: the first frame `input` does anything after every button was let go
+fn pressed(input: &PlayerInput, ready: &mut bool) -> bool {
+    if input.aim.is_none() && !input.fire && !input.alt_fire {
+        *ready = true;
+        return false;
+    }
+    std::mem::replace(ready, false)
+}
+
+/// moves a cursor through `lines` lines, aiming up goes towards the first
+fn move_cursor(selected: usize, aim: Option<Direction>, lines: usize) -> usize {
+    match aim {
+        Some(Direction::North) => selected.saturating_sub(1),
+        Some(Direction::South) => (selected + 1).min(lines.saturating_sub(1)),
+        _ => selected,
+    }
+}
+
+/// The first run starts as the character the profile played last, with what it has bought too.
+/// Runs before `Startup` so `setup` spawns the players with it.
+pub fn equip_profile(
+    mut commands: Commands,
+    profile: Res<Profile>,
+    shop: Res<ShopDefinitions>,
+    weapons: Res<WeaponDefinitions>,
+    characters: Res<CharacterDefinitions>,
+) {
+    let loadout = profile.loadout(&shop, &weapons, &characters);
+    info!("playing as {}", profile.character(&characters).name);
+    commands.insert_resource(loadout);
+}
+
+/// Pays out the run once every player is down and opens the shop for the first human player.
+/// Runs only bots played, e.g. the attract mode, pay nothing and the next one starts straight
+/// away, as the same character. Without a HUD to draw the shop the next run starts straight away
+/// too, after the payout.
+pub fn end_run(
+    mut commands: Commands,
+    mut all_down_events: EventReader<AllPlayersDownEvent>,
+    players: Query<(Entity, &PlayerId, Option<&Bot>)>,
+    mut profile: ResMut<Profile>,
+    mut loadout: ResMut<Loadout>,
+    mut paused: ResMut<Paused>,
+    mut new_runs: EventWriter<NewRunEvent>,
+    shop: Res<ShopDefinitions>,
+    weapons: Res<WeaponDefinitions>,
+    characters: Res<CharacterDefinitions>,
+    score: Res<Score>,
+    wave: Res<Wave>,
+    hud: Option<Res<HudShown>>,
+) {
+    if all_down_events.iter().count() == 0 {
+        return;
+    }
+    let shopper = players.iter()
+        .filter(|(_, _, bot)| bot.is_none())
+        .min_by_key(|(_, id, _)| id.0)
+        .map(|(player, ..)| player);
+    let player = match shopper {
+        Some(player) => player,
+        None => {
+            info!("bot run over at wave {} with score {}, nothing paid out", wave.0, score.0);
+            start_next_run(&mut commands, &profile, &mut loadout, &mut new_runs, &shop, &weapons, &characters);
+            return;
+        }
+    };
+
+    let earned = profile.record_run(score.0, wave.0, &shop);
+    info!("run over at wave {} with score {}, earned {} ({} to spend)", wave.0, score.0, earned, profile.currency);
+    save_profile(&profile);
+    if hud.is_none() {
+        start_next_run(&mut commands, &profile, &mut loadout, &mut new_runs, &shop, &weapons, &characters);
+        return;
+    }
+    commands.insert_resource(ShopScreen::new(player, earned));
+    paused.0 = true;
+}
+
+fn start_next_run(
+    commands: &mut Commands,
+    profile: &Profile,
+    loadout: &mut Loadout,
+    new_runs: &mut EventWriter<NewRunEvent>,
+    shop: &ShopDefinitions,
+    weapons: &WeaponDefinitions,
+    characters: &CharacterDefinitions,
+) {
+    *loadout = profile.loadout(shop, weapons, characters);
+    commands.remove_resource::<ShopScreen>();
+    commands.remove_resource::<CharacterSelect>();
+    new_runs.send(NewRunEvent);
+}
+
+fn open_character_select(commands: &mut Commands, profile: &Profile, characters: &CharacterDefinitions) {
+    commands.remove_resource::<ShopScreen>();
+    commands.insert_resource(CharacterSelect::new(profile, characters));
+}
+
+/// Aim up and down picks a line, fire buys the item or equips the weapon on it, a bomb closes
+/// the shop for the character select.
+pub fn browse_shop(
+    mut commands: Commands,
+    screen: Option<ResMut<ShopScreen>>,
+    inputs: Query<&PlayerInput>,
+    mut profile: ResMut<Profile>,
+    shop: Res<ShopDefinitions>,
+    weapons: Res<WeaponDefinitions>,
+    characters: Res<CharacterDefinitions>,
+) {
+    let mut screen = match screen {
+        Some(screen) => screen,
+        None => return,
+    };
+    let input = match inputs.get(screen.player) {
+        Ok(input) => input,
+        Err(_) => {
+            debug!("player {:?} left the shop", screen.player);
+            open_character_select(&mut commands, &profile, &characters);
+            return;
+        }
+    };
+    if !pressed(input, &mut screen.ready) {
+        return;
+    }
+    if input.alt_fire {
+        open_character_select(&mut commands, &profile, &characters);
+        return;
+    }
+
+    let lines = shop_lines(&shop, &weapons);
+    screen.selected = move_cursor(screen.selected, input.aim, lines.len());
+    if !input.fire {
+        return;
+    }
+    match lines.get(screen.selected) {
+        Some(ShopLine::Item(index)) => {
+            let item = &shop.items[*index];
+            match profile.buy(item) {
+                Ok(cost) => {
+                    info!("bought {} for {} ({} left)", item.name, cost, profile.currency);
+                    save_profile(&profile);
+                },
+                Err(e) => debug!("can't buy {}: {}", item.name, e),
+            }
+        },
+        Some(ShopLine::Weapon(index)) => {
+            let weapon = &weapons.weapons[*index];
+            if profile.equip_weapon(&weapon.name, &weapons) {
+                info!("equipped {}", weapon.name);
+                save_profile(&profile);
+            }
+        },
+        None => (),
+    }
+}
+
+/// Aim up and down picks a character, fire starts the run as them once they are unlocked. The
+/// first human player picks for everyone; with only bots the run starts as the selected one.
+pub fn select_character(
+    mut commands: Commands,
+    screen: Option<ResMut<CharacterSelect>>,
+    players: Query<(&PlayerId, &PlayerInput, Option<&Bot>)>,
+    mut profile: ResMut<Profile>,
+    mut loadout: ResMut<Loadout>,
+    mut new_runs: EventWriter<NewRunEvent>,
+    shop: Res<ShopDefinitions>,
+    weapons: Res<WeaponDefinitions>,
+    characters: Res<CharacterDefinitions>,
+) {
+    let mut screen = match screen {
+        Some(screen) => screen,
+        None => return,
+    };
+    let chooser = players.iter()
+        .filter(|(_, _, bot)| bot.is_none())
+        .min_by_key(|(id, ..)| id.0)
+        .map(|(_, input, _)| input);
+    let chosen = match chooser {
+        Some(input) => {
+            if !pressed(input, &mut screen.ready) {
+                return;
+            }
+            screen.selected = move_cursor(screen.selected, input.aim, characters.characters.len());
+            if !input.fire {
+                return;
+            }
+            &characters.characters[screen.selected]
+        },
+        None => profile.character(&characters),
+    };
+    if !profile.choose_character(&chosen.name, &characters) {
+        debug!("{} is locked", chosen.name);
+        return;
+    }
+    info!("playing as {}", chosen.name);
+    save_profile(&profile);
+    start_next_run(&mut commands, &profile, &mut loadout, &mut new_runs, &shop, &weapons, &characters);
+}
+
+/// Tells `MenuOpen` readers, e.g. the music, whether the shop or the character select is up.
+pub fn track_menus(
+    mut menu: ResMut<MenuOpen>,
+    shop: Option<Res<ShopScreen>>,
+    character_select: Option<Res<CharacterSelect>>,
+) {
+    let open = shop.is_some() || character_select.is_some();
+    // only written on a change so readers can rely on change detection
+    if menu.0 != open {
+        menu.0 = open;
+    }
+}
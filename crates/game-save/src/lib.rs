@@ -0,0 +1,400 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use game_data::Direction as Direction;
+use game_data::FireMode;
+use save_handler::*;
+pub use snapshot::*;
+mod save_handler;
+mod snapshot;
+
+
+/// F5 writes the running session to `SAVE_PATH`, F9 replaces the running session with it.
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system(quick_save.exclusive_system())
+            .add_system(quick_load.exclusive_system());
+    }
+}
+
+pub const SAVE_PATH: &str = "saves/session.ron";
+/// bumped whenever `SessionSnapshot` changes shape; older files are refused instead of misread
+pub const SNAPSHOT_VERSION: u32 = 11;
+
+/// Everything needed to continue a run. Positions are world space pixels.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionSnapshot {
+    pub version: u32,
+    pub score: u32,
+    pub wave: u32,
+    pub rng: RngSnapshot,
+    pub players: Vec<PlayerSnapshot>,
+    pub enemies: Vec<EnemySnapshot>,
+    pub bullets: Vec<BulletSnapshot>,
+    pub pickups: Vec<PickupSnapshot>,
+    pub orbs: Vec<OrbSnapshot>,
+    pub bombs: Vec<BombSnapshot>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlayerSnapshot {
+    pub id: u8,
+    pub position: (f32, f32),
+    /// pixels per second
+    pub velocity: (f32, f32),
+    pub health: i32,
+    /// kills credited to this player when scoring is split
+    pub score: u32,
+    /// revive progress if the player is down
+    pub downed: Option<Duration>,
+    /// `Character` name, if they play one
+    pub character: Option<String>,
+    pub weapon: WeaponSnapshot,
+    pub progress: ProgressSnapshot,
+    /// levels gained whose upgrade isn't picked yet, including an open choice; offered again on load
+    pub pending_upgrades: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProgressSnapshot {
+    pub level: u32,
+    /// experience towards the next level
+    pub xp: u32,
+    /// `PlayerStats` as they were, including weapon and shop bonuses that aren't upgrades
+    pub max_health: i32,
+    pub damage: i32,
+    pub extra_projectiles: u32,
+    pub fire_rate: f32,
+    pub move_speed: f32,
+    pub acceleration: f32,
+    pub pickup_radius: f32,
+    /// upgrade names and how often each was taken, sorted by name
+    pub upgrades: Vec<(String, u32)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WeaponSnapshot {
+    pub direction: Direction,
+    pub fire_mode: FireMode,
+    /// time since the last shot while the weapon is cooling down, `None` once it is ready
+    pub cooldown_elapsed: Option<Duration>,
+    /// alternate fire ammo left
+    pub bombs: u32,
+    pub ammo: Option<AmmoSnapshot>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AmmoSnapshot {
+    pub loaded: u32,
+    pub reserve: Option<u32>,
+    /// progress of a running reload
+    pub reload_elapsed: Option<Duration>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnemySnapshot {
+    /// `EnemyKind` name, if it had one
+    pub kind: Option<String>,
+    pub position: (f32, f32),
+    /// pixels per second
+    pub velocity: (f32, f32),
+    pub health: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BulletSnapshot {
+    pub position: (f32, f32),
+    pub direction: Direction,
+    /// unit vector it travels along
+    pub heading: (f32, f32),
+    /// pixels per second
+    pub speed: f32,
+    pub lifetime_elapsed: Duration,
+    /// `PlayerId` of whoever fired it
+    pub owner: Option<u8>,
+    pub damage: i32,
+    /// pixels across, bigger than `BULLET_SIZE` for charged shots
+    pub size: f32,
+    /// turn rate in radians per second and range in pixels
+    pub homing: Option<(f32, f32)>,
+    pub pierce: u32,
+    pub ricochet: u32,
+    /// fragments and spread in radians
+    pub split: Option<(u32, f32)>,
+    /// radius in pixels and damage
+    pub explosive: Option<(f32, i32)>,
+}
+
+/// a dropped bomb waiting to go off
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BombSnapshot {
+    pub position: (f32, f32),
+    pub fuse: Duration,
+    pub fuse_elapsed: Duration,
+    pub radius: f32,
+    pub damage: i32,
+    /// `PlayerId` of whoever dropped it
+    pub owner: Option<u8>,
+}
+
+/// an ammo pickup lying around
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PickupSnapshot {
+    pub position: (f32, f32),
+    pub rounds: u32,
+}
+
+/// an experience orb lying around
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrbSnapshot {
+    pub position: (f32, f32),
+    pub xp: u32,
+}
+
+/// ChaCha state: the seed plus how far into the stream the game has read
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RngSnapshot {
+    pub seed: [u8; 32],
+    pub stream: u64,
+    pub word_pos: u128,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(PathBuf, std::io::Error),
+    Parse(PathBuf, ron::Error),
+    Serialize(ron::Error),
+    /// the file was written by a different snapshot version
+    Version { found: u32, expected: u32 },
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Io(path, e) => write!(f, "could not access {}: {}", path.display(), e),
+            SaveError::Parse(path, e) => write!(f, "could not parse {}: {}", path.display(), e),
+            SaveError::Serialize(e) => write!(f, "could not serialize session: {}", e),
+            SaveError::Version { found, expected } => {
+                write!(f, "save file is version {}, this build reads version {}", found, expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+/// only the version, read first so a changed layout is reported as such rather than as a parse error
+#[derive(Deserialize)]
+struct VersionHeader {
+    version: u32,
+}
+
+impl SessionSnapshot {
+    pub fn to_ron(&self) -> Result<String, SaveError> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).map_err(SaveError::Serialize)
+    }
+
+    pub fn from_ron(text: &str) -> Result<SessionSnapshot, SaveError> {
+        let header: VersionHeader = ron::from_str(text).map_err(|e| SaveError::Parse(PathBuf::new(), e))?;
+        if header.version != SNAPSHOT_VERSION {
+            return Err(SaveError::Version { found: header.version, expected: SNAPSHOT_VERSION });
+        }
+        ron::from_str(text).map_err(|e| SaveError::Parse(PathBuf::new(), e))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SaveError> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| SaveError::Io(parent.to_owned(), e))?;
+        }
+        std::fs::write(path, self.to_ron()?).map_err(|e| SaveError::Io(path.to_owned(), e))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<SessionSnapshot, SaveError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|e| SaveError::Io(path.to_owned(), e))?;
+        SessionSnapshot::from_ron(&text).map_err(|e| match e {
+            SaveError::Parse(_, e) => SaveError::Parse(path.to_owned(), e),
+            e => e,
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_rapier2d::physics::RapierConfiguration;
+    use game_data::*;
+    use game_data::Direction as Direction;
+    use rand::Rng;
+
+    /// with a magazine and reserve, so there is ammo to save
+    fn config() -> GameConfig {
+        let mut config = GameConfig::default();
+        config.weapon.ammo.magazine = Some(12);
+        config.weapon.ammo.reserve = Some(96);
+        config
+    }
+
+    fn empty_app() -> App {
+        let mut app_builder = App::build();
+        app_builder
+            .add_plugins(MinimalPlugins)
+            .insert_resource(RapierConfiguration::default())
+            .insert_resource(config());
+        app_builder.app
+    }
+
+    fn running_session() -> App {
+        let mut app = empty_app();
+        let world = &mut app.world;
+        let rapier_config = RapierConfiguration::default();
+        let config = config();
+
+        let mut rng = GameRng::from_seed(7);
+        let _: f32 = rng.0.gen();
+        world.insert_resource(rng);
+        world.insert_resource(Score(12));
+        world.insert_resource(Wave(3));
+
+        let mut cooldown = FireCooldown::new(config.weapon.fire_mode, config.weapon.fire_interval);
+        cooldown.set_elapsed(Duration::from_millis(40));
+        let upgrades = UpgradeDefinitions::default();
+        let mut stats = PlayerStats::new(&config);
+        stats.apply(upgrades.get("rapid fire").unwrap());
+        stats.apply(upgrades.get("rapid fire").unwrap());
+        stats.apply(upgrades.get("magnet").unwrap());
+        let mut ammo = Ammo::new(&config.weapon.ammo);
+        ammo.loaded = 0;
+        ammo.start_reload(&config.weapon.ammo);
+        ammo.tick(Duration::from_millis(300), &config.weapon.ammo);
+        let levelled_up = world.spawn()
+            .insert(Transform::from_xyz(10.0, -20.0, 0.0))
+            .insert_bundle(PlayerBundle::new(&rapier_config, &config, PlayerId(0), WorldPos(Vec2::new(10.0, -20.0))))
+            .insert(Player { velocity: Vec2::new(150.0, 0.0), ..Player::default() })
+            .insert(PlayerScore(5))
+            .insert(Health(2))
+            .insert(Direction::NorthWest)
+            .insert(cooldown)
+            .insert(ammo)
+            .insert(Experience { level: 3, xp: 4 })
+            .insert(stats)
+            .insert(Character("scout".to_owned()))
+            .id();
+        // one level waiting to be offered and one being picked
+        world.insert_resource(PendingLevelUps(vec![levelled_up]));
+        world.insert_resource(UpgradeChoice { player: levelled_up, options: vec![0, 1], ready: false });
+        world.insert_resource(Paused(true));
+        let mut revive = Timer::from_seconds(config.coop.revive_time, false);
+        revive.set_elapsed(Duration::from_millis(1200));
+        world.spawn()
+            .insert(Transform::from_xyz(-60.0, 0.0, 0.0))
+            .insert_bundle(PlayerBundle::new(&rapier_config, &config, PlayerId(1), WorldPos(Vec2::new(-60.0, 0.0))))
+            .insert(Health(0))
+            .insert(Downed { revive });
+        for (i, kind) in ["grunt", "runner"].iter().enumerate() {
+            let position = WorldPos(Vec2::new(200.0 + i as f32 * 50.0, 100.0));
+            world.spawn()
+                .insert(Transform::from_translation(position.0.extend(0.0)))
+                .insert_bundle(EnemyBundle::new(&rapier_config, position, PixelsPerSecond(Vec2::new(-40.0, i as f32 * 10.0)), 3 - i as i32))
+                .insert(EnemyKind(kind.to_string()));
+        }
+        let mut lifetime = Timer::from_seconds(config.weapon.bullet_lifetime, true);
+        lifetime.set_elapsed(Duration::from_millis(250));
+        world.spawn()
+            .insert(Transform::from_xyz(31.0, -20.0, 0.0))
+            .insert_bundle(ProjectileBundle::new(&rapier_config, WorldPos(Vec2::new(31.0, -20.0)), PixelsPerSecond(Direction::NorthEast.unit() * 600.0), Direction::NorthEast, 1.5))
+            .insert(Bullet(600.0))
+            .insert(BulletLifetime(lifetime.clone()))
+            .insert(Owner(levelled_up))
+            .insert(Damage(2))
+            .insert(Pierce(1))
+            .insert(Explosive { radius: 40.0, damage: 1 });
+        // a charged shot, bigger than the pooled bullets
+        world.spawn()
+            .insert(Transform::from_xyz(10.0, 0.0, 0.0))
+            .insert_bundle(ProjectileBundle::new(&rapier_config, WorldPos(Vec2::new(10.0, 0.0)), PixelsPerSecond(Vec2::new(0.0, 600.0)), Direction::North, 1.5).sized(&rapier_config, BULLET_SIZE * 2.5))
+            .insert(BulletLifetime(lifetime))
+            .insert(Damage(4));
+        let mut fuse = Timer::from_seconds(config.weapon.bomb.fuse, false);
+        fuse.set_elapsed(Duration::from_millis(500));
+        world.spawn()
+            .insert(Transform::from_xyz(10.0, -20.0, 0.0))
+            .insert(Bomb { fuse, radius: 120.0, damage: 5 })
+            .insert(Owner(levelled_up));
+        world.spawn()
+            .insert(Transform::from_xyz(-100.0, 40.0, 0.0))
+            .insert(Pickup)
+            .insert(AmmoPickup(24));
+        world.spawn()
+            .insert(Transform::from_xyz(80.0, 80.0, 0.0))
+            .insert(Pickup)
+            .insert(XpPickup(3));
+        app
+    }
+
+    #[test]
+    fn session_survives_a_round_trip() {
+        let mut app = running_session();
+        let snapshot = SessionSnapshot::capture(&mut app.world);
+        assert_eq!(snapshot.players.len(), 2);
+        assert!(snapshot.players[1].downed.is_some());
+        assert_eq!(snapshot.players[0].progress.upgrades, vec![("magnet".to_owned(), 1), ("rapid fire".to_owned(), 2)]);
+        assert_eq!(snapshot.players[0].character.as_deref(), Some("scout"));
+        assert_eq!(snapshot.enemies.len(), 2);
+        assert_eq!(snapshot.enemies[1].velocity, (-40.0, 10.0));
+        assert_eq!(snapshot.bullets.len(), 2);
+        assert_eq!(snapshot.bullets[0].size, BULLET_SIZE * 2.5);
+        assert_eq!((snapshot.bullets[1].owner, snapshot.bullets[1].damage, snapshot.bullets[1].pierce), (Some(0), 2, 1));
+        assert_eq!(snapshot.bombs.len(), 1);
+        assert_eq!(snapshot.bombs[0].owner, Some(0));
+        assert_eq!(snapshot.pickups, vec![PickupSnapshot { position: (-100.0, 40.0), rounds: 24 }]);
+        assert_eq!(snapshot.orbs, vec![OrbSnapshot { position: (80.0, 80.0), xp: 3 }]);
+        assert_eq!(snapshot.players[0].pending_upgrades, 2);
+
+        let restored_snapshot = SessionSnapshot::from_ron(&snapshot.to_ron().unwrap()).unwrap();
+        let mut restored = empty_app();
+        restored_snapshot.restore(&mut restored.world);
+        assert_eq!(SessionSnapshot::capture(&mut restored.world), snapshot);
+
+        let next = |app: &mut App| app.world.get_resource_mut::<GameRng>().unwrap().0.gen::<u64>();
+        assert_eq!(next(&mut app), next(&mut restored));
+    }
+
+    #[test]
+    fn loading_replaces_orbs_and_bombs_and_closes_the_upgrade_choice() {
+        let mut app = running_session();
+        let snapshot = SessionSnapshot::capture(&mut app.world);
+        snapshot.restore(&mut app.world);
+
+        assert_eq!(app.world.query::<&XpPickup>().iter(&app.world).count(), 1);
+        assert_eq!(app.world.query::<&Bomb>().iter(&app.world).count(), 1);
+        assert!(app.world.get_resource::<UpgradeChoice>().is_none());
+        assert!(!app.world.get_resource::<Paused>().unwrap().0);
+        // the levels are offered again, to the restored player
+        let pending = app.world.get_resource::<PendingLevelUps>().unwrap().0.clone();
+        assert_eq!(pending.len(), 2);
+        for player in pending {
+            assert_eq!(app.world.get::<PlayerId>(player), Some(&PlayerId(0)));
+        }
+    }
+
+    #[test]
+    fn other_versions_are_refused() {
+        let mut app = running_session();
+        let mut snapshot = SessionSnapshot::capture(&mut app.world);
+        snapshot.version = SNAPSHOT_VERSION + 1;
+        match SessionSnapshot::from_ron(&snapshot.to_ron().unwrap()) {
+            Err(SaveError::Version { found, expected }) => {
+                assert_eq!(found, SNAPSHOT_VERSION + 1);
+                assert_eq!(expected, SNAPSHOT_VERSION);
+            },
+            other => panic!("expected a version error, got {:?}", other),
+        }
+    }
+}
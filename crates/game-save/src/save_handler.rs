@@ -0,0 +1,28 @@
+use bevy::prelude::*;
+use crate::*;
+
+
+pub fn quick_save(world: &mut World) {
+    let pressed = world.get_resource::<Input<KeyCode>>().map_or(false, |input| input.just_pressed(KeyCode::F5));
+    if !pressed {
+        return;
+    }
+    match SessionSnapshot::capture(world).save(SAVE_PATH) {
+        Ok(()) => info!("session saved to {}", SAVE_PATH),
+        Err(e) => error!("{}", e),
+    }
+}
+
+pub fn quick_load(world: &mut World) {
+    let pressed = world.get_resource::<Input<KeyCode>>().map_or(false, |input| input.just_pressed(KeyCode::F9));
+    if !pressed {
+        return;
+    }
+    match SessionSnapshot::load(SAVE_PATH) {
+        Ok(snapshot) => {
+            snapshot.restore(world);
+            info!("session restored from {}", SAVE_PATH);
+        },
+        Err(e) => error!("{}", e),
+    }
+}
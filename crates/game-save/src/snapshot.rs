@@ -0,0 +1,394 @@
+use bevy::prelude::*;
+use bevy::ecs::system::CommandQueue;
+use bevy_rapier2d::physics::{ColliderHandleComponent, RapierConfiguration, RigidBodyHandleComponent};
+use bevy_rapier2d::rapier::dynamics::{RigidBodyBuilder, RigidBodySet};
+use bevy_rapier2d::rapier::geometry::{ColliderBuilder, ColliderSet};
+use std::collections::HashMap;
+use rand_chacha::ChaCha8Rng;
+use rand_chacha::rand_core::SeedableRng;
+use game_animation::*;
+use game_data::*;
+use game_data::Direction as Direction;
+use game_pool::*;
+use crate::*;
+
+
+fn pair(v: Vec3) -> (f32, f32) {
+    (v.x, v.y)
+}
+
+/// Bodies the physics hasn't built yet, e.g. ones restored this frame, still carry their builder.
+fn enemy_velocity(units: Units, bodies: Option<&RigidBodySet>, handle: Option<&RigidBodyHandleComponent>, builder: Option<&RigidBodyBuilder>) -> (f32, f32) {
+    let linvel = handle
+        .zip(bodies)
+        .and_then(|(handle, bodies)| bodies.get(handle.handle()))
+        .map(|rb| *rb.linvel())
+        .or_else(|| builder.map(|builder| *builder.build().linvel()));
+    let velocity = linvel.map_or(Vec2::ZERO, |linvel| units.velocity_to_world(PhysicsVelocity::from_vector(&linvel)).0);
+    (velocity.x, velocity.y)
+}
+
+/// Pixels across a bullet's square collider, built or still a builder.
+fn bullet_size(units: Units, colliders: Option<&ColliderSet>, handle: Option<&ColliderHandleComponent>, builder: Option<&ColliderBuilder>) -> f32 {
+    let half_width = handle
+        .zip(colliders)
+        .and_then(|(handle, colliders)| colliders.get(handle.handle()))
+        .and_then(|collider| collider.shape().as_cuboid().map(|cuboid| cuboid.half_extents.x))
+        .or_else(|| builder.and_then(|builder| builder.shape.as_cuboid().map(|cuboid| cuboid.half_extents.x)));
+    half_width.map_or(BULLET_SIZE, |half_width| half_width * 2.0 * units.scale)
+}
+
+fn by_position(a: &(f32, f32), b: &(f32, f32)) -> std::cmp::Ordering {
+    a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal)
+}
+
+impl RngSnapshot {
+    pub fn new(rng: &ChaCha8Rng) -> Self {
+        RngSnapshot {
+            seed: rng.get_seed(),
+            stream: rng.get_stream(),
+            word_pos: rng.get_word_pos(),
+        }
+    }
+}
+
+impl ProgressSnapshot {
+    pub fn new(experience: Experience, stats: &PlayerStats) -> Self {
+        let mut upgrades = stats.taken.iter().map(|(name, count)| (name.clone(), *count)).collect::<Vec<_>>();
+        upgrades.sort();
+        ProgressSnapshot {
+            level: experience.level,
+            xp: experience.xp,
+            max_health: stats.max_health,
+            damage: stats.damage,
+            extra_projectiles: stats.extra_projectiles,
+            fire_rate: stats.fire_rate,
+            move_speed: stats.move_speed,
+            acceleration: stats.acceleration,
+            pickup_radius: stats.pickup_radius,
+            upgrades,
+        }
+    }
+
+    pub fn stats(&self) -> PlayerStats {
+        PlayerStats {
+            max_health: self.max_health,
+            damage: self.damage,
+            extra_projectiles: self.extra_projectiles,
+            fire_rate: self.fire_rate,
+            move_speed: self.move_speed,
+            acceleration: self.acceleration,
+            pickup_radius: self.pickup_radius,
+            taken: self.upgrades.iter().cloned().collect(),
+        }
+    }
+}
+
+impl SessionSnapshot {
+    /// Reads the run out of `world`. Parked and dying entities are left out.
+    pub fn capture(world: &mut World) -> SessionSnapshot {
+        let score = world.get_resource::<Score>().map_or(0, |score| score.0);
+        let wave = world.get_resource::<Wave>().map_or(1, |wave| wave.0);
+        let rng = match world.get_resource::<GameRng>() {
+            Some(rng) => RngSnapshot::new(&rng.0),
+            None => RngSnapshot::new(&GameRng::default().0),
+        };
+
+        let config = world.get_resource::<GameConfig>().cloned().unwrap_or_default();
+        let default_stats = PlayerStats::new(&config);
+        let mut pending = world.get_resource::<PendingLevelUps>().map_or(vec![], |pending| pending.0.clone());
+        pending.extend(world.get_resource::<UpgradeChoice>().map(|choice| choice.player));
+        let mut players = world
+            .query_filtered::<(Entity, &Transform, &Player, &PlayerId, &Health, &Direction, Option<&FireCooldown>, Option<&Bombs>, Option<&Ammo>, Option<&PlayerScore>, Option<&Downed>, Option<&Experience>, Option<&PlayerStats>, Option<&Character>), ()>()
+            .iter(world)
+            .map(|(entity, transform, player, id, health, direction, cooldown, bombs, ammo, score, downed, experience, stats, character)| PlayerSnapshot {
+                id: id.0,
+                position: pair(transform.translation),
+                velocity: (player.velocity.x, player.velocity.y),
+                health: health.0,
+                score: score.map_or(0, |score| score.0),
+                downed: downed.map(|downed| downed.revive.elapsed()),
+                character: character.map(|character| character.0.clone()),
+                weapon: WeaponSnapshot {
+                    direction: *direction,
+                    fire_mode: cooldown.map_or(config.weapon.fire_mode, |cooldown| cooldown.mode),
+                    // a ready weapon has nothing to restore
+                    cooldown_elapsed: cooldown.filter(|cooldown| !cooldown.is_ready()).map(FireCooldown::elapsed),
+                    bombs: bombs.map_or(0, |bombs| bombs.left),
+                    ammo: ammo.map(|ammo| AmmoSnapshot {
+                        loaded: ammo.loaded,
+                        reserve: ammo.reserve,
+                        reload_elapsed: ammo.reload_elapsed(),
+                    }),
+                },
+                progress: ProgressSnapshot::new(experience.copied().unwrap_or_default(), stats.unwrap_or(&default_stats)),
+                pending_upgrades: pending.iter().filter(|player| **player == entity).count() as u32,
+            })
+            .collect::<Vec<_>>();
+        players.sort_by_key(|player| player.id);
+
+        let units = world.get_resource::<RapierConfiguration>().map_or(Units { scale: 1.0 }, Units::new);
+        let mut enemy_query = world.query_filtered::<(&Transform, &Health, Option<&EnemyKind>, Option<&RigidBodyHandleComponent>, Option<&RigidBodyBuilder>), (With<Enemy>, Without<Inactive>, Without<Dying>)>();
+        let bodies = world.get_resource::<RigidBodySet>();
+        let mut enemies = enemy_query
+            .iter(world)
+            .map(|(transform, health, kind, handle, builder)| EnemySnapshot {
+                kind: kind.map(|kind| kind.0.clone()),
+                position: pair(transform.translation),
+                velocity: enemy_velocity(units, bodies, handle, builder),
+                health: health.0,
+            })
+            .collect::<Vec<_>>();
+        enemies.sort_by(|a, b| by_position(&a.position, &b.position));
+
+        let player_ids = world
+            .query::<(Entity, &PlayerId)>()
+            .iter(world)
+            .map(|(entity, id)| (entity, id.0))
+            .collect::<HashMap<_, _>>();
+        let owner_id = |owner: Option<&Owner>| owner.and_then(|owner| player_ids.get(&owner.0).copied());
+        let mut bullet_query = world.query_filtered::<(&Transform, &Direction, &Bullet, &BulletLifetime, Option<&Heading>, Option<&Owner>, Option<&Damage>, Option<&Homing>, Option<&Pierce>, Option<&Ricochet>, Option<&Split>, Option<&Explosive>, Option<&ColliderHandleComponent>, Option<&ColliderBuilder>), Without<Inactive>>();
+        let colliders = world.get_resource::<ColliderSet>();
+        let mut bullets = bullet_query
+            .iter(world)
+            .map(|(transform, direction, bullet, lifetime, heading, owner, damage, homing, pierce, ricochet, split, explosive, handle, builder)| {
+                let heading = heading.map_or(direction.unit(), |heading| heading.0);
+                BulletSnapshot {
+                    position: pair(transform.translation),
+                    direction: *direction,
+                    heading: (heading.x, heading.y),
+                    speed: bullet.0,
+                    lifetime_elapsed: lifetime.0.elapsed(),
+                    owner: owner_id(owner),
+                    damage: damage.map_or(1, |damage| damage.0),
+                    size: bullet_size(units, colliders, handle, builder),
+                    homing: homing.map(|homing| (homing.turn_rate, homing.range)),
+                    pierce: pierce.map_or(0, |pierce| pierce.0),
+                    ricochet: ricochet.map_or(0, |ricochet| ricochet.0),
+                    split: split.map(|split| (split.fragments, split.spread)),
+                    explosive: explosive.map(|explosive| (explosive.radius, explosive.damage)),
+                }
+            })
+            .collect::<Vec<_>>();
+        bullets.sort_by(|a, b| by_position(&a.position, &b.position));
+
+        let mut pickups = world
+            .query::<(&Transform, &AmmoPickup)>()
+            .iter(world)
+            .map(|(transform, pickup)| PickupSnapshot { position: pair(transform.translation), rounds: pickup.0 })
+            .collect::<Vec<_>>();
+        pickups.sort_by(|a, b| by_position(&a.position, &b.position));
+
+        let mut orbs = world
+            .query::<(&Transform, &XpPickup)>()
+            .iter(world)
+            .map(|(transform, orb)| OrbSnapshot { position: pair(transform.translation), xp: orb.0 })
+            .collect::<Vec<_>>();
+        orbs.sort_by(|a, b| by_position(&a.position, &b.position));
+
+        let mut bombs = world
+            .query::<(&Transform, &Bomb, Option<&Owner>)>()
+            .iter(world)
+            .map(|(transform, bomb, owner)| BombSnapshot {
+                position: pair(transform.translation),
+                fuse: bomb.fuse.duration(),
+                fuse_elapsed: bomb.fuse.elapsed(),
+                radius: bomb.radius,
+                damage: bomb.damage,
+                owner: owner_id(owner),
+            })
+            .collect::<Vec<_>>();
+        bombs.sort_by(|a, b| by_position(&a.position, &b.position));
+
+        SessionSnapshot {
+            version: SNAPSHOT_VERSION,
+            score,
+            wave,
+            rng,
+            players,
+            enemies,
+            bullets,
+            pickups,
+            orbs,
+            bombs,
+        }
+    }
+
+    /// Replaces the run in `world` with this snapshot. Existing players, enemies, bullets, bombs
+    /// and pickups are despawned, pools are emptied and the restored enemies and bullets become their
+    /// new members, apart from charged bullets which are too big for the pool.
+    /// An open upgrade choice is closed and the game unpaused; levels not picked yet are queued again.
+    /// Works on a fresh `App` as long as `RapierConfiguration` is present; sprites are only added
+    /// when `SpriteSheets` and `GameMaterials` are, character passives when `CharacterDefinitions` is.
+    pub fn restore(&self, world: &mut World) {
+        let existing = world
+            .query_filtered::<Entity, Or<(With<Player>, With<Enemy>, With<Bullet>, With<Bomb>, With<Pickup>)>>()
+            .iter(world)
+            .collect::<Vec<_>>();
+        for entity in existing {
+            world.despawn(entity);
+        }
+        let pooled = world.get_resource::<EntityPool<Bullet>>().is_some();
+        if pooled {
+            world.insert_resource(EntityPool::<Bullet>::default());
+            world.insert_resource(EntityPool::<Enemy>::default());
+        }
+
+        let mut rng = ChaCha8Rng::from_seed(self.rng.seed);
+        rng.set_stream(self.rng.stream);
+        rng.set_word_pos(self.rng.word_pos);
+        world.insert_resource(GameRng(rng));
+        world.insert_resource(Score(self.score));
+        world.insert_resource(Wave(self.wave));
+        world.insert_resource(EnemyCount(self.enemies.len() as i32));
+        world.remove_resource::<UpgradeChoice>();
+        if let Some(mut paused) = world.get_resource_mut::<Paused>() {
+            paused.0 = false;
+        }
+        let mut pending = PendingLevelUps::default();
+        let mut player_entities = HashMap::new();
+
+        let mut queue = CommandQueue::default();
+        let mut bullet_pool = EntityPool::<Bullet>::default();
+        let mut enemy_pool = EntityPool::<Enemy>::default();
+        {
+            let mut commands = Commands::new(&mut queue, world);
+            let rapier_config = world
+                .get_resource::<RapierConfiguration>()
+                .expect("restoring a session needs a RapierConfiguration");
+            let default_config = GameConfig::default();
+            let config = world.get_resource::<GameConfig>().unwrap_or(&default_config);
+            let visuals = world.get_resource::<SpriteSheets>().zip(world.get_resource::<GameMaterials>());
+            let characters = world.get_resource::<CharacterDefinitions>();
+
+            for player in self.players.iter() {
+                let position = WorldPos(player.position.into());
+                let stats = player.progress.stats();
+                let mut player_state = Player { velocity: player.velocity.into(), ..Player::from_config(&config.player) };
+                let mut cooldown = FireCooldown::new(player.weapon.fire_mode, config.weapon.fire_interval);
+                if let Some(elapsed) = player.weapon.cooldown_elapsed {
+                    cooldown.set_elapsed(elapsed);
+                }
+                stats.update_player(config, &mut player_state, &mut cooldown);
+                let character = player.character.as_deref().and_then(|name| characters.and_then(|characters| characters.get(name)));
+                let sheet = character.map_or("player", |character| character.sprite.as_str());
+                let mut entity_commands = spawn_visual(&mut commands, visuals, sheet, position, PLAYER_SIZE, |m| m.player.clone());
+                entity_commands.insert_bundle(PlayerBundle::new(rapier_config, config, PlayerId(player.id), position))
+                    .insert(player_state)
+                    .insert(cooldown)
+                    .insert(stats)
+                    .insert(Experience { level: player.progress.level, xp: player.progress.xp })
+                    .insert(Health(player.health))
+                    .insert(PlayerScore(player.score))
+                    .insert(Bombs::new(player.weapon.bombs))
+                    .insert(player.weapon.direction);
+                player_entities.insert(player.id, entity_commands.id());
+                for _ in 0..player.pending_upgrades {
+                    pending.0.push(entity_commands.id());
+                }
+                if let Some(name) = &player.character {
+                    entity_commands.insert(Character(name.clone()));
+                }
+                if let Some(regeneration) = character.and_then(CharacterDefinition::regeneration) {
+                    entity_commands.insert(regeneration);
+                }
+                if let Some(elapsed) = player.downed {
+                    let mut revive = Timer::from_seconds(config.coop.revive_time, false);
+                    revive.set_elapsed(elapsed);
+                    entity_commands.insert(Downed { revive });
+                }
+                if let Some(saved) = &player.weapon.ammo {
+                    let mut ammo = Ammo::new(&config.weapon.ammo);
+                    ammo.loaded = saved.loaded;
+                    ammo.reserve = saved.reserve;
+                    if let Some(elapsed) = saved.reload_elapsed {
+                        ammo.resume_reload(elapsed, &config.weapon.ammo);
+                    }
+                    entity_commands.insert(ammo);
+                }
+            }
+
+            for enemy in self.enemies.iter() {
+                let position = WorldPos(enemy.position.into());
+                let mut entity_commands = spawn_visual(&mut commands, visuals, "enemy", position, ENEMY_SIZE, |m| m.enemy.clone());
+                entity_commands.insert_bundle(EnemyBundle::new(rapier_config, position, PixelsPerSecond(enemy.velocity.into()), enemy.health));
+                if let Some(kind) = &enemy.kind {
+                    entity_commands.insert(EnemyKind(kind.clone()));
+                }
+                if pooled {
+                    entity_commands.insert(Pooled);
+                    enemy_pool.add();
+                }
+            }
+
+            for bullet in self.bullets.iter() {
+                let position = WorldPos(bullet.position.into());
+                let heading = Vec2::from(bullet.heading);
+                let velocity = PixelsPerSecond(heading * bullet.speed);
+                let charged = (bullet.size - BULLET_SIZE).abs() > 1e-3;
+                let mut lifetime = Timer::from_seconds(config.weapon.bullet_lifetime, true);
+                lifetime.set_elapsed(bullet.lifetime_elapsed);
+                let mut bundle = ProjectileBundle::new(rapier_config, position, velocity, bullet.direction, config.weapon.bullet_lifetime);
+                if charged {
+                    bundle = bundle.sized(rapier_config, bullet.size);
+                }
+                let mut entity_commands = spawn_visual(&mut commands, visuals, "bullet", position, bullet.size, |m| m.bullet.clone());
+                entity_commands.insert_bundle(bundle)
+                    .insert(Bullet(bullet.speed))
+                    .insert(Heading(heading))
+                    .insert(BulletLifetime(lifetime))
+                    .insert(Damage(bullet.damage));
+                if let Some(owner) = bullet.owner.and_then(|id| player_entities.get(&id)) {
+                    entity_commands.insert(Owner(*owner));
+                }
+                if let Some((turn_rate, range)) = bullet.homing {
+                    entity_commands.insert(Homing { turn_rate, range });
+                }
+                if bullet.pierce > 0 {
+                    entity_commands.insert(Pierce(bullet.pierce));
+                }
+                if bullet.ricochet > 0 {
+                    entity_commands.insert(Ricochet(bullet.ricochet));
+                }
+                if let Some((fragments, spread)) = bullet.split {
+                    entity_commands.insert(Split { fragments, spread });
+                }
+                if let Some((radius, damage)) = bullet.explosive {
+                    entity_commands.insert(Explosive { radius, damage });
+                }
+                if pooled && !charged {
+                    entity_commands.insert(Pooled);
+                    bullet_pool.add();
+                }
+            }
+
+            for bomb in self.bombs.iter() {
+                let mut fuse = Timer::new(bomb.fuse, false);
+                fuse.set_elapsed(bomb.fuse_elapsed);
+                let mut entity_commands = spawn_visual(&mut commands, visuals, "bomb", WorldPos(bomb.position.into()), BOMB_SIZE, |m| m.bomb.clone());
+                entity_commands.insert(Bomb { fuse, radius: bomb.radius, damage: bomb.damage });
+                if let Some(owner) = bomb.owner.and_then(|id| player_entities.get(&id)) {
+                    entity_commands.insert(Owner(*owner));
+                }
+            }
+
+            for pickup in self.pickups.iter() {
+                spawn_visual(&mut commands, visuals, "pickup", WorldPos(pickup.position.into()), PICKUP_SIZE, |m| m.pickup.clone())
+                    .insert(Pickup)
+                    .insert(AmmoPickup(pickup.rounds));
+            }
+
+            for orb in self.orbs.iter() {
+                spawn_visual(&mut commands, visuals, "xp", WorldPos(orb.position.into()), XP_ORB_SIZE, |m| m.xp.clone())
+                    .insert(Pickup)
+                    .insert(XpPickup(orb.xp));
+            }
+        }
+        queue.apply(world);
+        world.insert_resource(pending);
+        if pooled {
+            world.insert_resource(bullet_pool);
+            world.insert_resource(enemy_pool);
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! Neighbour lookups for every enemy, as separation steering or auto-aim would do them each frame:
+//! the grid index (rebuild included) against scanning every other enemy.
+use bevy::prelude::*;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use game_data::*;
+use game_spatial::*;
+
+const NEIGHBOUR_RADIUS: f32 = 60.0;
+const NEAREST: usize = 5;
+
+/// enemies spread over a 1280x720 arena
+fn enemies(count: usize) -> Vec<(Entity, Vec2)> {
+    let mut rng = ChaCha8Rng::seed_from_u64(count as u64);
+    (0..count)
+        .map(|i| (Entity::new(i as u32), Vec2::new(rng.gen_range(-640.0..640.0), rng.gen_range(-360.0..360.0))))
+        .collect()
+}
+
+fn build(enemies: &[(Entity, Vec2)]) -> SpatialIndex<Enemy> {
+    let mut index = SpatialIndex::default();
+    for (entity, position) in enemies.iter() {
+        index.insert(*entity, *position);
+    }
+    index
+}
+
+fn radius_queries(c: &mut Criterion) {
+    let mut group = c.benchmark_group("neighbours within radius");
+    for count in [1_000, 5_000, 10_000].iter() {
+        let enemies = enemies(*count);
+        group.bench_with_input(BenchmarkId::new("grid", count), &enemies, |b, enemies| {
+            b.iter(|| {
+                let index = build(enemies);
+                enemies.iter().map(|(_, position)| index.within_radius(*position, NEIGHBOUR_RADIUS).count()).sum::<usize>()
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("full scan", count), &enemies, |b, enemies| {
+            b.iter(|| {
+                enemies.iter()
+                    .map(|(_, position)| enemies.iter().filter(|(_, other)| other.distance_squared(*position) <= NEIGHBOUR_RADIUS * NEIGHBOUR_RADIUS).count())
+                    .sum::<usize>()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn nearest_queries(c: &mut Criterion) {
+    let mut group = c.benchmark_group("nearest enemies");
+    for count in [1_000, 5_000, 10_000].iter() {
+        let enemies = enemies(*count);
+        group.bench_with_input(BenchmarkId::new("grid", count), &enemies, |b, enemies| {
+            b.iter(|| {
+                let index = build(enemies);
+                enemies.iter().map(|(_, position)| index.nearest(*position, NEAREST).len()).sum::<usize>()
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("full scan", count), &enemies, |b, enemies| {
+            b.iter(|| {
+                enemies.iter()
+                    .map(|(_, position)| {
+                        let mut distances = enemies.iter().map(|(_, other)| other.distance_squared(*position)).collect::<Vec<_>>();
+                        distances.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                        distances.len().min(NEAREST)
+                    })
+                    .sum::<usize>()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, radius_queries, nearest_queries);
+criterion_main!(benches);
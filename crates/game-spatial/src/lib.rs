@@ -0,0 +1,225 @@
+use bevy::prelude::*;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use game_data::*;
+use spatial_handler::*;
+mod spatial_handler;
+
+
+/// Uniform grid indexes of enemies, players and pickups, rebuilt from `Transform`s at the start
+/// of every frame so AI can ask for neighbours without scanning every entity.
+pub struct SpatialPlugin;
+
+impl Plugin for SpatialPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(SpatialIndex::<Enemy>::default())
+            .insert_resource(SpatialIndex::<Player>::default())
+            .insert_resource(SpatialIndex::<Pickup>::default())
+            .add_system_to_stage(CoreStage::First, rebuild_index::<Enemy>.system())
+            .add_system_to_stage(CoreStage::First, rebuild_index::<Player>.system())
+            .add_system_to_stage(CoreStage::First, rebuild_index::<Pickup>.system());
+    }
+}
+
+/// pixels, about two enemies wide
+pub const DEFAULT_CELL_SIZE: f32 = 100.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpatialEntry {
+    pub entity: Entity,
+    pub position: Vec2,
+}
+
+type Cell = (i32, i32);
+
+/// Positions of the entities with marker component `T` that are still in play, bucketed into
+/// square cells of `cell_size` pixels.
+pub struct SpatialIndex<T> {
+    cell_size: f32,
+    cells: HashMap<Cell, Vec<SpatialEntry>>,
+    len: usize,
+    /// lowest and highest occupied cell on each axis
+    bounds: Option<(Cell, Cell)>,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Default for SpatialIndex<T> {
+    fn default() -> Self {
+        SpatialIndex::new(DEFAULT_CELL_SIZE)
+    }
+}
+
+impl<T> SpatialIndex<T> {
+    pub fn new(cell_size: f32) -> Self {
+        assert!(cell_size > 0.0, "cells need a size");
+        SpatialIndex {
+            cell_size,
+            cells: HashMap::new(),
+            len: 0,
+            bounds: None,
+            marker: PhantomData,
+        }
+    }
+
+    /// Empties the index but keeps the cells' allocations for the next rebuild.
+    pub fn clear(&mut self) {
+        for entries in self.cells.values_mut() {
+            entries.clear();
+        }
+        self.len = 0;
+        self.bounds = None;
+    }
+
+    pub fn insert(&mut self, entity: Entity, position: Vec2) {
+        let cell = self.cell(position);
+        self.cells.entry(cell).or_insert_with(Vec::new).push(SpatialEntry { entity, position });
+        self.len += 1;
+        self.bounds = Some(match self.bounds {
+            Some((min, max)) => ((min.0.min(cell.0), min.1.min(cell.1)), (max.0.max(cell.0), max.1.max(cell.1))),
+            None => (cell, cell),
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn cell(&self, position: Vec2) -> Cell {
+        ((position.x / self.cell_size).floor() as i32, (position.y / self.cell_size).floor() as i32)
+    }
+
+    /// entries inside the axis-aligned rectangle from `min` to `max`, edges included
+    pub fn within_rect(&self, min: Vec2, max: Vec2) -> impl Iterator<Item = &SpatialEntry> + '_ {
+        let (low, high) = match self.bounds {
+            // only visit occupied cells, a huge rectangle shouldn't cost a huge loop
+            Some((bounds_min, bounds_max)) => {
+                let (low, high) = (self.cell(min), self.cell(max));
+                ((low.0.max(bounds_min.0), low.1.max(bounds_min.1)), (high.0.min(bounds_max.0), high.1.min(bounds_max.1)))
+            },
+            None => ((0, 0), (-1, -1)),
+        };
+        (low.0..=high.0)
+            .flat_map(move |x| (low.1..=high.1).map(move |y| (x, y)))
+            .filter_map(move |cell| self.cells.get(&cell))
+            .flatten()
+            .filter(move |entry| {
+                entry.position.x >= min.x && entry.position.x <= max.x && entry.position.y >= min.y && entry.position.y <= max.y
+            })
+    }
+
+    /// entries at most `radius` away from `center`
+    pub fn within_radius(&self, center: Vec2, radius: f32) -> impl Iterator<Item = &SpatialEntry> + '_ {
+        let extent = Vec2::splat(radius);
+        self.within_rect(center - extent, center + extent)
+            .filter(move |entry| entry.position.distance_squared(center) <= radius * radius)
+    }
+
+    /// Up to `k` entries closest to `center`, nearest first. Searches outwards ring by ring of
+    /// cells and stops once no unvisited cell can hold anything closer.
+    pub fn nearest(&self, center: Vec2, k: usize) -> Vec<SpatialEntry> {
+        let (bounds_min, bounds_max) = match self.bounds {
+            Some(bounds) if k > 0 => bounds,
+            _ => return vec![],
+        };
+        let origin = self.cell(center);
+        let last_ring = [
+            origin.0 - bounds_min.0,
+            bounds_max.0 - origin.0,
+            origin.1 - bounds_min.1,
+            bounds_max.1 - origin.1,
+        ].iter().copied().max().unwrap_or(0).max(0);
+
+        let by_distance = |a: &SpatialEntry, b: &SpatialEntry| {
+            a.position.distance_squared(center)
+                .partial_cmp(&b.position.distance_squared(center))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        };
+        let mut found = vec![];
+        for ring in 0..=last_ring {
+            for x in origin.0 - ring..=origin.0 + ring {
+                for y in origin.1 - ring..=origin.1 + ring {
+                    if (x - origin.0).abs() != ring && (y - origin.1).abs() != ring {
+                        continue;
+                    }
+                    if let Some(entries) = self.cells.get(&(x, y)) {
+                        found.extend(entries.iter().copied());
+                    }
+                }
+            }
+            if found.len() >= k {
+                found.sort_by(by_distance);
+                // cells past this ring are at least `ring` whole cells away from `center`
+                let reach = ring as f32 * self.cell_size;
+                if found[k - 1].position.distance_squared(center) <= reach * reach {
+                    break;
+                }
+            }
+        }
+        found.sort_by(by_distance);
+        found.truncate(k);
+        found
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index(points: &[(f32, f32)]) -> SpatialIndex<Enemy> {
+        let mut index = SpatialIndex::new(50.0);
+        for (i, point) in points.iter().enumerate() {
+            index.insert(Entity::new(i as u32), Vec2::new(point.0, point.1));
+        }
+        index
+    }
+
+    fn ids<'a>(entries: impl IntoIterator<Item = &'a SpatialEntry>) -> Vec<u32> {
+        let mut ids = entries.into_iter().map(|entry| entry.entity.id()).collect::<Vec<_>>();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn radius_and_rect_queries_match_a_full_scan() {
+        let points = (0..400)
+            .map(|i| (((i * 37) % 613) as f32 - 300.0, ((i * 91) % 487) as f32 - 240.0))
+            .collect::<Vec<_>>();
+        let index = index(&points);
+        assert_eq!(index.len(), points.len());
+
+        let center = Vec2::new(12.0, -40.0);
+        let expected = (0..points.len() as u32)
+            .filter(|i| Vec2::new(points[*i as usize].0, points[*i as usize].1).distance(center) <= 120.0)
+            .collect::<Vec<_>>();
+        assert_eq!(ids(index.within_radius(center, 120.0)), expected);
+
+        let expected = (0..points.len() as u32)
+            .filter(|i| {
+                let (x, y) = points[*i as usize];
+                x >= -100.0 && x <= 25.0 && y >= 0.0 && y <= 230.0
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(ids(index.within_rect(Vec2::new(-100.0, 0.0), Vec2::new(25.0, 230.0))), expected);
+    }
+
+    #[test]
+    fn nearest_finds_the_closest_entries_in_order() {
+        let index = index(&[(0.0, 0.0), (400.0, 0.0), (-30.0, 10.0), (0.0, -1000.0), (120.0, 120.0)]);
+        let nearest = index.nearest(Vec2::new(-10.0, 0.0), 3);
+        assert_eq!(nearest.iter().map(|entry| entry.entity.id()).collect::<Vec<_>>(), vec![0, 2, 4]);
+        // far away from everything and asking for more than there is
+        assert_eq!(index.nearest(Vec2::new(5000.0, 5000.0), 10).len(), 5);
+        assert_eq!(index.nearest(Vec2::ZERO, 0), vec![]);
+
+        let mut empty = index;
+        empty.clear();
+        assert!(empty.is_empty());
+        assert_eq!(empty.within_radius(Vec2::ZERO, 1000.0).count(), 0);
+        assert_eq!(empty.nearest(Vec2::ZERO, 1), vec![]);
+    }
+}
@@ -0,0 +1,16 @@
+use bevy::prelude::*;
+use bevy::ecs::component::Component;
+use game_data::*;
+use crate::*;
+
+
+/// Parked, dying and downed entities are left out.
+pub fn rebuild_index<T: Component>(
+    mut index: ResMut<SpatialIndex<T>>,
+    entities: Query<(Entity, &Transform), (With<T>, Without<Inactive>, Without<Dying>, Without<Downed>)>,
+) {
+    index.clear();
+    for (entity, transform) in entities.iter() {
+        index.insert(entity, transform.translation.truncate());
+    }
+}
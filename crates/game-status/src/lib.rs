@@ -0,0 +1,59 @@
+use bevy::prelude::*;
+use game_data::*;
+use status_handler::*;
+mod status_handler;
+
+
+/// Counts down the `StatusEffects` on players and enemies and tints their sprites while an
+/// effect is on. Movement, firing and damage read the effects themselves. Flat fallback
+/// rectangles share one material per kind and stay untinted.
+pub struct StatusPlugin;
+
+impl Plugin for StatusPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_to_stage(CoreStage::PreUpdate, tick_status_effects.system().with_run_criteria(unpaused.system()))
+            .add_system(tint_sprites.system());
+    }
+}
+
+/// Sprite colour for the most noticeable effect: freeze, then stun, burn and slow.
+/// Knockbacks are too short to need one.
+pub fn tint(effects: &StatusEffects) -> Color {
+    let mut tint = None;
+    for active in effects.iter() {
+        let (priority, color) = match active.effect {
+            StatusEffect::Freeze => (4, Color::rgb(0.55, 0.8, 1.0)),
+            StatusEffect::Stun => (3, Color::rgb(1.0, 1.0, 0.45)),
+            StatusEffect::Burn { .. } => (2, Color::rgb(1.0, 0.55, 0.25)),
+            StatusEffect::Slow { .. } => (1, Color::rgb(0.7, 0.6, 1.0)),
+            StatusEffect::Knockback { .. } => continue,
+        };
+        if tint.map_or(true, |(best, _)| priority > best) {
+            tint = Some((priority, color));
+        }
+    }
+    tint.map_or(Color::WHITE, |(_, color)| color)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_most_noticeable_effect_picks_the_tint() {
+        let mut effects = StatusEffects::default();
+        assert_eq!(tint(&effects), Color::WHITE);
+        effects.apply(&TimedEffect { effect: StatusEffect::Knockback { speed: 200.0 }, seconds: 0.2 }, Vec2::X);
+        assert_eq!(tint(&effects), Color::WHITE);
+        effects.apply(&TimedEffect { effect: StatusEffect::Slow { fraction: 0.5 }, seconds: 2.0 }, Vec2::ZERO);
+        let slowed = tint(&effects);
+        assert_ne!(slowed, Color::WHITE);
+        effects.apply(&TimedEffect { effect: StatusEffect::Freeze, seconds: 1.0 }, Vec2::ZERO);
+        let frozen = tint(&effects);
+        assert_ne!(frozen, slowed);
+
+        effects.tick(std::time::Duration::from_secs_f32(1.5));
+        assert_eq!(tint(&effects), slowed);
+    }
+}
@@ -0,0 +1,31 @@
+use bevy::prelude::*;
+use game_data::*;
+use crate::*;
+
+
+/// Downed players lose whatever was on them.
+pub fn tick_status_effects(
+    mut query: Query<(&mut StatusEffects, Option<&Downed>), (Without<Inactive>, Without<Dying>)>,
+    time: Res<Time>,
+) {
+    for (mut effects, downed) in query.iter_mut() {
+        if downed.is_some() {
+            if !effects.is_empty() {
+                effects.clear();
+            }
+            continue;
+        }
+        if !effects.is_empty() {
+            effects.tick(time.delta());
+        }
+    }
+}
+
+pub fn tint_sprites(mut query: Query<(&StatusEffects, &mut TextureAtlasSprite)>) {
+    for (effects, mut sprite) in query.iter_mut() {
+        let color = tint(effects);
+        if sprite.color != color {
+            sprite.color = color;
+        }
+    }
+}
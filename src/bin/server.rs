@@ -0,0 +1,223 @@
+use bevy::prelude::*;
+use bevy::app::{AppExit, ScheduleRunnerSettings};
+use bevy::log::LogPlugin;
+use bevy::transform::TransformPlugin;
+use bevy_rapier2d::physics::RapierPhysicsPlugin;
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+use game_bot::*;
+use game_core::*;
+use game_data::*;
+use game_input::*;
+use game_net::*;
+
+
+const USAGE: &str = "usage: server [--server <addr>] [--players <n>] [--bots] [--script <file>] [--seed <n>] [--tick-rate <hz>] [--ticks <n>] [--stats <seconds>]";
+
+/// Runs the game without a window or renderer: as a dedicated server for network clients,
+/// for bots and for soak tests in CI. Local players only move when played by bots or an input script.
+fn main() {
+    let options = match ServerOptions::from_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let mut config = load_or_exit(GameConfig::load(asset_path(CONFIG_ASSET)));
+    if let Some(players) = options.players {
+        config.coop.players = players;
+    }
+    let enemy_definitions = load_or_exit(EnemyDefinitions::load(asset_path(ENEMY_DEFINITIONS_ASSET)));
+    let upgrade_definitions = load_or_exit(UpgradeDefinitions::load(asset_path(UPGRADE_DEFINITIONS_ASSET)));
+    let rng = options.seed.map_or_else(GameRng::default, GameRng::from_seed);
+
+    let mut app = App::build();
+    app.insert_resource(ScheduleRunnerSettings::run_loop(Duration::from_secs_f64(1.0 / options.tick_rate)))
+    .add_plugins(MinimalPlugins)
+    .add_plugin(LogPlugin)
+    .add_plugin(TransformPlugin)
+    .add_plugin(RapierPhysicsPlugin)
+    .insert_resource(config)
+    .insert_resource(enemy_definitions)
+    .insert_resource(upgrade_definitions)
+    .insert_resource(rng)
+    .add_plugin(GameplayPlugin)
+    .insert_resource(ServerStats::new(options.stats_interval))
+    .add_system(report_stats.system());
+
+    if options.bots {
+        app.add_plugin(BotPlugin { control_local_players: true });
+    }
+    if let Some(path) = &options.script {
+        match InputScript::load(path) {
+            Ok(script) => app.add_plugin(InputScriptPlugin(script)),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        };
+    }
+    if let Some(ticks) = options.ticks {
+        app.insert_resource(TickLimit(ticks))
+        .add_system(stop_after_ticks.system());
+    }
+
+    app.add_plugin(NetPlugin(options.net))
+    .run();
+}
+
+/// command line of the headless server
+#[derive(Debug, Clone, PartialEq)]
+struct ServerOptions {
+    net: NetMode,
+    /// local players, overrides `coop.players`. 0 leaves every slot to network clients.
+    players: Option<u8>,
+    /// local players are played by bots
+    bots: bool,
+    script: Option<PathBuf>,
+    seed: Option<u64>,
+    tick_rate: f64,
+    /// stop after this many ticks instead of running until killed
+    ticks: Option<u64>,
+    /// seconds between stats lines
+    stats_interval: f32,
+}
+
+impl ServerOptions {
+    fn from_args(args: impl IntoIterator<Item = String>) -> Result<ServerOptions, String> {
+        let args = args.into_iter().collect::<Vec<_>>();
+        let net = NetMode::from_args(args.clone())?;
+        if let NetMode::Client(_) = net {
+            return Err(format!("the headless server can't join another server\n{}", USAGE));
+        }
+        let mut options = ServerOptions {
+            net,
+            players: None,
+            bots: false,
+            script: None,
+            seed: None,
+            tick_rate: 60.0,
+            ticks: None,
+            stats_interval: 5.0,
+        };
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("{} needs a value\n{}", arg, USAGE));
+            match arg.as_str() {
+                "--server" => {
+                    value()?;
+                },
+                "--players" => options.players = Some(parse(&arg, value()?)?),
+                "--bots" => options.bots = true,
+                "--script" => options.script = Some(PathBuf::from(value()?)),
+                "--seed" => options.seed = Some(parse(&arg, value()?)?),
+                "--tick-rate" => options.tick_rate = parse(&arg, value()?)?,
+                "--ticks" => options.ticks = Some(parse(&arg, value()?)?),
+                "--stats" => options.stats_interval = parse(&arg, value()?)?,
+                _ => return Err(format!("unknown argument {}\n{}", arg, USAGE)),
+            }
+        }
+
+        if options.players.map_or(false, |players| players > MAX_PLAYERS) {
+            return Err(format!("--players can be at most {}", MAX_PLAYERS));
+        }
+        if !(options.tick_rate > 0.0) {
+            return Err("--tick-rate must be greater than 0".to_owned());
+        }
+        if !(options.stats_interval > 0.0) {
+            return Err("--stats must be greater than 0".to_owned());
+        }
+        Ok(options)
+    }
+}
+
+fn parse<T: FromStr>(arg: &str, value: String) -> Result<T, String> where T::Err: Display {
+    value.parse().map_err(|e| format!("{} {}: {}", arg, value, e))
+}
+
+struct TickLimit(u64);
+
+struct ServerStats {
+    timer: Timer,
+    ticks: u64,
+    ticks_at_last_report: u64,
+}
+
+impl ServerStats {
+    fn new(interval: f32) -> Self {
+        ServerStats {
+            timer: Timer::from_seconds(interval, true),
+            ticks: 0,
+            ticks_at_last_report: 0,
+        }
+    }
+}
+
+/// logs tick rate, entity counts, score and wave every `--stats` seconds
+fn report_stats(
+    mut stats: ResMut<ServerStats>,
+    players: Query<Option<&Downed>, With<Player>>,
+    enemies: Query<(), (With<Enemy>, Without<Inactive>)>,
+    bullets: Query<(), (With<Bullet>, Without<Inactive>)>,
+    score: Res<Score>,
+    wave: Res<Wave>,
+    time: Res<Time>,
+) {
+    stats.ticks += 1;
+    stats.timer.tick(time.delta());
+    if !stats.timer.finished() {
+        return;
+    }
+    let tick_rate = (stats.ticks - stats.ticks_at_last_report) as f32 / stats.timer.duration().as_secs_f32();
+    stats.ticks_at_last_report = stats.ticks;
+    let downed = players.iter().filter(|downed| downed.is_some()).count();
+    info!(
+        "tick {} | {:.1} ticks/s | players {} ({} down) | enemies {} | bullets {} | score {} | wave {}",
+        stats.ticks,
+        tick_rate,
+        players.iter().count(),
+        downed,
+        enemies.iter().count(),
+        bullets.iter().count(),
+        score.0,
+        wave.0,
+    );
+}
+
+fn stop_after_ticks(stats: Res<ServerStats>, limit: Res<TickLimit>, score: Res<Score>, mut exit: EventWriter<AppExit>) {
+    if stats.ticks >= limit.0 {
+        info!("stopping after {} ticks with score {}", stats.ticks, score.0);
+        exit.send(AppExit);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_owned).collect()
+    }
+
+    #[test]
+    fn options_come_from_the_command_line() {
+        let options = ServerOptions::from_args(args("--server 127.0.0.1:7777 --players 0 --seed 42 --bots --ticks 600")).unwrap();
+        assert_eq!(options.net, NetMode::Server("127.0.0.1:7777".parse().unwrap()));
+        assert_eq!(options.players, Some(0));
+        assert_eq!(options.seed, Some(42));
+        assert_eq!(options.ticks, Some(600));
+        assert!(options.bots);
+        assert_eq!(options.tick_rate, 60.0);
+
+        assert_eq!(ServerOptions::from_args(args("")).unwrap().net, NetMode::Offline);
+        assert!(ServerOptions::from_args(args("--client 127.0.0.1:7777")).is_err());
+        assert!(ServerOptions::from_args(args("--players 9")).is_err());
+        assert!(ServerOptions::from_args(args("--seed")).is_err());
+        assert!(ServerOptions::from_args(args("--fast")).is_err());
+    }
+}
@@ -1,455 +1,76 @@
 use bevy::prelude::*;
-use bevy_rapier2d::physics::{RapierPhysicsPlugin, RapierConfiguration,RigidBodyHandleComponent};
-use bevy_rapier2d::rapier::dynamics::{RigidBodyBuilder, RigidBodySet};
-// use bevy_rapier2d
-use bevy_rapier2d::rapier::geometry::{ColliderBuilder};
-use bevy_rapier2d::rapier::na::Vector2;
+use bevy_rapier2d::physics::RapierPhysicsPlugin;
 
-use rand::Rng;
 use std::time::Duration;
-use game_collisions::*;
+use game_audio::*;
+use game_animation::*;
+use game_bot::*;
+use game_config::*;
+use game_core::*;
+use game_hud::*;
+use game_save::*;
+use game_profile::*;
+use game_input::*;
+use game_net::*;
 use game_data::*;
-use game_data::Direction as Direction;
 
 
 fn main() {
-    App::build()
-    .add_plugins(DefaultPlugins)
-    .add_plugin(RapierPhysicsPlugin)
-    .add_plugin(CollisionPlugin)
-    .add_startup_system(setup.system())
-    .add_system(movement_system.system())
-    .add_system(spawn_bullet.system())
-    .add_system(move_bullets.system())
-    .add_system(move_enemies.system())
-    .add_system(spawn_enemies.system())
-    .add_system(despawn_bullets.system())
-    .insert_resource(BulletSpeedTimer(Timer::from_seconds(0.1, true)))
-    .insert_resource(EnemySpawnTimer(Timer::from_seconds(3.0, true)))
-    .insert_resource(EnemyCount(0))
-    .run();
-
-    //defaults to a window of 1280x720. 
-}
-
-
-fn setup(
-    mut commands: Commands,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    mut rapier_config: ResMut<RapierConfiguration>
-    
-) {
-    //spawn camera
-    commands.spawn_bundle(OrthographicCameraBundle::new_2d());
-    rapier_config.gravity = Vector2::zeros();
-
-    let sprite_size_x = 40.0;
-    let sprite_size_y = 40.0;
-
-    // While we want our sprite to look ~40 px square, we want to keep the physics units smaller
-    // to prevent float rounding problems. To do this, we set the scale factor in RapierConfiguration
-    // and divide our sprite_size by the scale.
-    rapier_config.scale = 20.0;
-    let collider_size_x = sprite_size_x / rapier_config.scale;
-    let collider_size_y = sprite_size_y / rapier_config.scale;
-
-
-    commands.spawn_bundle(SpriteBundle{
-        transform: Transform::from_translation(Vec3::new(0.0, 0.0, 0.0)),
-        material: materials.add(Color::WHITE.into()),
-        sprite: Sprite::new(Vec2::new(sprite_size_x, sprite_size_y)),
-        ..Default::default()
-    })
-    .insert(Player::default())
-    .insert(Direction::East)
-    .insert(RigidBodyBuilder::new_dynamic())
-    .insert(ColliderBuilder::cuboid(collider_size_x / 2.0, collider_size_y / 2.0));
-}
-
-
-fn movement_system(
-    mut player_query: Query<( &mut Player, &RigidBodyHandleComponent)>,
-    time: Res<Time>,
-    keyboard_input: Res<Input<KeyCode>>,
-    mut rigid_bodies: ResMut<RigidBodySet>,
-) {
-    let mut x = 0.0;
-    let mut y = 0.0;
-    if keyboard_input.pressed(KeyCode::W) {
-        y = 1.0;
-    } else if keyboard_input.pressed(KeyCode::S) {
-        y = -1.0;
-    }
-    if keyboard_input.pressed(KeyCode::A) {
-        x = -1.0
-    } else if keyboard_input.pressed(KeyCode::D) {
-        x = 1.0
-    } 
-    for ( mut player, rigid_body_component) in player_query.iter_mut() {
-        let mut x_velocity = 0.0;
-        let mut y_velocity = 0.0;
-        //speed * dt * direction + current_velocity = some value. 
-        //if that value is larger than the max_velocity, set it to max velocity
-        //if a player stops pushing, the player will accelerate in the opposite direction of current movement
-        x_velocity += player.velocity.x + player.acceleration * time.delta_seconds() * x;
-        y_velocity += player.velocity.y + player.acceleration * time.delta_seconds() * y;
-        // if abs value of velocity is greater than
-        if player.max_velocity < x_velocity.abs(){
-            debug!("before applying max x: {} ", x_velocity );
-            x_velocity = player.max_velocity*x; 
-            debug!("after applying max x: {} ", x_velocity );
-        } 
-        if player.max_velocity < y_velocity.abs() {
-            debug!("before applying max y: {} ", y_velocity );
-            y_velocity = player.max_velocity*y;
-            debug!("after applying max y: {} ", y_velocity );
-
-        }
-        debug!("before applying friction x: {} ", x_velocity );
-        debug!("before applying friction y: {} ", y_velocity );
-
-        x_velocity = apply_frictions(x_velocity);
-        y_velocity = apply_frictions(y_velocity);
-        
-        player.velocity.x = x_velocity;
-        debug!("x velocity: {}", x_velocity);
-        player.velocity.y = y_velocity;
-        debug!("y velocity: {}", y_velocity);
-        
-        if let Some(rb) = rigid_bodies.get_mut(rigid_body_component.handle()) {
-            rb.set_linvel( player.velocity , true);
-        }
-    }
-}
-/// using player position as origin of shot, fires into direction of latest arrowkey position
-/// spawns a projectile that despawns on hit or after time elapses
-fn spawn_bullet(
-    mut commands: Commands,
-    mut player_query: Query<(&Transform, &Player, &mut Timer, &mut Direction)>,
-    mut player_entity_query: Query<(&Player, Entity, &Transform, &mut Direction), Without<Timer>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    keyboard_input: Res<Input<KeyCode>>,
-    rapier_config: ResMut<RapierConfiguration>,
-    time: Res<Time>,
-) {
-    if keyboard_input.pressed(KeyCode::Space) {
-        for(_, entity, transform, last_direction) in player_entity_query.iter_mut() {
-            create_bullet(&mut commands, &rapier_config, &keyboard_input, transform, materials.add(Color::YELLOW.into()), last_direction);
-            commands.entity(entity).insert(Timer::from_seconds(0.15, true));
-            debug!("Inserted one timer and created a bullet");
-        }
-        for (transform, _, mut timer, last_direction) in player_query.iter_mut() {
-            debug!("ticking {}", time.delta_seconds());
-            timer.tick(Duration::from_secs_f32(time.delta_seconds()));
-            if timer.finished() {
-                debug!("Timer finished so I'm creating one bullet");
-                create_bullet(&mut commands, &rapier_config, &keyboard_input, transform, materials.add(Color::YELLOW.into()), last_direction);    
-            }
-        }
-        
-    }
-}
-
-fn create_enemy(
-    commands: &mut Commands,
-    rapier_config: &ResMut<RapierConfiguration>,
-    material: Handle<ColorMaterial>,
-    x_position: i32,
-    y_position: i32,
-) {
-    let sprite_size_x = 10.0;
-    let sprite_size_y = 10.0;
-    
-    let collider_size_x = sprite_size_x / rapier_config.scale;
-    let collider_size_y = sprite_size_y / rapier_config.scale;
-
-    commands
-        .spawn_bundle(SpriteBundle {
-            material,
-            transform: Transform::from_translation(Vec3::new(x_position as f32, y_position as f32, 0.0)),
-            sprite: Sprite::new(Vec2::new(sprite_size_x, sprite_size_y)),
-            ..Default::default()
-        })
-        .insert(Enemy)
-        .insert(RigidBodyBuilder::new_dynamic()
-        .translation(x_position as f32 / rapier_config.scale, y_position as f32 / rapier_config.scale))
-        .insert(ColliderBuilder::cuboid(collider_size_x/2., collider_size_y/2.));
-}
-
-fn create_bullet (
-    commands: & mut Commands, 
-    rapier_config: &ResMut<RapierConfiguration>, 
-    keyboard_input: &Res<Input<KeyCode>>,
-    transform: &Transform,
-    material: Handle<ColorMaterial>,
-    default_direction: Mut<Direction>,
-) {
-    let sprite_size_x = 5.0;
-    let sprite_size_y = 5.0;
-    // While we want our sprite to look ~40 px square, we want to keep the physics units smaller
-    // to prevent float rounding problems. To do this, we set the scale factor in RapierConfiguration
-    // and divide our sprite_size by the scale.
-    let collider_size_x = sprite_size_x / rapier_config.scale;
-    let collider_size_y = sprite_size_y / rapier_config.scale;
-
-    let direction = determine_direction(&keyboard_input, default_direction);
-    debug!("current x translation for player entity: {} y: {}", transform.translation.x, transform.translation.y );
-    
-    let translation = match direction {
-        Direction::North => {
-            Vec2::new(transform.translation.x, transform.translation.y  + 21.)
-        },Direction::NorthEast => {
-            Vec2::new(transform.translation.x + 21., transform.translation.y  + 21.)
-        },Direction::NorthWest => {
-            Vec2::new(transform.translation.x - 21., transform.translation.y  + 21.)
-        },Direction::South => {
-            Vec2::new(transform.translation.x , transform.translation.y  - 21.)
-        },Direction::SouthEast => {
-            Vec2::new(transform.translation.x + 21., transform.translation.y  - 21.)
-        }, Direction::Southwest => {
-            Vec2::new(transform.translation.x  - 21., transform.translation.y  - 21.)
-        }, Direction::East => {
-            Vec2::new(transform.translation.x  + 21., transform.translation.y )
-        }, Direction::West => {
-            Vec2::new(transform.translation.x  - 21., transform.translation.y )
+    let config = load_or_exit(GameConfig::load(asset_path(CONFIG_ASSET)));
+    let enemy_definitions = load_or_exit(EnemyDefinitions::load(asset_path(ENEMY_DEFINITIONS_ASSET)));
+    let upgrade_definitions = load_or_exit(UpgradeDefinitions::load(asset_path(UPGRADE_DEFINITIONS_ASSET)));
+    let weapon_definitions = load_or_exit(WeaponDefinitions::load(asset_path(WEAPON_DEFINITIONS_ASSET)));
+    let shop_definitions = load_or_exit(ShopDefinitions::load(asset_path(SHOP_DEFINITIONS_ASSET)));
+    let character_definitions = load_or_exit(CharacterDefinitions::load(asset_path(CHARACTER_DEFINITIONS_ASSET)));
+    let profile = match Profile::load(PROFILE_PATH) {
+        Ok(profile) => profile,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
         }
     };
-
-    commands
-        .spawn_bundle(SpriteBundle{
-            material,
-            transform: Transform::from_translation(Vec3::new(transform.translation.x, transform.translation.y, 0.)),
-            sprite: Sprite::new(Vec2::new(sprite_size_x, sprite_size_y)),
-            ..Default::default()
-        })
-        .insert(direction)
-        .insert(Bullet(30.))
-        .insert(RigidBodyBuilder::new_dynamic()
-        .translation(translation.x / rapier_config.scale, translation.y / rapier_config.scale))
-        .insert(ColliderBuilder::cuboid(collider_size_x/2., collider_size_y/2.))
-        .insert(BulletLifetime(Timer::from_seconds(1.5, true)));
-}
-
-fn move_bullets(
-    mut query_bullet: Query<(& Direction, & RigidBodyHandleComponent, &Bullet)>,
-    mut rigid_bodies: ResMut<RigidBodySet>,
-    mut timer: ResMut< BulletSpeedTimer>,
-    time: Res<Time>,
-) {
-    timer.0.tick(Duration::from_secs_f32(time.delta_seconds()));
-    if timer.0.finished() {
-        for (direction, rigid_body_handle, bullet) in query_bullet.iter_mut() {
-            debug!("Direction: {:?}", direction);
-            match direction {
-                Direction::North => {
-                    if let Some(rb) = rigid_bodies.get_mut(rigid_body_handle.handle()) {
-                        rb.set_linvel(Vector2::new(0.0, bullet.0), true);
-                    }
-                },Direction::NorthEast => {
-                    if let Some(rb) = rigid_bodies.get_mut(rigid_body_handle.handle()) {
-                        rb.set_linvel(Vector2::new(bullet.0 * 0.7, bullet.0 * 0.7), true);
-                    }
-                },Direction::NorthWest => {
-                    if let Some(rb) = rigid_bodies.get_mut(rigid_body_handle.handle()) {
-                        rb.set_linvel(Vector2::new(-bullet.0 * 0.7, bullet.0 * 0.7), true);
-                    }
-                },Direction::South => {
-                    if let Some(rb) = rigid_bodies.get_mut(rigid_body_handle.handle()) {
-                        rb.set_linvel(Vector2::new(0.0, -bullet.0), true);
-                    }
-                },Direction::SouthEast => {
-                    if let Some(rb) = rigid_bodies.get_mut(rigid_body_handle.handle()) {
-                        rb.set_linvel(Vector2::new(bullet.0 * 0.7, -bullet.0 * 0.7), true);
-                    }
-                }, Direction::Southwest => {
-                    if let Some(rb) = rigid_bodies.get_mut(rigid_body_handle.handle()) {
-                        rb.set_linvel(Vector2::new(-bullet.0 * 0.7, -bullet.0 * 0.7), true);
-                    }
-                }, Direction::East => {
-                    if let Some(rb) = rigid_bodies.get_mut(rigid_body_handle.handle()) {
-                        rb.set_linvel(Vector2::new(bullet.0, 0.0), true);
-                    }
-                }, Direction::West => {
-                    if let Some(rb) = rigid_bodies.get_mut(rigid_body_handle.handle()) {
-                        rb.set_linvel(Vector2::new(-bullet.0, 0.0), true);
-                    }
-                }
-            }
+    let net_mode = match NetMode::from_args(std::env::args().skip(1)) {
+        Ok(net_mode) => net_mode,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
         }
-    }
-}
+    };
 
-fn despawn_bullets(
-    mut commands: Commands,
-    mut bullet_query: Query<(&mut BulletLifetime, Entity)>,
-    time: Res<Time>,
-) {
-    for (mut bullet_timer, entity) in bullet_query.iter_mut() {
-        bullet_timer.0.tick(Duration::from_secs_f32(time.delta_seconds()));
-        if bullet_timer.0.finished() {
-            debug!("Despawning a bullet");
-            commands.entity(entity).despawn();
-        }
+    let mut app = App::build();
+    app.add_plugins(DefaultPlugins)
+    .add_plugin(RapierPhysicsPlugin)
+    .add_plugin(GameAudioPlugin)
+    .add_plugin(AnimationPlugin)
+    .add_plugin(ConfigPlugin)
+    .add_plugin(PlayerInputPlugin)
+    .add_plugin(HudPlugin)
+    .add_startup_system(setup_camera.system())
+    .insert_resource(config)
+    .insert_resource(enemy_definitions)
+    .insert_resource(upgrade_definitions)
+    .insert_resource(weapon_definitions)
+    .insert_resource(shop_definitions)
+    .insert_resource(character_definitions)
+    .insert_resource(profile);
+
+    // clients draw what the server sends them instead of running the game themselves
+    if net_mode.simulates() {
+        app.add_plugin(GameplayPlugin)
+        .add_plugin(SavePlugin)
+        .add_plugin(ProfilePlugin)
+        .add_plugin(BotPlugin { control_local_players: false })
+        .add_plugin(AttractModePlugin { idle: Duration::from_secs(30) });
+    } else {
+        app.add_plugin(GameEventsPlugin);
     }
-}
 
-///generates a random number that is outside of the range of the player position plus some buffer distance
-fn spawn_enemies(
-    mut commands: Commands,
-    player_position_query: Query<&Transform, With<Player>>,
-    mut enemy_count: ResMut<EnemyCount>,
-    mut enemy_spawn_timer: ResMut<EnemySpawnTimer>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    rapier_config: ResMut<RapierConfiguration>,
-    time: Res<Time>,
-) {
-    //get player position, generate random number around that position, spawn the enemy there, 
-    // use timer and enemy count to decide when to spawn
-    enemy_spawn_timer.0.tick(Duration::from_secs_f32(time.delta_seconds()));
-    if enemy_spawn_timer.0.finished() && enemy_count.0 < 20 {
-        debug!("timer finished");
-        for transform in player_position_query.iter() {
-            let (x_position, y_position) = generate_xy_values(&transform);
-            debug!("Spawn at pos x: {}, pos y: {}", x_position, y_position);
-            create_enemy(&mut commands, &rapier_config, materials.add(Color::RED.into()), x_position, y_position);
-            enemy_count.0 +=1;
-        }   
-    }
-}
-///Enemies will know where player is and move towards that direction
-/// TODO: Maybe some types of enemies move in different ways.
-fn move_enemies(
-    player_position_query: Query<&Transform, With<Player>>,
-    enemies_query: Query<(&RigidBodyHandleComponent, &Transform), With<Enemy>>,
-    mut rigid_bodies: ResMut<RigidBodySet>
-) {
-    for player_transform in player_position_query.iter() {
-        for (rigid_body_handle, enemy_transform) in enemies_query.iter() {
-            if let Some(rb) = rigid_bodies.get_mut(rigid_body_handle.handle()) {
-                //Should move towards player with some fuzzy logic added
-                //if transform.translation.x > 0, x_force = 5.0, else x_force = -5.0, 
-                let mut x_force = 5.0;
-                let mut y_force = 5.0;
-                if player_transform.translation.x < enemy_transform.translation.x {
-                    x_force = -5.0;
-                }
-                if player_transform.translation.y < enemy_transform.translation.y {
-                    y_force = -5.0;
-                }
-                rb.set_linvel(Vector2::new(x_force, y_force), true)
-            }
-        }
-    }
-}
+    app.add_plugin(NetPlugin(net_mode))
+    .run();
 
-fn generate_xy_values(transform: &Transform) -> (i32, i32) {
-    let window_max_x= 640;
-    let window_max_y= 360;
-    let window_min_x = -window_max_x;
-    let window_min_y = -window_max_y;
-    let mut rng = rand::thread_rng();
-    debug!("translation.x: {}, translation.y: {}", transform.translation.x, transform.translation.y);
-    let mut x = 0;
-    let mut is_x_valid = false;
-    if transform.translation.x as i32 + 50 < window_max_x {
-        x = rng.gen_range(transform.translation.x as i32 + 50.. window_max_x);
-        is_x_valid = true;
-    }
-    let mut x2 = 0;
-    let mut is_x2_valid = false;
-    if transform.translation.x as i32 -50 > window_min_x {
-        x2 = rng.gen_range(window_min_x.. transform.translation.x as i32 + 50);
-        is_x2_valid = true;
-    }
-    let mut y = 0;
-    let mut is_y_valid = false;
-    if transform.translation.y as i32 + 50 < window_max_y {
-        y = rng.gen_range(transform.translation.y as i32 + 50..window_max_y);
-        is_y_valid = true;
-    }
-    let mut y2 = 0;
-    let mut is_y2_valid = false;
-    if transform.translation.y as i32 - 50 > window_min_y {
-        y2 = rng.gen_range(window_min_y..transform.translation.y as i32) - 50;
-        is_y2_valid = true;
-    }
-    let x_pair = [x, x2];
-    let y_pair = [y, y2];
-    // pick between one of the two x values, as long as the value is within range of 0..1280 for x and 0..720 for y.
-    let choose_x = rng.gen_range(0usize..2usize);
-    let choose_y = rng.gen_range(0usize..2usize);
-    let mut x_position = 0;
-    let mut y_position = 0;
-    if is_x_valid && is_x2_valid {
-        x_position = x_pair[choose_x];
-    } else if is_x_valid {
-        x_position = x;
-    } else if is_x2_valid {
-        x_position = x2;
-    }
-    if is_y_valid && is_y2_valid {
-        y_position = y_pair[choose_y];
-    } else if is_y_valid {
-        y_position = y;
-    } else if is_y2_valid {
-        y_position = y2;
-    }
-    debug!("x_position: {}, y_position: {}, translation.x: {}, translation.y: {}", x_position, y_position, transform.translation.x, transform.translation.y);
-    (x_position, y_position)
+    //defaults to a window of 1280x720. 
 }
 
-///TODO: Don't think it's proper to pass and mutate the past_direction here. 
-/// logic should be above this to generalize this method for later uses
-fn determine_direction(keyboard_input: &Res<Input<KeyCode>>, mut past_direction: Mut<Direction>) -> Direction {
-    let mut  latest_direction = *past_direction;
-    if keyboard_input.pressed(KeyCode::Up) 
-    && !(keyboard_input.pressed(KeyCode::Left)  || keyboard_input.pressed(KeyCode::Right)) {
-        latest_direction = Direction::North;
-    } else if keyboard_input.pressed(KeyCode::Down)
-    && !(keyboard_input.pressed(KeyCode::Left) || keyboard_input.pressed(KeyCode::Right)) {
-        latest_direction = Direction::South;
-    } else if keyboard_input.pressed(KeyCode::Up) && keyboard_input.pressed(KeyCode::Left) {
-        latest_direction =  Direction::NorthWest;
-    } else if keyboard_input.pressed(KeyCode::Up) && keyboard_input.pressed(KeyCode::Right) {
-        latest_direction = Direction::NorthEast;
-    } else if keyboard_input.pressed(KeyCode::Down) && keyboard_input.pressed(KeyCode::Right) {
-        latest_direction = Direction::SouthEast;
-    } else if keyboard_input.pressed(KeyCode::Down) && keyboard_input.pressed(KeyCode::Left) {
-        latest_direction = Direction::Southwest;
-    } else if keyboard_input.pressed(KeyCode::Left) {
-        latest_direction = Direction::West;
-    } else if keyboard_input.pressed(KeyCode::Right) {
-        latest_direction = Direction::East;
-    }
-        *past_direction = latest_direction;
-        latest_direction
-        
+fn setup_camera(mut commands: Commands) {
+    commands.spawn_bundle(OrthographicCameraBundle::new_2d());
 }
-
-
-
-pub fn apply_frictions( mut velocity: f32 ) -> f32 {
-    let friction_force = 0.02;
-    if velocity.abs() > 0.0 {
-        if velocity > 0. {
-            velocity -= 0.1;
-            if velocity < 0. {
-                velocity = 0.0;
-            } else {
-                velocity -= friction_force * velocity;
-            }
-        } else {
-            debug!("neg velocity: {}", velocity);
-            velocity += 0.3;
-            if velocity > 0. {
-                velocity = 0.0;
-            } else {
-                debug!("neg velocity added: {}", velocity);
-                velocity += friction_force * velocity.abs();
-                debug!("neg velocity added frictionforce: {}", velocity);
-
-            }
-        }
-    }
-    velocity
-}
\ No newline at end of file